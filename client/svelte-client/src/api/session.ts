@@ -1,5 +1,5 @@
-import type {LocalSession, NetworkSession} from "../store/model/session";
-import {SessionState, SessionType} from "../store/model/session";
+import type {LocalSession, MatchFormat, MatchmakingStatus, NetworkSession, RematchStatus, SessionPage, SessionQuery} from "../store/model/session";
+import {isPlayer, SessionMode, SessionState, SessionType, SessionVisibility} from "../store/model/session";
 import type {NetworkSessionEventPayload} from "../store/model/event";
 
 async function createLocalSession(): Promise<LocalSession> {
@@ -15,8 +15,12 @@ async function createLocalSession(): Promise<LocalSession> {
     }
 }
 
-async function createNetworkSession(): Promise<NetworkSession> {
-    return fetch("/pong/api/create_session", {method: 'POST', headers: [['Content-Type', 'application/json']]})
+async function createNetworkSession(mode: SessionMode = SessionMode.HOST, format?: MatchFormat, visibility: SessionVisibility = SessionVisibility.PRIVATE): Promise<NetworkSession> {
+    return fetch("/pong/api/create_session", {
+        method: 'POST',
+        body: JSON.stringify({mode, format, visibility}),
+        headers: [['Content-Type', 'application/json']]
+    })
         .then(sessionResponseHandler)
         .then(session => ({...session, type: SessionType.HOST}) as NetworkSession)
         .catch(err => {
@@ -25,18 +29,102 @@ async function createNetworkSession(): Promise<NetworkSession> {
         });
 }
 
-function createJoinLink(sessionId: string): string {
-    return `${window.location.origin}${window.location.pathname}?join=${sessionId}`;
+// queues for an opponent and polls until the server matched the ticket, the server waits up to 20s per poll.
+async function matchmake(mode: SessionMode = SessionMode.HOST, isCancelled: () => boolean = () => false): Promise<NetworkSession> {
+    let status = await fetch("/pong/api/matchmake", {
+        method: 'POST',
+        body: JSON.stringify({mode}),
+        headers: [['Content-Type', 'application/json']]
+    }).then(matchmakingResponseHandler);
+    while (status.status === 'Queued') {
+        const ticketUrl = `/pong/api/matchmake?ticket_id=${status.ticket_id}`;
+        if (isCancelled()) {
+            await fetch(ticketUrl, {method: 'DELETE'});
+            return Promise.reject('matchmaking cancelled');
+        }
+        status = await fetch(ticketUrl).then(matchmakingResponseHandler);
+    }
+    if (status.status === 'Failed') {
+        console.error(`Matchmaking failed: ${status.reason}`);
+        return Promise.reject(status.reason);
+    }
+    const type = status.role === 'HOST' ? SessionType.HOST : SessionType.PEER;
+    return {...status.session, you: status.actor, token: status.token, type} as NetworkSession;
+}
+
+async function matchmakingResponseHandler(response: Response): Promise<MatchmakingStatus> {
+    if (!response.ok) {
+        return response.text().then(text => Promise.reject(`${response.status}: ${text}`));
+    }
+    return response.json().then(({data}) => {
+        console.debug(`matchmaking status: ${JSON.stringify(data)}`)
+        return data as MatchmakingStatus;
+    });
+}
+
+// accepts a rematch of the session, it is created once the other player accepted as well.
+async function rematch(session: NetworkSession): Promise<RematchStatus> {
+    return fetch("/pong/api/rematch", {
+        method: 'POST',
+        body: JSON.stringify({token: session.token}),
+        headers: [['Content-Type', 'application/json']]
+    })
+        .then(response => {
+            if (!response.ok) {
+                return response.text().then(text => Promise.reject(`${response.status}: ${text}`));
+            }
+            return response.json();
+        })
+        .then(({data}) => data as RematchStatus)
+        .catch(err => {
+            console.error(`Failed to accept rematch: ${err}`);
+            throw(err);
+        });
+}
+
+function rematchSession(status: Extract<RematchStatus, {status: 'Created'}>): NetworkSession {
+    const type = isPlayer(status.actor) ? (status.actor.nr === 1 ? SessionType.HOST : SessionType.PEER) : SessionType.OBSERVER;
+    return {...status.session, you: status.actor, token: status.token, type} as NetworkSession;
+}
+
+async function listSessions(query: SessionQuery = {}): Promise<SessionPage> {
+    const params = Object.entries(query)
+        .filter(([, value]) => value != null)
+        .map(([key, value]) => `${key}=${encodeURIComponent(value)}`)
+        .join('&');
+    return fetch(`/pong/api/sessions?${params}`)
+        .then(response => {
+            if (!response.ok) {
+                return response.text().then(text => Promise.reject(`${response.status}: ${text}`));
+            }
+            return response.json();
+        })
+        .then(({data}) => data as SessionPage)
+        .catch(err => {
+            console.error(`Failed to list sessions: ${err}`);
+            throw(err);
+        });
+}
+
+// sessions are shared with their join code, ids are only used by the lobby.
+function createJoinLink(session: NetworkSession): string {
+    return `${window.location.origin}${window.location.pathname}?join=${session.join_code ?? session.session_id}`;
+}
+
+// 6 characters without 0/O and 1/I/L, session ids are much longer.
+function isJoinCode(value: string): boolean {
+    return /^[A-HJKMNP-Z2-9]{6}$/i.test(value.trim());
 }
 
 function createWatchLink(sessionId: string): string {
     return `${window.location.origin}${window.location.pathname}?watch=${sessionId}`;
 }
 
-async function joinNetworkSession(sessionId): Promise<NetworkSession> {
+async function joinNetworkSession(sessionIdOrCode: string): Promise<NetworkSession> {
+    const body = isJoinCode(sessionIdOrCode) ? {join_code: sessionIdOrCode.trim()} : {session_id: sessionIdOrCode};
     return fetch("/pong/api/join_session", {
         method: 'POST',
-        body: JSON.stringify({session_id: sessionId}),
+        body: JSON.stringify(body),
         headers: [['Content-Type', 'application/json']]
     })
         .then(sessionResponseHandler)
@@ -70,12 +158,19 @@ async function sessionResponseHandler(response: Response): Promise<NetworkSessio
     return response.json().then(({data}) => {
         console.debug(`session action result: ${JSON.stringify(data)}`)
         return data;
-    }).then((event: NetworkSessionEventPayload) => ({you: event.actor, ...event.session}));
+    }).then((event: NetworkSessionEventPayload & {token: string}) => ({you: event.actor, token: event.token, ...event.session}));
+}
+
+export type WireFormat = 'binary' | 'json';
+
+// binary is the default, json can be enabled for debugging with localStorage.setItem('pong.wire_format', 'json').
+function getWireFormat(): WireFormat {
+    return localStorage.getItem('pong.wire_format') === 'json' ? 'json' : 'binary';
 }
 
 async function createEventWebsocket(session: NetworkSession): Promise<WebSocket> {
     console.debug("creating ws for session: ", session)
-    const url = `/pong/ws?session_id=${session.session_id}&actor_id=${session.you.id}&connection_type=${session.type.toLowerCase()}`;
+    const url = `/pong/ws?token=${encodeURIComponent(session.token)}&format=${getWireFormat()}`;
     return createWebsocket(url);
 }
 
@@ -93,6 +188,8 @@ async function createWebsocket(path: string): Promise<WebSocket> {
 const waitForWebsocket = (websocket, retries, success, fail) => {
     if (retries <= 0) {
         console.error("ws not established successfully")
+        websocket.close()
+        fail()
         return
     }
     if (websocket.readyState !== 1) {
@@ -111,7 +208,12 @@ export default {
     createNetworkSession,
     joinNetworkSession,
     watchNetworkSession,
+    matchmake,
+    rematch,
+    rematchSession,
+    listSessions,
     createEventWebsocket,
+    getWireFormat,
     createJoinLink,
     createWatchLink
 }
@@ -1,12 +1,22 @@
-import {FieldWrapper} from "wasm-app";
+import {FieldWrapper, set_log_filter} from "wasm-app";
 import {derived, get, readable, Readable, Writable, writable} from "svelte/store";
 import {getContext, onMount} from "svelte";
-import type {GameObject, GameScore, GameState} from "./model/session";
+import type {GameObject, GameScore, GameState, MatchFormat, SnapshotDelta} from "./model/session";
 import type {Input} from "./model/input";
 import type {Subscriber} from "svelte/types/runtime/store";
 import {subscribe} from "svelte/internal";
 import Fps from "../components/Fps.svelte";
 
+// engine log levels per logger, e.g. 'warn,desync_detector=debug', only applies to engines created afterwards.
+const logFilter = localStorage.getItem('pong.log_filter');
+if (logFilter) {
+    try {
+        set_log_filter(logFilter);
+    } catch (e) {
+        console.error(`invalid log filter ${logFilter}: `, e);
+    }
+}
+
 export const engineCanvas = writable();
 export const engineCtx = writable();
 export const width = writable(800);
@@ -65,6 +75,7 @@ export type GameFieldState = {
     ts: number,
     objects: GameObject[],
     state: GameState,
+    checksum?: number,
     meta: {
         fps: number
     }
@@ -101,14 +112,15 @@ const createFpsStore = (): FpsStore => {
     }
 }
 
-export type GameFieldStore = Readable<GameFieldState> & {tick: (inputs: Input[], dt: number) => void, update: (objects: GameObject[], state: GameState) => void};
+export type GameFieldStore = Readable<GameFieldState> & {tick: (inputs: Input[], dt: number) => void, update: (objects: GameObject[], state: GameState) => void, setMatchFormat: (format: MatchFormat) => void, checkChecksum: (checksum: number) => boolean, snapshotDelta: () => SnapshotDelta, resetSnapshotDelta: () => void, reset: () => void};
 
 function createGameFieldStore(): GameFieldStore {
-    const {subscribe, set} = writable<GameFieldState>({ts: 0, objects: [], state: {score: {player_1: 0, player_2: 0}}, meta: {fps: 0}});
+    const initialState: GameFieldState = {ts: 0, objects: [], state: {score: {player_1: 0, player_2: 0}}, meta: {fps: 0}};
+    const {subscribe, set} = writable<GameFieldState>(initialState);
 
     const fps = createFpsStore();
 
-    const field = FieldWrapper.new();
+    let field = FieldWrapper.new();
 
     function tick(inputs: Input[], dt: number) {
         field.tick(inputs, dt);
@@ -116,11 +128,31 @@ function createGameFieldStore(): GameFieldStore {
         const objects = JSON.parse(field.objects());
         const ts = Date.now();
         const state = JSON.parse(field.game_state()) as GameState;
+        const checksum = field.checksum();
 
         fps.inc();
 
         const meta = {fps: get(fps)};
-        set({objects, ts, state, meta} as GameFieldState);
+        set({objects, ts, state, checksum, meta} as GameFieldState);
+    }
+
+    // objects that changed since the last call, every call is the baseline of the next one.
+    function snapshotDelta(): SnapshotDelta {
+        return JSON.parse(field.snapshot_delta()) as SnapshotDelta;
+    }
+
+    // the next snapshot contains every object again.
+    function resetSnapshotDelta() {
+        field.reset_snapshot_delta();
+    }
+
+    // compares the host checksum with the local simulation, desyncs are logged by the engine.
+    function checkChecksum(checksum: number): boolean {
+        return field.check_checksum(checksum);
+    }
+
+    function setMatchFormat(format: MatchFormat) {
+        field.set_match_format(JSON.stringify(format));
     }
 
     function update(objects: GameObject[], state: GameState) {
@@ -129,10 +161,22 @@ function createGameFieldStore(): GameFieldStore {
         set({objects, ts: Date.now(), state, meta});
     }
 
+    // a fresh field, e.g. for a rematch.
+    function reset() {
+        field.free();
+        field = FieldWrapper.new();
+        set(initialState);
+    }
+
     return {
         subscribe,
         tick,
-        update
+        update,
+        setMatchFormat,
+        checkChecksum,
+        snapshotDelta,
+        resetSnapshotDelta,
+        reset
     }
 }
 
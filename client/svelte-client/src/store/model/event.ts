@@ -1,9 +1,11 @@
 import type {
     GameObject,
-    GameState,
+    GameScore,
     HostSessionSnapshot,
+    MatchFormat,
     NetworkSession,
     PeerSessionSnapshot,
+    RematchStatus,
     Session,
     SessionSnapshot
 } from "./session";
@@ -28,13 +30,37 @@ export type InputEventPayload = {
     inputs: Input[],
     player_id: string,
     ts: number,
+    input_seq?: number,
 }
 
 export type StatusEventPayload = {
     session_id: string,
-    state: GameState
+    score: GameScore,
+    winner?: string,
+    format: MatchFormat
 }
 
+export enum Presence {
+    CONNECTED = 'Connected', LAGGING = 'Lagging', GONE = 'Gone'
+}
+
+export type PresenceEventPayload = {
+    session_id: string,
+    actor_id: string,
+    presence: Presence,
+    ts: number
+}
+
+export enum Emote {
+    WAVE = 'Wave', GOOD_GAME = 'GoodGame', LAUGH = 'Laugh', WOW = 'Wow', OOPS = 'Oops'
+}
+
+export type ChatEventPayload = {
+    session_id: string,
+    actor_id: string,
+    ts: number
+} & ({kind: 'Text', text: string} | {kind: 'Emote', emote: Emote})
+
 export type SessionEvenWrapper = {
     topic: 'session',
     event: SessionEventPayload
@@ -60,7 +86,23 @@ export type StatusEventWrapper = {
     event: StatusEventPayload
 }
 
-export type GameEventWrapper = SessionEvenWrapper | InputEventWrapper | MoveEventWrapper | TickEventWrapper | StatusEventWrapper;
+export type PresenceEventWrapper = {
+    topic: 'presence',
+    event: PresenceEventPayload
+}
+
+// the place of the own actor in a rematch, only sent to the actor itself.
+export type RematchEventWrapper = {
+    topic: 'rematch',
+    event: RematchStatus
+}
+
+export type ChatEventWrapper = {
+    topic: 'chat',
+    event: ChatEventPayload
+}
+
+export type GameEventWrapper = SessionEvenWrapper | InputEventWrapper | MoveEventWrapper | TickEventWrapper | StatusEventWrapper | PresenceEventWrapper | RematchEventWrapper | ChatEventWrapper;
 
 export const isSessionEvent = (event: GameEventWrapper): event is SessionEvenWrapper => {
     return event.topic === 'session';
@@ -81,3 +123,15 @@ export const isTickEvent = (event: GameEventWrapper): event is TickEventWrapper
 export const isStatusEvent = (event: GameEventWrapper): event is StatusEventWrapper => {
     return event.topic === 'status';
 }
+
+export const isPresenceEvent = (event: GameEventWrapper): event is PresenceEventWrapper => {
+    return event.topic === 'presence';
+}
+
+export const isRematchEvent = (event: GameEventWrapper): event is RematchEventWrapper => {
+    return event.topic === 'rematch';
+}
+
+export const isChatEvent = (event: GameEventWrapper): event is ChatEventWrapper => {
+    return event.topic === 'chat';
+}
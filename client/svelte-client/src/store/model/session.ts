@@ -1,9 +1,18 @@
 import type {Input} from "./input";
+import type {Emote} from "./event";
 
 export enum SessionState {
     PENDING = 'PENDING', RUNNING = 'RUNNING', CLOSED = 'CLOSED'
 }
 
+export enum SessionMode {
+    HOST = 'HOST', SERVER = 'SERVER'
+}
+
+export enum SessionVisibility {
+    PUBLIC = 'PUBLIC', PRIVATE = 'PRIVATE'
+}
+
 export enum SessionType {
     LOCAL = 'LOCAL', HOST = 'HOST', PEER = 'PEER', OBSERVER = 'OBSERVER'
 }
@@ -46,10 +55,58 @@ export type LocalSession = {
 
 export type NetworkSession = {
     session_id: string,
+    // short code to join while the session is pending.
+    join_code?: string,
     type: SessionType.HOST | SessionType.PEER | SessionType.OBSERVER,
     state: SessionState,
+    mode: SessionMode,
+    format?: MatchFormat,
+    visibility?: SessionVisibility,
     players: Player[],
-    you: Actor
+    you: Actor,
+    // authorises the websocket connection of `you`.
+    token: string,
+    // players that accepted a rematch and the links of a series.
+    rematch_accepted_by?: string[],
+    rematch_of?: string,
+    rematch?: string
+}
+
+// a session as listed in the lobby.
+export type SessionSummary = {
+    session_id: string,
+    state: SessionState,
+    mode: SessionMode,
+    format: MatchFormat,
+    players: number,
+    observers: number,
+    has_free_slot: boolean,
+    observable: boolean
+}
+
+export type SessionPage = {
+    sessions: SessionSummary[],
+    page: number,
+    page_size: number,
+    total: number
+}
+
+export type MatchmakingStatus =
+    { status: 'Queued', ticket_id: string }
+    | { status: 'Matched', ticket_id: string, role: 'HOST' | 'PEER', session: NetworkSession, actor: Actor, token: string }
+    | { status: 'Failed', ticket_id: string, reason: string }
+
+export type RematchStatus =
+    { status: 'Pending', session_id: string, accepted_by: string[] }
+    | { status: 'Created', session: NetworkSession, actor: Actor, token: string }
+
+export type SessionQuery = {
+    state?: SessionState,
+    has_free_slot?: boolean,
+    observable?: boolean,
+    sort?: 'created' | '-created' | 'observers' | '-observers',
+    page?: number,
+    page_size?: number
 }
 
 export type Session = LocalSession | NetworkSession;
@@ -62,14 +119,42 @@ export function isLocalSession(session: Session): session is LocalSession {
     return !!session.type && session.type === SessionType.LOCAL
 }
 
-export type GameScore = {
+export type PlayerScore = {
     player_1: number,
     player_2: number,
 }
 
+export type GameScore = PlayerScore & {
+    games?: PlayerScore,
+    sets?: PlayerScore,
+}
+
+export type MatchFormat =
+    { format: 'FirstTo', points: number, win_by_two: boolean }
+    | { format: 'BestOf', points: number, win_by_two: boolean, games: number, sets: number }
+    | { format: 'Timed', duration_sec: number }
+
+export type GoalStat = {
+    player: string,
+    time: number
+}
+
+export type MatchStats = {
+    rallies: number,
+    current_rally: number,
+    longest_rally: number,
+    hits: GameScore,
+    top_ball_speed: number,
+    goals: GoalStat[],
+    time_in_play: number,
+    last_hit?: string
+}
+
 export type GameState = {
     score: GameScore,
-    winner?: string
+    winner?: string,
+    sudden_death?: boolean,
+    stats?: MatchStats
 }
 
 export type HostSessionSnapshot = {
@@ -78,14 +163,31 @@ export type HostSessionSnapshot = {
     objects: GameObject[],
     state: GameState,
     player_id: string,
-    ts: number
+    ts: number,
+    checksum?: number,
+    seq?: number,
+    // seq of the snapshot the objects are a delta to, absent if the snapshot contains every object.
+    baseline?: number,
+    // last input_seq of each player that went into this tick.
+    acked_inputs?: {[player_id: string]: number}
+}
+
+export type SnapshotDelta = {
+    seq: number,
+    baseline?: number,
+    objects: GameObject[]
+}
+
+export const isHostSessionSnapshot = (snapshot: SessionSnapshot): snapshot is HostSessionSnapshot => {
+    return !!(snapshot as HostSessionSnapshot).objects;
 }
 
 export type PeerSessionSnapshot = {
     session_id: string,
     inputs: Input[],
     player_id: string,
-    ts: number
+    ts: number,
+    input_seq?: number
 }
 
 export type SessionSnapshot = HostSessionSnapshot | PeerSessionSnapshot;
@@ -97,13 +199,25 @@ export type Heartbeat = {
 }
 
 export enum MessageType {
-    Snapshot = "SessionSnapshot", Heartbeat = "HeartBeat"
+    Snapshot = "SessionSnapshot", Heartbeat = "HeartBeat", Rematch = "Rematch", Chat = "Chat", Emote = "Emote"
 }
 
+// longer chat messages are rejected by the server.
+export const MAX_CHAT_MESSAGE_LENGTH = 200;
+
 export type Message = {
     msg_type: MessageType.Snapshot,
     payload: SessionSnapshot
 } | {
     msg_type: MessageType.Heartbeat,
     payload: Heartbeat
+} | {
+    msg_type: MessageType.Rematch,
+    payload: {session_id: string}
+} | {
+    msg_type: MessageType.Chat,
+    payload: {session_id: string, text: string}
+} | {
+    msg_type: MessageType.Emote,
+    payload: {session_id: string, emote: Emote}
 }
@@ -1,6 +1,8 @@
 import {derived, get, Readable, readable, Unsubscriber, writable} from "svelte/store";
 import api from "../api/session";
+import {decode_message, encode_message} from "wasm-app";
 import type {
+    GameObject,
     HostSessionSnapshot,
     LocalSession,
     Message,
@@ -9,10 +11,10 @@ import type {
     Session,
     SessionSnapshot
 } from "./model/session";
-import {isLocalSession, isNetworkSession, MessageType, SessionState, SessionType} from "./model/session";
+import {isHostSessionSnapshot, isLocalSession, isNetworkSession, isPlayer, MessageType, SessionMode, SessionState, SessionType, SessionVisibility} from "./model/session";
 import type {NetworkStore} from "./network";
-import type {GameEventWrapper, InputEventPayload, SessionEventPayload} from "./model/event";
-import {isSessionEvent, isTickEvent} from "./model/event";
+import type {ChatEventPayload, Emote, GameEventWrapper, InputEventPayload, Presence, SessionEventPayload} from "./model/event";
+import {isChatEvent, isPresenceEvent, isRematchEvent, isSessionEvent, isTickEvent} from "./model/event";
 import {getPlayerKeyboardInputs, playerKeyboardInputs} from "./input";
 import type {Subscriber} from "svelte/types/runtime/store";
 import {combined} from "./utils";
@@ -20,13 +22,19 @@ import type {Input} from "./model/input";
 
 const sessionStore = writable<Session>(null);
 
+// players of running sessions reconnect after this delay, the server keeps their session for a grace period (30s by default).
+const RECONNECT_DELAY_MS = 1_000;
+const MAX_RECONNECT_ATTEMPTS = 30;
+
 function createNetworkEvents() {
     const {subscribe, set} = writable<GameEventWrapper[]>([]);
 
     const websocket = writable<WebSocket>(null);
     const sessionId = writable<string>(null);
     const playerId = writable<string>(null);
-    const lastSnapshot = writable<SessionSnapshot>(null);
+    // established websockets, snapshots sent while the previous one was down are lost.
+    const connections = writable<number>(0);
+    let reconnectAttempts = 0;
 
     const unsubscribeSession = sessionStore.subscribe(session => {
         if (!session || isLocalSession(session)) {
@@ -35,44 +43,84 @@ function createNetworkEvents() {
         if (get(sessionId) === session.session_id) {
             return;
         }
+        // e.g. after a rematch, the connection of the previous session is not needed anymore.
+        const previousWebsocket = get(websocket);
         sessionId.set(session.session_id);
         playerId.set(session.you.id);
+        previousWebsocket?.close();
+        connect(session);
+    })
+
+    function reconnectLater() {
+        const session = get(sessionStore);
+        if (!isNetworkSession(session) || !isPlayer(session.you) || session.state !== SessionState.RUNNING) {
+            return;
+        }
+        if (reconnectAttempts >= MAX_RECONNECT_ATTEMPTS) {
+            console.error(`giving up to reconnect ws after ${reconnectAttempts} attempts`);
+            return;
+        }
+        reconnectAttempts++;
+        console.log(`reconnecting ws in ${RECONNECT_DELAY_MS}ms`);
+        setTimeout(() => connect(session), RECONNECT_DELAY_MS);
+    }
+
+    function connect(session: NetworkSession) {
         console.log("creating ws to receive/send websocket events for session: ", JSON.stringify(session))
         api.createEventWebsocket(session).then(ws => {
             console.log("ws successfully established: ", ws)
+            reconnectAttempts = 0;
 
             ws.onopen = () => {
                 console.debug("ws successfully opened")
             }
+            ws.binaryType = 'arraybuffer';
             ws.onmessage = event => {
                 console.debug("Received event: ", event)
-                let events = JSON.parse(event.data);
-                // TODO: Hotfix, would be better to have clean serialization in the backend...
-                events = events.map(({event, ...rest}) => ({...rest, event: JSON.parse(event)}))
+                let events;
+                if (event.data instanceof ArrayBuffer) {
+                    // binary batches already contain the events as values.
+                    events = JSON.parse(decode_message(new Uint8Array(event.data)));
+                } else {
+                    events = JSON.parse(event.data);
+                    // TODO: Hotfix, would be better to have clean serialization in the backend...
+                    events = events.map(({event, ...rest}) => ({...rest, event: JSON.parse(event)}))
+                }
                 console.debug("Parsed events: ", events)
                 set(events);
+                const rematch = events.find(isRematchEvent);
+                if (rematch && rematch.event.status === 'Created') {
+                    console.log("switching to rematch: ", rematch.event.session.session_id);
+                    sessionStore.set(api.rematchSession(rematch.event));
+                }
             }
             ws.onerror = err => {
                 console.error("ws error: ", err)
             }
             ws.onclose = event => {
                 console.error("ws closed: ", event)
+                if (get(sessionId) !== session.session_id) {
+                    return;
+                }
+                websocket.set(null);
+                reconnectLater();
             }
 
             websocket.set(ws);
+            connections.update(count => count + 1);
+        }).catch(() => {
+            console.error("failed to establish ws");
+            reconnectLater();
         });
-    })
+    }
 
+    // the server derives the presence of every actor from the heartbeats, so they are sent even while snapshots are.
     const interval = setInterval(() => {
         const cachedSessionId = get(sessionId);
         if (!cachedSessionId) {
             return;
         }
-        const last = get(lastSnapshot);
         const now = Date.now();
-        if (last && now - last.ts < 1_000) {
-            return
-        }
         console.debug("sending heartbeat")
         const heartbeat: Message = {msg_type: MessageType.Heartbeat, payload: {session_id: cachedSessionId, player_id: get(playerId), ts: now}};
         sendMessage(heartbeat);
@@ -84,15 +132,53 @@ function createNetworkEvents() {
             return;
         }
         console.debug("producing message to ws: ", message);
+        if (api.getWireFormat() === 'binary') {
+            ws.send(encode_message(JSON.stringify(message)));
+            return;
+        }
         // TODO: Hotfix, double serialize to ease deserialization on server.
         ws.send(JSON.stringify({msg_type: message.msg_type, payload: JSON.stringify(message.payload)}));
     }
 
     function produce(snapshot: SessionSnapshot) {
-        lastSnapshot.set(snapshot);
         sendMessage({msg_type: MessageType.Snapshot, payload: snapshot});
     }
 
+    // both players have to accept, the rematch then arrives as `rematch` event for every actor.
+    function requestRematch() {
+        const session = get(sessionStore);
+        if (!isNetworkSession(session)) {
+            return;
+        }
+        if (get(websocket)) {
+            sendMessage({msg_type: MessageType.Rematch, payload: {session_id: session.session_id}});
+            return;
+        }
+        // without a connection, e.g. once the session was closed, the endpoint is used.
+        api.rematch(session).then(status => {
+            if (status.status === 'Created') {
+                sessionStore.set(api.rematchSession(status));
+            }
+        });
+    }
+
+    // every actor can chat, the server drops messages that are too long or too frequent.
+    function sendChat(text: string) {
+        const cachedSessionId = get(sessionId);
+        if (!cachedSessionId || !text.trim()) {
+            return;
+        }
+        sendMessage({msg_type: MessageType.Chat, payload: {session_id: cachedSessionId, text}});
+    }
+
+    function sendEmote(emote: Emote) {
+        const cachedSessionId = get(sessionId);
+        if (!cachedSessionId) {
+            return;
+        }
+        sendMessage({msg_type: MessageType.Emote, payload: {session_id: cachedSessionId, emote}});
+    }
+
     const customSubscribe = (run: Subscriber<GameEventWrapper[]>, invalidate): Unsubscriber => {
         const unsubscribe = subscribe(run, invalidate);
         return () => {
@@ -104,12 +190,20 @@ function createNetworkEvents() {
 
     return {
         subscribe: customSubscribe,
-        produce
+        produce,
+        requestRematch,
+        sendChat,
+        sendEmote,
+        connections: {subscribe: connections.subscribe}
     }
 }
 
 export type NetworkEventStore = Readable<GameEventWrapper[]> & {
-    produce: (snapshot: SessionSnapshot) => void
+    produce: (snapshot: SessionSnapshot) => void,
+    requestRematch: () => void,
+    sendChat: (text: string) => void,
+    sendEmote: (emote: Emote) => void,
+    connections: Readable<number>
 }
 
 export const networkEvents: NetworkEventStore = createNetworkEvents();
@@ -130,6 +224,7 @@ export const networkSessionStateEvents = readable<SessionEventPayload[]>([], set
         const session: Session = {
             ...(latestSessionEvent.session as NetworkSession),
             you: currentSession.you,
+            token: currentSession.token,
             type: currentSession.type
         }
         console.debug("updating current session: ", session)
@@ -141,6 +236,44 @@ export const networkSessionStateEvents = readable<SessionEventPayload[]>([], set
     }
 });
 
+// latest presence per actor id, as reported by the server.
+export const networkPresence = readable<{[actor_id: string]: Presence}>({}, set => {
+    let presence = {};
+    const unsub = networkEvents.subscribe(events => {
+        const presenceEvents = events.filter(isPresenceEvent).map(({event}) => event);
+        if (!presenceEvents.length) {
+            return;
+        }
+        presence = presenceEvents.reduce((acc, {actor_id, presence}) => ({...acc, [actor_id]: presence}), presence);
+        set(presence);
+    })
+
+    return () => {
+        unsub();
+    }
+});
+
+// only the latest chat messages of the current session are kept.
+const MAX_CHAT_HISTORY = 50;
+
+export const networkChat = readable<ChatEventPayload[]>([], set => {
+    let messages: ChatEventPayload[] = [];
+    const unsub = networkEvents.subscribe(events => {
+        const chatEvents = events.filter(isChatEvent).map(({event}) => event);
+        if (!chatEvents.length) {
+            return;
+        }
+        // e.g. after a rematch, the chat starts over.
+        const sameSession = messages.length && messages[0].session_id === chatEvents[0].session_id;
+        messages = [...(sameSession ? messages : []), ...chatEvents].slice(-MAX_CHAT_HISTORY);
+        set(messages);
+    })
+
+    return () => {
+        unsub();
+    }
+});
+
 export type NetworkTickEventState = {
     hasNext: boolean;
     events: HostSessionSnapshot[] | PeerSessionSnapshot[]
@@ -149,8 +282,21 @@ export type NetworkTickEventState = {
 const createNetworkTickEventStore = function() {
     const ticks = writable<NetworkTickEventState>({hasNext: false, events: []});
 
+    // host ticks only contain the objects that changed, merged in arrival order they give the full state.
+    let knownObjects = new Map<string, GameObject>();
+    function withAllObjects(tick: SessionSnapshot): SessionSnapshot {
+        if (!isHostSessionSnapshot(tick)) {
+            return tick;
+        }
+        if (tick.baseline == null) {
+            knownObjects = new Map();
+        }
+        tick.objects.forEach(object => knownObjects.set(object.id, object));
+        return {...tick, objects: [...knownObjects.values()]};
+    }
+
     const unsubSessionEvents = networkEvents.subscribe($sessionEvents => {
-        const tickEvents = $sessionEvents.filter(isTickEvent).map(({event}) => event);
+        const tickEvents = $sessionEvents.filter(isTickEvent).map(({event}) => withAllObjects(event));
         ticks.update(({events}) => {
             const updatedEvents = [...events, ...tickEvents];
             return {
@@ -194,9 +340,15 @@ export const networkTickEvents = createNetworkTickEventStore();
 
 const networkInputEvents = derived(networkTickEvents, ($networkTickEvents: NetworkTickEventState) => $networkTickEvents.events.map((tick: SessionSnapshot): InputEventPayload => {
     const inputs = tick.inputs;
-    return {inputs, player_id: tick.player_id, ts: tick.ts, session_id: tick.session_id}
+    const input_seq = isHostSessionSnapshot(tick) ? undefined : tick.input_seq;
+    return {inputs, player_id: tick.player_id, ts: tick.ts, session_id: tick.session_id, input_seq}
 }));
 
+// latest input_seq of every player, the host echoes them so that peers can reconcile their prediction.
+export const networkInputSeqs = derived(networkInputEvents, $networkInputEvents => $networkInputEvents.reduce((seqs, {player_id, input_seq}) => {
+    return input_seq == null ? seqs : {...seqs, [player_id]: input_seq};
+}, {} as {[player_id: string]: number}));
+
 const getPlayerNetworkInputEvents = (player_nr: number): Readable<Input[]> => derived(networkInputEvents, $networkInputEvents => {
     const session = get(sessionStore);
     if (!isNetworkSession(session)) {
@@ -257,7 +409,7 @@ export type SessionStore = NetworkStore & {
     session?: Session
 }
 
-export const networkSession = (type: SessionType.HOST | SessionType.PEER | SessionType.OBSERVER, sessionId?: string) => readable<SessionStore>(null, function (set) {
+export const networkSession = (type: SessionType.HOST | SessionType.PEER | SessionType.OBSERVER, sessionId?: string, mode?: SessionMode, visibility?: SessionVisibility) => readable<SessionStore>(null, function (set) {
     function sessionCreator(fn) {
         set({loading: true});
         fn().then(session => {
@@ -275,7 +427,7 @@ export const networkSession = (type: SessionType.HOST | SessionType.PEER | Sessi
 
     switch (type) {
         case SessionType.HOST:
-            sessionCreator(() => api.createNetworkSession());
+            sessionCreator(() => api.createNetworkSession(mode, undefined, visibility));
             break;
         case SessionType.PEER:
             sessionCreator(() => api.joinNetworkSession(sessionId));
@@ -291,3 +443,30 @@ export const networkSession = (type: SessionType.HOST | SessionType.PEER | Sessi
         unsubscribe();
     }
 })
+
+export const matchmadeSession = (mode?: SessionMode) => readable<SessionStore>(null, function (set) {
+    // leaving before a match was found takes the ticket out of the queue.
+    let cancelled = false;
+    set({loading: true});
+    api.matchmake(mode, () => cancelled).then(session => {
+        set({loading: false, session});
+        sessionStore.set(session);
+    }).catch(e => {
+        if (cancelled) {
+            return;
+        }
+        set({loading: false, error: {value: e, at: performance.now()}});
+        sessionStore.set(null);
+    })
+
+    const unsubscribe = sessionStore.subscribe(session => {
+        if (session) {
+            set({loading: false, session})
+        }
+    })
+
+    return () => {
+        cancelled = true;
+        unsubscribe();
+    }
+})
@@ -1,14 +1,19 @@
 mod utils;
 
-use pong::game_field::{Field, Input, InputType};
+use pong::codec::codec;
+use pong::game_field::{Field, GameState, Input, InputType};
 use pong::game_object::game_object::{GameObject};
 use pong::geom::shape::ShapeType;
+use pong::interpolation::interpolation::{InterpolationBuffer, InterpolationConfig, ObjectState};
 use pong::pong::pong_events::{NoopPongEventWriter};
-use pong::utils::utils::{DefaultLoggerFactory, Logger};
+use pong::pong::pong_delta::DeltaEncoder;
+use pong::pong::pong_sync::DesyncDetector;
+use pong::reconciliation::reconciliation::PeerPredictor;
+use pong::utils::utils::{DefaultLoggerFactory, LogFilter, LogLevel, LogRecord, Logger, LoggerFactory};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::cell::RefCell;
-use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
 use wasm_bindgen::prelude::*;
 
 extern crate serde_json;
@@ -42,8 +47,8 @@ pub struct GameObjectDTO {
 }
 
 impl GameObjectDTO {
-    pub fn from(obj: &Rc<RefCell<Box<dyn GameObject>>>) -> GameObjectDTO {
-        let obj = RefCell::borrow(obj);
+    pub fn from(obj: &Arc<RwLock<Box<dyn GameObject>>>) -> GameObjectDTO {
+        let obj = obj.read().unwrap();
 
         let pos = obj.pos();
         let orientation = obj.orientation();
@@ -122,16 +127,20 @@ impl InputDTO {
 #[wasm_bindgen]
 pub struct FieldWrapper {
     field: Field,
+    desync_detector: DesyncDetector,
+    delta_encoder: DeltaEncoder,
 }
 
 #[wasm_bindgen]
 impl FieldWrapper {
     pub fn new() -> FieldWrapper {
-        let field = Field::new(
-            DefaultLoggerFactory::new(Box::new(WasmLogger::root())),
-            NoopPongEventWriter::new(),
-        );
-        FieldWrapper { field }
+        let logger_factory = logger_factory();
+        let desync_detector = DesyncDetector::new(&*logger_factory);
+        let mut field = Field::new(logger_factory, NoopPongEventWriter::new());
+        if cfg!(debug_assertions) {
+            field.enable_invariant_checks();
+        }
+        FieldWrapper { field, desync_detector, delta_encoder: DeltaEncoder::new() }
     }
 
     pub fn width(&self) -> u16 {
@@ -163,10 +172,45 @@ impl FieldWrapper {
         serde_json::to_string(&json).unwrap()
     }
 
+    /// Objects that changed since the previous call, to be sent as host snapshot.
+    pub fn snapshot_delta(&mut self) -> String {
+        let delta = self.delta_encoder.encode(&mut self.field);
+        let objects = delta.objs.into_iter().map(GameObjectDTO::from).collect::<Vec<GameObjectDTO>>();
+        let json = json!({"seq": delta.seq, "baseline": delta.baseline, "objects": objects});
+        serde_json::to_string(&json).unwrap()
+    }
+
+    pub fn reset_snapshot_delta(&mut self) {
+        self.delta_encoder.reset();
+    }
+
     pub fn game_state(&self) -> String {
         serde_json::to_string(&self.field.game_state).unwrap()
     }
 
+    /// Invariant violations since the previous call, only checked in debug builds.
+    pub fn take_invariant_violations(&mut self) -> String {
+        serde_json::to_string(&self.field.take_invariant_violations()).unwrap()
+    }
+
+    pub fn checksum(&self) -> u32 {
+        self.field.checksum()
+    }
+
+    /// Compares the checksum of the host with the local simulation, desyncs are logged to the console.
+    pub fn check_checksum(&mut self, remote_checksum: u32) -> bool {
+        self.desync_detector.check(&self.field, remote_checksum).is_none()
+    }
+
+    pub fn set_match_format(&mut self, format_json: String) {
+        self.field.match_format = serde_json::from_str(&format_json).unwrap();
+    }
+
+    pub fn predict_ball_path(&self, ball_id: String, horizon: f64) -> String {
+        let path = self.field.predict_ball_path(&ball_id, horizon);
+        serde_json::to_string(&path).unwrap()
+    }
+
     pub fn set_dimensions(&mut self, width_js: JsValue, height_js: JsValue) {
         let width = width_js.as_f64().unwrap();
         let height = height_js.as_f64().unwrap();
@@ -174,6 +218,98 @@ impl FieldWrapper {
     }
 }
 
+#[wasm_bindgen]
+pub struct InterpolationBufferWrapper {
+    buffer: InterpolationBuffer,
+}
+
+#[wasm_bindgen]
+impl InterpolationBufferWrapper {
+    pub fn new(render_delay_ms: f64) -> InterpolationBufferWrapper {
+        let config = InterpolationConfig { render_delay_ms, ..InterpolationConfig::default() };
+        InterpolationBufferWrapper { buffer: InterpolationBuffer::new(config) }
+    }
+
+    pub fn set_render_delay(&mut self, render_delay_ms: f64) {
+        self.buffer.set_render_delay(render_delay_ms);
+    }
+
+    /// Adds the objects of a host snapshot, ts is the host time of the snapshot, local_ts the time it arrived.
+    pub fn push(&mut self, ts: f64, local_ts: f64, objects_json: String) {
+        let objects = serde_json::from_str::<Vec<ObjectState>>(&objects_json).unwrap();
+        self.buffer.push(ts, local_ts, objects);
+    }
+
+    pub fn sample(&mut self, now: f64) -> String {
+        serde_json::to_string(&self.buffer.sample(now)).unwrap()
+    }
+}
+
+#[wasm_bindgen]
+pub struct PeerPredictorWrapper {
+    predictor: PeerPredictor,
+    logger: Box<dyn Logger>,
+}
+
+#[wasm_bindgen]
+impl PeerPredictorWrapper {
+    pub fn new(paddle_id: String) -> PeerPredictorWrapper {
+        let logger_factory = logger_factory();
+        let logger = logger_factory.get("peer_predictor");
+        let field = Field::new(logger_factory, NoopPongEventWriter::new());
+        PeerPredictorWrapper { predictor: PeerPredictor::new(field, &paddle_id), logger }
+    }
+
+    /// Moves the own paddle, returns the sequence number the inputs are sent with.
+    pub fn predict(&mut self, inputs_js: JsValue, ms_diff_js: JsValue) -> u32 {
+        let input_dtos: Vec<InputDTO> = inputs_js.into_serde().unwrap();
+        let inputs = input_dtos.into_iter().map(|i| i.to_input()).collect::<Vec<Input>>();
+        self.predictor.predict(inputs, ms_diff_js.as_f64().unwrap()) as u32
+    }
+
+    /// Resets the prediction to a host snapshot, acked_seq is the last input sequence number the host applied.
+    pub fn reconcile(&mut self, acked_seq: Option<u32>, objects_json: String, state_json: String) {
+        let objects = serde_json::from_str::<Vec<ObjectState>>(&objects_json).unwrap();
+        let state = serde_json::from_str::<GameState>(&state_json).unwrap();
+        if let Err(e) = self.predictor.reconcile(acked_seq.map(|s| s as u64), &objects, &state) {
+            self.logger.warn("failed to reconcile prediction", &[("error", &e)]);
+        }
+    }
+
+    pub fn paddle(&self) -> String {
+        serde_json::to_string(&self.predictor.paddle()).unwrap()
+    }
+}
+
+/// Encodes a json message in the binary wire format of the websocket connection.
+#[wasm_bindgen]
+pub fn encode_message(json: String) -> Result<Vec<u8>, JsValue> {
+    let value = serde_json::from_str::<serde_json::Value>(&json).map_err(|e| JsValue::from(e.to_string()))?;
+    Ok(codec::encode_value(&value))
+}
+
+/// Decodes a binary websocket message to json.
+#[wasm_bindgen]
+pub fn decode_message(bytes: Vec<u8>) -> Result<String, JsValue> {
+    let value = codec::decode_value(&bytes).map_err(JsValue::from)?;
+    Ok(value.to_string())
+}
+
+static LOG_FILTER: RwLock<Option<LogFilter>> = RwLock::new(None);
+
+/// Sets the log filter of engines created afterwards, e.g. `warn,desync_detector=debug`.
+#[wasm_bindgen]
+pub fn set_log_filter(filter: String) -> Result<(), JsValue> {
+    let filter = LogFilter::from_str(&filter).map_err(JsValue::from)?;
+    *LOG_FILTER.write().unwrap() = Some(filter);
+    Ok(())
+}
+
+fn logger_factory() -> Box<dyn LoggerFactory> {
+    let filter = LOG_FILTER.read().unwrap().clone().unwrap_or_default();
+    DefaultLoggerFactory::filtered(Box::new(WasmLogger::root()), filter)
+}
+
 #[derive(Clone)]
 pub struct WasmLogger {
     name: String,
@@ -196,7 +332,13 @@ impl Logger for WasmLogger {
         self.name = String::from(name);
     }
 
-    fn log(&self, msg: &str) {
-        log!("[{}] {}", self.name, msg)
+    fn write(&self, record: &LogRecord) {
+        let line = JsValue::from(format!("[{}] {}", self.name, record));
+        match record.level {
+            LogLevel::Trace | LogLevel::Debug => web_sys::console::debug_1(&line),
+            LogLevel::Info => web_sys::console::info_1(&line),
+            LogLevel::Warn => web_sys::console::warn_1(&line),
+            LogLevel::Error => web_sys::console::error_1(&line),
+        }
     }
 }
@@ -0,0 +1,346 @@
+pub mod codec {
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+    use serde_json::{Map, Number, Value};
+    use std::str::FromStr;
+
+    /// Encoding of the messages of a websocket connection, negotiated when the connection is opened.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub enum WireFormat {
+        // human readable, kept for debugging.
+        #[default]
+        Json,
+        // MessagePack, see https://github.com/msgpack/msgpack/blob/master/spec.md
+        Binary,
+    }
+
+    impl FromStr for WireFormat {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s.to_lowercase().as_str() {
+                "json" => Ok(WireFormat::Json),
+                "binary" | "msgpack" => Ok(WireFormat::Binary),
+                _ => Err(format!("unknown wire format: {}", s)),
+            }
+        }
+    }
+
+    pub fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, String> {
+        let value = serde_json::to_value(value).map_err(|e| e.to_string())?;
+        Ok(encode_value(&value))
+    }
+
+    pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, String> {
+        let value = decode_value(bytes)?;
+        serde_json::from_value(value).map_err(|e| e.to_string())
+    }
+
+    pub fn encode_value(value: &Value) -> Vec<u8> {
+        let mut buf = vec![];
+        write_value(&mut buf, value);
+        buf
+    }
+
+    pub fn decode_value(bytes: &[u8]) -> Result<Value, String> {
+        let mut reader = Reader { bytes, pos: 0, depth: 0 };
+        let value = reader.read_value()?;
+        if reader.pos != bytes.len() {
+            return Err(format!("{} trailing bytes after value", bytes.len() - reader.pos));
+        }
+        Ok(value)
+    }
+
+    fn write_value(buf: &mut Vec<u8>, value: &Value) {
+        match value {
+            Value::Null => buf.push(0xc0),
+            Value::Bool(false) => buf.push(0xc2),
+            Value::Bool(true) => buf.push(0xc3),
+            Value::Number(n) => write_number(buf, n),
+            Value::String(s) => {
+                write_len(buf, s.len(), (0xa0, 31), 0xd9, 0xda, 0xdb);
+                buf.extend_from_slice(s.as_bytes());
+            }
+            Value::Array(values) => {
+                write_len(buf, values.len(), (0x90, 15), 0xdc, 0xdc, 0xdd);
+                for value in values {
+                    write_value(buf, value);
+                }
+            }
+            Value::Object(map) => {
+                write_len(buf, map.len(), (0x80, 15), 0xde, 0xde, 0xdf);
+                for (key, value) in map {
+                    write_value(buf, &Value::String(key.clone()));
+                    write_value(buf, value);
+                }
+            }
+        }
+    }
+
+    fn write_number(buf: &mut Vec<u8>, n: &Number) {
+        if let Some(u) = n.as_u64() {
+            match u {
+                0..=0x7f => buf.push(u as u8),
+                0x80..=0xff => buf.extend_from_slice(&[0xcc, u as u8]),
+                0x100..=0xffff => {
+                    buf.push(0xcd);
+                    buf.extend_from_slice(&(u as u16).to_be_bytes());
+                }
+                0x10000..=0xffff_ffff => {
+                    buf.push(0xce);
+                    buf.extend_from_slice(&(u as u32).to_be_bytes());
+                }
+                _ => {
+                    buf.push(0xcf);
+                    buf.extend_from_slice(&u.to_be_bytes());
+                }
+            }
+        } else if let Some(i) = n.as_i64() {
+            // only negative numbers end up here.
+            if i >= -32 {
+                buf.push(i as i8 as u8);
+            } else if i >= i8::MIN as i64 {
+                buf.extend_from_slice(&[0xd0, i as i8 as u8]);
+            } else if i >= i16::MIN as i64 {
+                buf.push(0xd1);
+                buf.extend_from_slice(&(i as i16).to_be_bytes());
+            } else if i >= i32::MIN as i64 {
+                buf.push(0xd2);
+                buf.extend_from_slice(&(i as i32).to_be_bytes());
+            } else {
+                buf.push(0xd3);
+                buf.extend_from_slice(&i.to_be_bytes());
+            }
+        } else {
+            let f = n.as_f64().unwrap();
+            // most coordinates survive the trip through f32, which halves their size.
+            if (f as f32) as f64 == f {
+                buf.push(0xca);
+                buf.extend_from_slice(&(f as f32).to_be_bytes());
+            } else {
+                buf.push(0xcb);
+                buf.extend_from_slice(&f.to_be_bytes());
+            }
+        }
+    }
+
+    // strings, arrays and maps only differ in their markers, there is no 8 bit length for arrays and maps.
+    fn write_len(buf: &mut Vec<u8>, len: usize, fix: (u8, usize), marker_8: u8, marker_16: u8, marker_32: u8) {
+        let (fix_marker, fix_max) = fix;
+        if len <= fix_max {
+            buf.push(fix_marker | len as u8);
+        } else if len <= 0xff && marker_8 != marker_16 {
+            buf.extend_from_slice(&[marker_8, len as u8]);
+        } else if len <= 0xffff {
+            buf.push(marker_16);
+            buf.extend_from_slice(&(len as u16).to_be_bytes());
+        } else {
+            buf.push(marker_32);
+            buf.extend_from_slice(&(len as u32).to_be_bytes());
+        }
+    }
+
+    // nesting limit of arrays and maps, like serde_json, deeper input would overflow the stack.
+    const MAX_DEPTH: usize = 128;
+
+    struct Reader<'a> {
+        bytes: &'a [u8],
+        pos: usize,
+        depth: usize,
+    }
+
+    impl<'a> Reader<'a> {
+        fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+            let end = match self.pos.checked_add(n) {
+                Some(end) if end <= self.bytes.len() => end,
+                _ => return Err(format!("unexpected end of input at byte {}", self.pos)),
+            };
+            let slice = &self.bytes[self.pos..end];
+            self.pos = end;
+            Ok(slice)
+        }
+
+        fn take_array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+            let mut array = [0; N];
+            array.copy_from_slice(self.take(N)?);
+            Ok(array)
+        }
+
+        fn read_value(&mut self) -> Result<Value, String> {
+            let marker = self.take(1)?[0];
+            let value = match marker {
+                0x00..=0x7f => Value::from(marker),
+                0x80..=0x8f => self.read_map((marker & 0x0f) as usize)?,
+                0x90..=0x9f => self.read_array((marker & 0x0f) as usize)?,
+                0xa0..=0xbf => self.read_str((marker & 0x1f) as usize)?,
+                0xc0 => Value::Null,
+                0xc2 => Value::Bool(false),
+                0xc3 => Value::Bool(true),
+                0xca => float(f32::from_be_bytes(self.take_array()?) as f64)?,
+                0xcb => float(f64::from_be_bytes(self.take_array()?))?,
+                0xcc => Value::from(self.take(1)?[0]),
+                0xcd => Value::from(u16::from_be_bytes(self.take_array()?)),
+                0xce => Value::from(u32::from_be_bytes(self.take_array()?)),
+                0xcf => Value::from(u64::from_be_bytes(self.take_array()?)),
+                0xd0 => Value::from(self.take(1)?[0] as i8),
+                0xd1 => Value::from(i16::from_be_bytes(self.take_array()?)),
+                0xd2 => Value::from(i32::from_be_bytes(self.take_array()?)),
+                0xd3 => Value::from(i64::from_be_bytes(self.take_array()?)),
+                0xd9 => {
+                    let len = self.take(1)?[0] as usize;
+                    self.read_str(len)?
+                }
+                0xda => {
+                    let len = u16::from_be_bytes(self.take_array()?) as usize;
+                    self.read_str(len)?
+                }
+                0xdb => {
+                    let len = u32::from_be_bytes(self.take_array()?) as usize;
+                    self.read_str(len)?
+                }
+                0xdc => {
+                    let len = u16::from_be_bytes(self.take_array()?) as usize;
+                    self.read_array(len)?
+                }
+                0xdd => {
+                    let len = u32::from_be_bytes(self.take_array()?) as usize;
+                    self.read_array(len)?
+                }
+                0xde => {
+                    let len = u16::from_be_bytes(self.take_array()?) as usize;
+                    self.read_map(len)?
+                }
+                0xdf => {
+                    let len = u32::from_be_bytes(self.take_array()?) as usize;
+                    self.read_map(len)?
+                }
+                0xe0..=0xff => Value::from(marker as i8),
+                _ => return Err(format!("unsupported marker {:#x} at byte {}", marker, self.pos - 1)),
+            };
+            Ok(value)
+        }
+
+        fn read_str(&mut self, len: usize) -> Result<Value, String> {
+            let bytes = self.take(len)?;
+            let s = std::str::from_utf8(bytes).map_err(|e| e.to_string())?;
+            Ok(Value::String(s.to_owned()))
+        }
+
+        fn read_array(&mut self, len: usize) -> Result<Value, String> {
+            self.enter()?;
+            let mut values = vec![];
+            for _ in 0..len {
+                values.push(self.read_value()?);
+            }
+            self.depth -= 1;
+            Ok(Value::Array(values))
+        }
+
+        fn read_map(&mut self, len: usize) -> Result<Value, String> {
+            self.enter()?;
+            let mut map = Map::new();
+            for _ in 0..len {
+                let key = match self.read_value()? {
+                    Value::String(key) => key,
+                    other => return Err(format!("only string keys are supported, got {}", other)),
+                };
+                let value = self.read_value()?;
+                map.insert(key, value);
+            }
+            self.depth -= 1;
+            Ok(Value::Object(map))
+        }
+
+        fn enter(&mut self) -> Result<(), String> {
+            if self.depth >= MAX_DEPTH {
+                return Err(format!("nesting deeper than {} at byte {}", MAX_DEPTH, self.pos));
+            }
+            self.depth += 1;
+            Ok(())
+        }
+    }
+
+    fn float(f: f64) -> Result<Value, String> {
+        Number::from_f64(f)
+            .map(Value::Number)
+            .ok_or(format!("{} can't be represented in json", f))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use crate::codec::codec::{decode, decode_value, encode, encode_value, WireFormat};
+        use crate::game_field::{GameState, Input, InputType};
+        use rstest::rstest;
+        use serde_json::{json, Value};
+        use std::str::FromStr;
+
+        #[rstest]
+        #[case(json!(null), vec![0xc0])]
+        #[case(json!(true), vec![0xc3])]
+        #[case(json!(5), vec![0x05])]
+        #[case(json!(-3), vec![0xfd])]
+        #[case(json!(300), vec![0xcd, 0x01, 0x2c])]
+        #[case(json!(-200), vec![0xd1, 0xff, 0x38])]
+        #[case(json!(0.5), vec![0xca, 0x3f, 0x00, 0x00, 0x00])]
+        #[case(json!("ab"), vec![0xa2, 0x61, 0x62])]
+        #[case(json!([1, 2]), vec![0x92, 0x01, 0x02])]
+        #[case(json!({"a": 1}), vec![0x81, 0xa1, 0x61, 0x01])]
+        fn should_encode_message_pack(#[case] value: Value, #[case] expected: Vec<u8>) {
+            assert_eq!(encode_value(&value), expected);
+            assert_eq!(decode_value(&expected).unwrap(), value);
+        }
+
+        #[rstest]
+        #[case(json!(0.1))]
+        #[case(json!(u64::MAX))]
+        #[case(json!(i64::MIN))]
+        #[case(json!("x".repeat(40)))]
+        #[case(json!("x".repeat(70_000)))]
+        #[case(json!((0..20).collect::<Vec<u32>>()))]
+        #[case(json!({"nested": {"list": [1.5, -2, "s", null, false]}}))]
+        fn should_roundtrip_values(#[case] value: Value) {
+            assert_eq!(decode_value(&encode_value(&value)).unwrap(), value);
+        }
+
+        #[test]
+        fn should_roundtrip_structs() {
+            let inputs = vec![Input { input: InputType::UP, obj_id: "player_1".to_owned(), player: 1 }];
+            assert_eq!(decode::<Vec<Input>>(&encode(&inputs).unwrap()).unwrap(), inputs);
+            let state = GameState::new();
+            assert_eq!(decode::<GameState>(&encode(&state).unwrap()).unwrap(), state);
+        }
+
+        #[test]
+        fn should_be_smaller_than_json() {
+            let state = GameState::new();
+            let json = serde_json::to_vec(&state).unwrap();
+            assert!(encode(&state).unwrap().len() < json.len());
+        }
+
+        #[rstest]
+        #[case(vec![0x92, 0x01])]
+        #[case(vec![0xc1])]
+        #[case(vec![0x01, 0x02])]
+        #[case(vec![0x81, 0x01, 0x01])]
+        fn should_reject_invalid_input(#[case] bytes: Vec<u8>) {
+            assert!(decode_value(&bytes).is_err());
+        }
+
+        #[test]
+        fn should_reject_deeply_nested_input() {
+            assert!(decode_value(&vec![0x91; 1_000_000]).is_err());
+            let mut nested = vec![0x91; 128];
+            nested.push(0xc0);
+            assert!(decode_value(&nested).is_ok());
+            nested.insert(0, 0x91);
+            assert!(decode_value(&nested).is_err());
+        }
+
+        #[test]
+        fn should_parse_wire_format() {
+            assert_eq!(WireFormat::from_str("BINARY"), Ok(WireFormat::Binary));
+            assert_eq!(WireFormat::from_str("json"), Ok(WireFormat::Json));
+            assert!(WireFormat::from_str("xml").is_err());
+        }
+    }
+}
@@ -2,8 +2,7 @@ pub mod detection {
     use crate::collision::collision::{Collision, CollisionRegistry, Collisions};
     use crate::game_object::game_object::GameObject;
     use crate::utils::utils::{Logger, LoggerFactory};
-    use std::cell::RefCell;
-    use std::rc::Rc;
+    use std::sync::{Arc, RwLock};
 
     pub struct CollisionDetectorConfig {
         groups: Vec<CollisionGroup>,
@@ -49,7 +48,7 @@ pub mod detection {
 
         pub fn detect_collisions(
             &self,
-            objs: Vec<Rc<RefCell<Box<dyn GameObject>>>>,
+            objs: Vec<Arc<RwLock<Box<dyn GameObject>>>>,
         ) -> Box<dyn CollisionRegistry> {
             if objs.is_empty() {
                 return Box::new(Collisions::new(vec![]));
@@ -58,11 +57,11 @@ pub mod detection {
             let mut i = 0;
             loop {
                 let o = &objs[i];
-                let obj = RefCell::borrow(o);
+                let obj = o.read().unwrap();
                 i += 1;
 
                 let rest = &objs[i..];
-                for other in rest.iter().map(|o| o.borrow()) {
+                for other in rest.iter().map(|o| o.read().unwrap()) {
                     if !self
                         .config
                         .matches_any_group(obj.obj_type(), other.obj_type())
@@ -95,8 +94,7 @@ pub mod detection {
         use crate::geom::vector::Vector;
         use crate::utils::utils::DefaultLoggerFactory;
         use rstest::rstest;
-        use std::cell::RefCell;
-        use std::rc::Rc;
+        use std::sync::{Arc, RwLock};
 
         #[rstest]
         #[case(vec![], vec![])]
@@ -136,7 +134,7 @@ pub mod detection {
         vec![]
         )]
         pub fn should_detect_collisions(
-            #[case] objs: Vec<Rc<RefCell<Box<dyn GameObject>>>>,
+            #[case] objs: Vec<Arc<RwLock<Box<dyn GameObject>>>>,
             #[case] expected_collisions: Vec<Collision>,
         ) {
             let logger = DefaultLoggerFactory::noop();
@@ -161,8 +159,8 @@ pub mod detection {
                 id: &str,
                 obj_type: &str,
                 bounding_box: BoundingBox,
-            ) -> Rc<RefCell<Box<dyn GameObject>>> {
-                Rc::new(RefCell::new(Box::new(MockGameObject {
+            ) -> Arc<RwLock<Box<dyn GameObject>>> {
+                Arc::new(RwLock::new(Box::new(MockGameObject {
                     id: id.to_owned(),
                     obj_type: String::from(obj_type),
                     bounding_box,
@@ -231,9 +229,8 @@ pub mod detection {
 }
 
 pub mod handler {
-    use std::cell::RefCell;
     use std::collections::HashMap;
-    use std::rc::Rc;
+    use std::sync::{Arc, RwLock};
     use crate::game_object::game_object::GameObject;
     use crate::utils::utils::{Logger, LoggerFactory};
 
@@ -258,7 +255,7 @@ pub mod handler {
         pub fn register(
             &mut self,
             mapping: (String, String),
-            callback: fn(&FieldStats, &Rc<RefCell<Box<dyn GameObject>>>, &Rc<RefCell<Box<dyn GameObject>>>),
+            callback: fn(&FieldStats, &Arc<RwLock<Box<dyn GameObject>>>, &Arc<RwLock<Box<dyn GameObject>>>),
         ) {
             self.handlers.add(mapping, callback)
         }
@@ -266,17 +263,19 @@ pub mod handler {
         pub fn handle(
             &self,
             stats: &FieldStats,
-            obj_a: &Rc<RefCell<Box<dyn GameObject>>>,
-            obj_b: &Rc<RefCell<Box<dyn GameObject>>>,
+            obj_a: &Arc<RwLock<Box<dyn GameObject>>>,
+            obj_b: &Arc<RwLock<Box<dyn GameObject>>>,
         ) -> bool {
             let key = (
-                RefCell::borrow(&obj_a).obj_type().to_string(),
-                RefCell::borrow(&obj_b).obj_type().to_string(),
+                obj_a.read().unwrap().obj_type().to_string(),
+                obj_b.read().unwrap().obj_type().to_string(),
             );
             let handler_res = self.handlers.call(&key, (&stats, &obj_a, &obj_b));
             if !handler_res {
-                self.logger
-                    .log(&*format!("Found no matching collision handler: {:?}", key));
+                self.logger.warn(
+                    "Found no matching collision handler",
+                    &[("obj_type_a", &key.0), ("obj_type_b", &key.1)],
+                );
                 return false;
             }
             return true;
@@ -304,12 +303,12 @@ pub mod handler {
     pub struct CollisionHandlerRegistry {
         handlers: HashMap<
             (String, String),
-            fn(&FieldStats, &Rc<RefCell<Box<dyn GameObject>>>, &Rc<RefCell<Box<dyn GameObject>>>),
+            fn(&FieldStats, &Arc<RwLock<Box<dyn GameObject>>>, &Arc<RwLock<Box<dyn GameObject>>>),
         >,
     }
 
     type CollisionCallback =
-    fn(&FieldStats, &Rc<RefCell<Box<dyn GameObject>>>, &Rc<RefCell<Box<dyn GameObject>>>);
+    fn(&FieldStats, &Arc<RwLock<Box<dyn GameObject>>>, &Arc<RwLock<Box<dyn GameObject>>>);
 
     impl CollisionHandlerRegistry {
         pub fn new() -> CollisionHandlerRegistry {
@@ -333,8 +332,8 @@ pub mod handler {
             mapping: &(String, String),
             values: (
                 &FieldStats,
-                &Rc<RefCell<Box<dyn GameObject>>>,
-                &Rc<RefCell<Box<dyn GameObject>>>,
+                &Arc<RwLock<Box<dyn GameObject>>>,
+                &Arc<RwLock<Box<dyn GameObject>>>,
             ),
         ) -> bool {
             let regular = self.handlers.get(&mapping);
@@ -354,8 +353,7 @@ pub mod handler {
     #[cfg(test)]
     mod tests {
         use rstest::rstest;
-        use std::cell::RefCell;
-        use std::rc::Rc;
+        use std::sync::{Arc, RwLock};
         use crate::collision::handler::{CollisionHandler, FieldStats};
         use crate::game_object::components::{DefaultGeomComp, DefaultPhysicsComp};
         use crate::game_object::game_object::{DefaultGameObject, GameObject};
@@ -393,8 +391,8 @@ pub mod handler {
         create_game_obj("2", Vector::new(0., 0.), Vector::new(0., 1.), true),
         )]
         pub fn should_handle_collision(
-            #[case] obj_a: Rc<RefCell<Box<dyn GameObject>>>,
-            #[case] obj_b: Rc<RefCell<Box<dyn GameObject>>>,
+            #[case] obj_a: Arc<RwLock<Box<dyn GameObject>>>,
+            #[case] obj_b: Arc<RwLock<Box<dyn GameObject>>>,
         ) {
             let logger = DefaultLoggerFactory::noop();
             let mut handler = CollisionHandler::new(&logger);
@@ -402,18 +400,18 @@ pub mod handler {
                 dimensions: (1000., 1000.)
             };
             handler.register((String::from("obj"), String::from("obj")), |_stats, _a, _b| {
-                let mut a_mut = RefCell::borrow_mut(_a);
+                let mut a_mut = _a.write().unwrap();
                 let mut vel_inverted = a_mut.vel().clone();
                 vel_inverted.invert();
                 *a_mut.vel_mut() = vel_inverted;
             });
-            let expected_vel_a = Vector::inverted(RefCell::borrow(&obj_a).vel());
+            let expected_vel_a = Vector::inverted(obj_a.read().unwrap().vel());
             let res = handler.handle(&field_stats, &obj_a, &obj_b);
             assert_eq!(true, res);
-            assert_eq!(RefCell::borrow(&obj_a).pos(), RefCell::borrow(&obj_a).pos());
-            assert_eq!(RefCell::borrow(&obj_a).vel(), &expected_vel_a);
-            assert_eq!(RefCell::borrow(&obj_b).pos(), RefCell::borrow(&obj_b).pos());
-            assert_eq!(RefCell::borrow(&obj_a).vel(), RefCell::borrow(&obj_a).vel());
+            assert_eq!(obj_a.read().unwrap().pos(), obj_a.read().unwrap().pos());
+            assert_eq!(obj_a.read().unwrap().vel(), &expected_vel_a);
+            assert_eq!(obj_b.read().unwrap().pos(), obj_b.read().unwrap().pos());
+            assert_eq!(obj_a.read().unwrap().vel(), obj_a.read().unwrap().vel());
         }
 
         fn create_game_obj(
@@ -421,8 +419,8 @@ pub mod handler {
             vel: Vector,
             orientation: Vector,
             is_static: bool,
-        ) -> Rc<RefCell<Box<dyn GameObject>>> {
-            Rc::new(RefCell::new(Box::new(DefaultGameObject::new(
+        ) -> Arc<RwLock<Box<dyn GameObject>>> {
+            Arc::new(RwLock::new(Box::new(DefaultGameObject::new(
                 id,
                 "obj".to_string(),
                 Box::new(DefaultGeomComp::new(Shape::rect(
@@ -1,10 +1,22 @@
 pub mod event {
+    use crate::game_field::Input;
+    use crate::pong::pong_rules::MatchFormat;
     use async_trait::async_trait;
     use serde::{Deserialize, Serialize};
     use std::fmt::Debug;
     use std::fs::OpenOptions;
     use std::io::Write;
 
+    /// Everything that changes the state of the engine, see `Field::apply`.
+    /// Seed and rules have to be applied before the first tick.
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(tag = "event_type")]
+    pub enum EngineEvent {
+        Seed { seed: u64 },
+        Rules { format: MatchFormat },
+        Tick { frame: u64, inputs: Vec<Input>, delta_sec: f64 },
+    }
+
     #[derive(Debug, Deserialize, Serialize)]
     pub struct EventWrapper {
         pub topic: String,
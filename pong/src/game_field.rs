@@ -1,20 +1,27 @@
-use std::cell::RefCell;
-use std::rc::Rc;
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 
 use crate::collision::collision::{CollisionRegistry, Collisions};
 use crate::collision::detection::{CollisionDetector, CollisionGroup};
 use crate::collision::handler::{CollisionHandler, FieldStats};
+use crate::event::event::EngineEvent;
 use crate::game_object::components::{DefaultGeomComp, DefaultPhysicsComp};
 use crate::game_object::game_object::{DefaultGameObject, GameObject};
 use crate::geom::shape::Shape;
 use crate::geom::vector::Vector;
+use crate::interpolation::interpolation::ObjectState;
+use crate::invariants::invariants::{InvariantChecker, InvariantViolation};
 use crate::pong::pong_collisions::{
     handle_ball_bounds_collision, handle_player_ball_collision, handle_player_bound_collision,
 };
 use crate::pong::pong_events::{
     GameObjUpdate, NoopPongEventWriter, PongEventType, PongEventWriter,
 };
+use crate::pong::pong_prediction::{self, BallPath};
+use crate::pong::pong_rules::{self, MatchFormat};
 use crate::utils::utils::{DefaultLoggerFactory, Logger, LoggerFactory, NoopLogger};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
@@ -30,37 +37,121 @@ pub struct Input {
     pub player: u16,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct GameScore {
     pub player_1: u16,
-    pub player_2: u16
+    pub player_2: u16,
+    #[serde(default)]
+    pub games: PlayerScore,
+    #[serde(default)]
+    pub sets: PlayerScore,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Games or sets won by each player, only counted by multi game formats.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerScore {
+    pub player_1: u16,
+    pub player_2: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct GameState {
     pub score: GameScore,
-    pub winner: Option<String>
+    pub winner: Option<String>,
+    #[serde(default)]
+    pub sudden_death: bool,
+    #[serde(default)]
+    pub stats: MatchStats,
 }
 
 impl GameState {
     pub fn new() -> GameState {
         GameState {
-            score: GameScore{ player_1: 0, player_2: 0 },
-            winner: None
+            score: GameScore::default(),
+            winner: None,
+            sudden_death: false,
+            stats: MatchStats::default(),
         }
     }
 }
 
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlayerHits {
+    pub player_1: u32,
+    pub player_2: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GoalStat {
+    pub player: String,
+    /// Seconds of play that had passed when the goal was scored.
+    pub time: f64,
+}
+
+/// Statistics of the running match, updated on every tick.
+/// A rally is the number of paddle hits between two goals.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MatchStats {
+    pub rallies: u32,
+    pub current_rally: u32,
+    pub longest_rally: u32,
+    pub hits: PlayerHits,
+    pub top_ball_speed: f64,
+    pub goals: Vec<GoalStat>,
+    pub time_in_play: f64,
+    pub last_hit: Option<String>,
+}
+
+impl MatchStats {
+    pub fn record_time(&mut self, delta_sec: f64) {
+        self.time_in_play += delta_sec;
+    }
+
+    pub fn record_hit(&mut self, player_id: &str) {
+        // The ball can touch a paddle on several consecutive ticks, only the first touch is a hit.
+        if self.last_hit.as_deref() == Some(player_id) {
+            return;
+        }
+        match player_id {
+            "player_1" => self.hits.player_1 += 1,
+            "player_2" => self.hits.player_2 += 1,
+            _ => return,
+        }
+        self.last_hit = Some(player_id.to_owned());
+        self.current_rally += 1;
+        self.longest_rally = self.longest_rally.max(self.current_rally);
+    }
+
+    pub fn record_ball_speed(&mut self, speed: f64) {
+        self.top_ball_speed = self.top_ball_speed.max(speed);
+    }
+
+    pub fn record_goal(&mut self, player_id: &str) {
+        self.goals.push(GoalStat {
+            player: player_id.to_owned(),
+            time: self.time_in_play,
+        });
+        self.rallies += 1;
+        self.current_rally = 0;
+        self.last_hit = None;
+    }
+}
+
 pub struct Field {
     pub logger_factory: Box<dyn LoggerFactory>,
     pub logger: Box<dyn Logger>,
     pub width: u16,
     pub height: u16,
     pub game_state: GameState,
-    objs: Vec<Rc<RefCell<Box<dyn GameObject>>>>,
+    pub match_format: MatchFormat,
+    frame: u64,
+    rng: StdRng,
+    dirty_objs: HashSet<String>,
+    objs: Vec<Arc<RwLock<Box<dyn GameObject>>>>,
     event_writer: Box<dyn PongEventWriter>,
     collision_detector: CollisionDetector,
     collision_handler: CollisionHandler,
+    invariant_checker: Option<InvariantChecker>,
 }
 
 impl Field {
@@ -72,7 +163,7 @@ impl Field {
         let height = 600;
 
         let objs = DefaultGameObject::bounds(width, height).into_iter()
-            .map(|b| Rc::new(RefCell::new(b.inner())))
+            .map(|b| Arc::new(RwLock::new(b.inner())))
             .collect();
 
         let mut field = Field {
@@ -81,8 +172,13 @@ impl Field {
             height,
             objs,
             game_state: GameState::new(),
+            match_format: MatchFormat::default(),
+            frame: 0,
+            rng: StdRng::seed_from_u64(rand::random()),
+            dirty_objs: HashSet::new(),
             collision_detector: CollisionDetector::new(&logger_factory),
             collision_handler: CollisionHandler::new(&logger_factory),
+            invariant_checker: None,
             event_writer,
             logger_factory,
         };
@@ -124,38 +220,143 @@ impl Field {
             height,
             objs: DefaultGameObject::bounds(width, height)
                 .into_iter()
-                .map(|b| Rc::new(RefCell::new(b.inner())))
+                .map(|b| Arc::new(RwLock::new(b.inner())))
                 .collect(),
             game_state: GameState::new(),
+            match_format: MatchFormat::default(),
+            frame: 0,
+            rng: StdRng::seed_from_u64(rand::random()),
+            dirty_objs: HashSet::new(),
             collision_detector: CollisionDetector::new(&logger_factory),
             collision_handler: CollisionHandler::new(&logger_factory),
+            invariant_checker: None,
             event_writer,
             logger_factory,
         }
     }
 
+    /// Rebuilds a field by applying the events of a log in order.
+    /// Logs that start with the same seed and rules and contain the same inputs end in the same state.
+    pub fn from_log(
+        logger_factory: Box<dyn LoggerFactory>,
+        event_writer: Box<dyn PongEventWriter>,
+        events: &[EngineEvent],
+    ) -> Result<Field, String> {
+        let mut field = Field::new(logger_factory, event_writer);
+        for event in events {
+            field.apply(event)?;
+        }
+        Ok(field)
+    }
+
+    pub fn apply(&mut self, event: &EngineEvent) -> Result<(), String> {
+        match event {
+            EngineEvent::Seed { seed } => {
+                if self.frame > 0 {
+                    return Err(format!("seed can't be changed after frame {}", self.frame));
+                }
+                self.set_seed(*seed);
+            }
+            EngineEvent::Rules { format } => {
+                if self.frame > 0 {
+                    return Err(format!("rules can't be changed after frame {}", self.frame));
+                }
+                self.match_format = format.clone();
+            }
+            EngineEvent::Tick { frame, inputs, delta_sec } => {
+                if *frame != self.frame {
+                    return Err(format!("expected frame {} but got {}", self.frame, frame));
+                }
+                self.tick(inputs.clone(), *delta_sec);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Ids of the objects that changed since the last call.
+    pub fn take_dirty_objs(&mut self) -> HashSet<String> {
+        std::mem::take(&mut self.dirty_objs)
+    }
+
+    /// Validates the field after every tick, violations are logged and collected.
+    pub fn enable_invariant_checks(&mut self) {
+        if self.invariant_checker.is_none() {
+            self.invariant_checker = Some(InvariantChecker::new(&*self.logger_factory));
+        }
+    }
+
+    pub fn invariant_violations(&self) -> &[InvariantViolation] {
+        match &self.invariant_checker {
+            Some(checker) => checker.violations(),
+            None => &[],
+        }
+    }
+
+    pub fn take_invariant_violations(&mut self) -> Vec<InvariantViolation> {
+        match &mut self.invariant_checker {
+            Some(checker) => checker.take_violations(),
+            None => vec![],
+        }
+    }
+
+    /// Number of ticks that were applied to the field so far.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Checksum of the objects, the score and the phase of the match. Vectors are quantised before hashing,
+    /// so that float noise below the quantisation step doesn't change the checksum.
+    pub fn checksum(&self) -> u32 {
+        let mut hasher = StateHasher::new();
+        for obj in self.objs.iter() {
+            let obj = obj.read().unwrap();
+            hasher.write(obj.id().as_bytes());
+            for vector in [obj.pos(), obj.vel(), obj.orientation()] {
+                hasher.write_quantised(vector.x);
+                hasher.write_quantised(vector.y);
+            }
+        }
+        let score = &self.game_state.score;
+        for points in [score.player_1, score.player_2, score.games.player_1, score.games.player_2, score.sets.player_1, score.sets.player_2] {
+            hasher.write(&points.to_le_bytes());
+        }
+        hasher.write(&[self.game_state.sudden_death as u8]);
+        hasher.write(self.game_state.winner.as_deref().unwrap_or("").as_bytes());
+        hasher.finish()
+    }
+
     pub fn add_player(&mut self, id: &str, x: u16, y: u16) {
         let player = DefaultGameObject::player(&id, x, y, &self);
-        self.objs.push(Rc::new(RefCell::new(player)));
+        self.objs.push(Arc::new(RwLock::new(player)));
     }
 
     pub fn add_ball(&mut self, id: &str, x: u16, y: u16) {
         let ball = DefaultGameObject::ball(id, x, y, &self);
-        self.objs.push(Rc::new(RefCell::new(ball)));
+        self.objs.push(Arc::new(RwLock::new(ball)));
     }
 
     pub fn tick(&mut self, inputs: Vec<Input>, delta_sec: f64) {
+        self.frame += 1;
+        if self.game_state.winner.is_some() {
+            return;
+        }
+        self.game_state.stats.record_time(delta_sec);
+        pong_rules::check_time(&self.match_format, &mut self.game_state);
         if self.game_state.winner.is_some() {
             return;
         }
 
         for obj in self.objs.iter() {
-            let mut obj_mut = RefCell::borrow_mut(obj);
+            let mut obj_mut = obj.write().unwrap();
             if obj_mut.obj_type() != "ball" {
                 continue;
             }
             if *obj_mut.vel() == Vector::zero() {
-                let go_right = rand::random::<bool>();
+                let go_right = self.rng.gen::<bool>();
                 let start_vel_x = match go_right {
                     true => 500.,
                     false => -500.
@@ -166,13 +367,17 @@ impl Field {
 
         {
             for obj in self.objs.iter() {
-                let mut obj_mut = RefCell::borrow_mut(obj);
+                let mut obj_mut = obj.write().unwrap();
                 if obj_mut.obj_type() != "player" {
                     continue;
                 }
                 let input_opt = inputs.iter().find(|i| i.obj_id == obj_mut.id());
                 if let None = input_opt {
-                    obj_mut.vel_mut().y = 0.;
+                    if obj_mut.vel().y != 0. {
+                        // a stopped paddle doesn't move, so update_pos won't mark it as dirty.
+                        obj_mut.vel_mut().y = 0.;
+                        obj_mut.set_dirty(true);
+                    }
                     continue;
                 }
                 let input = input_opt.unwrap();
@@ -191,7 +396,7 @@ impl Field {
 
         {
             for obj in self.objs.iter() {
-                let mut obj_mut = RefCell::borrow_mut(obj);
+                let mut obj_mut = obj.write().unwrap();
                 obj_mut.update_pos(delta_sec);
             }
         }
@@ -209,12 +414,12 @@ impl Field {
             let objs = &self.objs;
             let obj_a = objs
                 .iter()
-                .find(|o| RefCell::borrow(o).id() == collision.0)
+                .find(|o| o.read().unwrap().id() == collision.0)
                 .unwrap()
                 .clone();
             let obj_b = objs
                 .iter()
-                .find(|o| RefCell::borrow(o).id() == collision.1)
+                .find(|o| o.read().unwrap().id() == collision.1)
                 .unwrap()
                 .clone();
             let field_stats = FieldStats {dimensions: (self.width as f64, self.height as f64)};
@@ -222,8 +427,8 @@ impl Field {
         }
 
         let ball_collisions = {
-            let balls = self.objs.iter().filter(|o| o.borrow().obj_type() == "ball").collect::<Vec<&Rc<RefCell<Box<dyn GameObject>>>>>();
-            let ball_ids = balls.iter().map(|b| b.borrow().id().to_owned()).collect::<Vec<String>>();
+            let balls = self.objs.iter().filter(|o| o.read().unwrap().obj_type() == "ball").collect::<Vec<&Arc<RwLock<Box<dyn GameObject>>>>>();
+            let ball_ids = balls.iter().map(|b| b.read().unwrap().id().to_owned()).collect::<Vec<String>>();
             registered_collisions
                 .iter()
                 .map(|c| {
@@ -240,28 +445,34 @@ impl Field {
                 .collect::<Vec<String>>()
         };
 
+        for obj in self.objs.iter() {
+            let obj = obj.read().unwrap();
+            if obj.obj_type() == "player" && ball_collisions.iter().any(|id| id == obj.id()) {
+                self.game_state.stats.record_hit(obj.id());
+            }
+            if obj.obj_type() == "ball" {
+                self.game_state.stats.record_ball_speed(obj.vel().len());
+            }
+        }
+
         if ball_collisions.len() > 0 {
-            let left_bound = self.objs.iter().find(|o| o.borrow().id() == "bound_left").unwrap();
-            let right_bound = self.objs.iter().find(|o| o.borrow().id() == "bound_right").unwrap();
+            let left_bound = self.objs.iter().find(|o| o.read().unwrap().id() == "bound_left").unwrap();
+            let right_bound = self.objs.iter().find(|o| o.read().unwrap().id() == "bound_right").unwrap();
 
-            if ball_collisions.iter().any(|id| id == right_bound.borrow().id()) {
+            if ball_collisions.iter().any(|id| id == right_bound.read().unwrap().id()) {
                 // goal for player 1
-                self.game_state.score.player_1 += 1;
-                if self.game_state.score.player_1 >= 10 {
-                    self.game_state.winner = Some("player_1".to_owned());
-                }
-            } else if ball_collisions.iter().any(|id| id == left_bound.borrow().id()) {
+                self.game_state.stats.record_goal("player_1");
+                pong_rules::score_goal(&self.match_format, &mut self.game_state, "player_1");
+            } else if ball_collisions.iter().any(|id| id == left_bound.read().unwrap().id()) {
                 // goal for player 2
-                self.game_state.score.player_2 += 1;
-                if self.game_state.score.player_2 >= 10 {
-                    self.game_state.winner = Some("player_2".to_owned());
-                }
+                self.game_state.stats.record_goal("player_2");
+                pong_rules::score_goal(&self.match_format, &mut self.game_state, "player_2");
             }
         }
 
         {
-            for obj in self.objs.iter().filter(|o| RefCell::borrow(o).is_dirty()) {
-                let mut obj = RefCell::borrow_mut(obj);
+            for obj in self.objs.iter().filter(|o| o.read().unwrap().is_dirty()) {
+                let mut obj = obj.write().unwrap();
                 let event_write_res =
                     self.event_writer
                         .write(PongEventType::GameObjUpdate(GameObjUpdate {
@@ -271,12 +482,24 @@ impl Field {
                             pos: obj.pos(),
                         }));
                 if let Err(e) = event_write_res {
-                    self.logger
-                        .log(&*format!("Failed to write event logs: {}", e))
+                    self.logger.error("Failed to write event logs", &[("obj_id", &obj.id()), ("error", &e)])
                 }
                 obj.set_dirty(false);
+                self.dirty_objs.insert(obj.id().to_owned());
             }
         }
+
+        if let Some(checker) = &mut self.invariant_checker {
+            // collisions that are still detected weren't resolved by their handlers.
+            let remaining_collisions = self.collision_detector.detect_collisions(self.objs.clone());
+            checker.check(
+                self.frame,
+                (self.width as f64, self.height as f64),
+                &self.objs,
+                &remaining_collisions.get_collisions(),
+                &self.game_state.score,
+            );
+        }
     }
 
     fn get_collisions(&self) -> Box<dyn CollisionRegistry> {
@@ -284,16 +507,69 @@ impl Field {
         self.collision_detector.detect_collisions(objs)
     }
 
-    pub fn objs(&self) -> Vec<&Rc<RefCell<Box<dyn GameObject>>>> {
+    pub fn objs(&self) -> Vec<&Arc<RwLock<Box<dyn GameObject>>>> {
         self.objs.iter().collect()
     }
 
+    pub fn predict_ball_path(&self, ball_id: &str, horizon: f64) -> Option<BallPath> {
+        let ball = self.objs.iter().find(|o| o.read().unwrap().id() == ball_id)?;
+        let bounds = self.objs_of_type("bound");
+        let paddles = self.objs_of_type("player");
+        Some(pong_prediction::predict_ball_path(ball, &bounds, &paddles, horizon))
+    }
+
+    fn objs_of_type(&self, obj_type: &str) -> Vec<&Arc<RwLock<Box<dyn GameObject>>>> {
+        self.objs.iter().filter(|o| o.read().unwrap().obj_type() == obj_type).collect()
+    }
+
+    /// Overwrites an object with its authoritative state, e.g. taken from a host snapshot.
+    pub fn apply_obj_state(&mut self, state: &ObjectState) -> Result<(), String> {
+        let obj = match self.objs.iter().find(|o| o.read().unwrap().id() == state.id) {
+            Some(obj) => obj,
+            None => return Err(format!("unknown object {}", state.id)),
+        };
+        let mut obj = obj.write().unwrap();
+        *obj.pos_mut() = Vector::new(state.x, state.y);
+        *obj.vel_mut() = Vector::new(state.vel_x, state.vel_y);
+        *obj.orientation_mut() = Vector::new(state.orientation_x, state.orientation_y);
+        Ok(())
+    }
+
     pub fn set_dimensions(&mut self, width: u16, height: u16) {
         self.width = width;
         self.height = height;
     }
 }
 
+// FNV-1a, the std hashers don't guarantee the same output across releases and platforms.
+struct StateHasher {
+    hash: u32,
+}
+
+impl StateHasher {
+    const QUANTISATION: f64 = 100.;
+
+    fn new() -> StateHasher {
+        StateHasher { hash: 0x811c9dc5 }
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.hash ^= *byte as u32;
+            self.hash = self.hash.wrapping_mul(0x01000193);
+        }
+    }
+
+    fn write_quantised(&mut self, value: f64) {
+        let quantised = (value * StateHasher::QUANTISATION).round() as i64;
+        self.write(&quantised.to_le_bytes());
+    }
+
+    fn finish(&self) -> u32 {
+        self.hash
+    }
+}
+
 impl DefaultGameObject {
     pub fn player(id: &str, x: u16, y: u16, field: &Field) -> Box<dyn GameObject> {
         Box::new(DefaultGameObject::new(
@@ -425,8 +701,12 @@ impl Bounds {
 
 #[cfg(test)]
 mod tests {
-    use std::cell::RefCell;
-    use crate::game_field::{Field, Input, InputType};
+    use crate::event::event::EngineEvent;
+    use crate::pong::pong_rules::MatchFormat;
+    use crate::game_field::{Field, GoalStat, Input, InputType, MatchStats, PlayerHits};
+    use crate::geom::vector::Vector;
+    use crate::pong::pong_events::NoopPongEventWriter;
+    use crate::utils::utils::{DefaultLoggerFactory, NoopLogger};
 
     #[test]
     fn player_input_update_pos_up() {
@@ -440,13 +720,13 @@ mod tests {
             player: 1,
         }];
         field.tick(inputs, 1.);
-        let player = RefCell::borrow(
-            field
-                .objs()
-                .iter()
-                .find(|o| RefCell::borrow(o).obj_type() == "player")
-                .unwrap(),
-        );
+        let objs = field.objs();
+        let player = objs
+            .iter()
+            .find(|o| o.read().unwrap().obj_type() == "player")
+            .unwrap()
+            .read()
+            .unwrap();
         assert_eq!(player.pos().y, 530.);
     }
 
@@ -464,8 +744,152 @@ mod tests {
         let objs = field.objs();
         let player = objs
             .iter()
-            .find(|o| RefCell::borrow(o).obj_type() == "player")
+            .find(|o| o.read().unwrap().obj_type() == "player")
             .unwrap();
-        assert_eq!(RefCell::borrow(player).pos().y, 470.);
+        assert_eq!(player.read().unwrap().pos().y, 470.);
+    }
+
+    #[test]
+    fn should_count_paddle_hit_once() {
+        let mut stats = MatchStats::default();
+        stats.record_hit("player_1");
+        stats.record_hit("player_1");
+        stats.record_hit("player_2");
+        assert_eq!(stats.hits, PlayerHits { player_1: 1, player_2: 1 });
+        assert_eq!(stats.current_rally, 2);
+        assert_eq!(stats.longest_rally, 2);
+    }
+
+    #[test]
+    fn should_end_rally_on_goal() {
+        let mut stats = MatchStats::default();
+        stats.record_hit("player_1");
+        stats.record_time(2.5);
+        stats.record_goal("player_1");
+        stats.record_hit("player_2");
+        assert_eq!(stats.rallies, 1);
+        assert_eq!(stats.current_rally, 1);
+        assert_eq!(stats.longest_rally, 1);
+        assert_eq!(stats.goals, vec![GoalStat { player: "player_1".to_owned(), time: 2.5 }]);
+    }
+
+    #[test]
+    fn should_track_stats_while_playing() {
+        let mut field = Field::new(
+            DefaultLoggerFactory::new(Box::new(NoopLogger {})),
+            NoopPongEventWriter::new(),
+        );
+        place_ball(&field, Vector::new(730., 300.), Vector::new(500., 0.));
+        field.tick(vec![], 0.01);
+        place_ball(&field, Vector::new(790., 300.), Vector::new(500., 0.));
+        field.tick(vec![], 0.01);
+
+        let stats = &field.game_state.stats;
+        assert_eq!(stats.hits, PlayerHits { player_1: 0, player_2: 1 });
+        assert_eq!(stats.rallies, 1);
+        assert_eq!(stats.longest_rally, 1);
+        assert_eq!(stats.top_ball_speed, 510.);
+        assert_eq!(stats.goals, vec![GoalStat { player: "player_1".to_owned(), time: 0.02 }]);
+        assert_eq!(stats.time_in_play, 0.02);
+    }
+
+    fn place_ball(field: &Field, pos: Vector, vel: Vector) {
+        let objs = field.objs();
+        let mut ball = objs.iter().find(|o| o.read().unwrap().id() == "ball_1").unwrap().write().unwrap();
+        *ball.pos_mut() = pos;
+        *ball.vel_mut() = vel;
+    }
+
+    fn log(seed: u64, ticks: u64) -> Vec<EngineEvent> {
+        let mut events = vec![
+            EngineEvent::Seed { seed },
+            EngineEvent::Rules { format: MatchFormat::FirstTo { points: 1, win_by_two: false } },
+        ];
+        for frame in 0..ticks {
+            let input = match frame % 3 {
+                0 => InputType::UP,
+                _ => InputType::DOWN,
+            };
+            let inputs = vec![Input { input, obj_id: "player_1".to_owned(), player: 1 }];
+            events.push(EngineEvent::Tick { frame, inputs, delta_sec: 1. / 60. });
+        }
+        events
+    }
+
+    fn from_log(events: &[EngineEvent]) -> Result<Field, String> {
+        Field::from_log(
+            DefaultLoggerFactory::new(Box::new(NoopLogger {})),
+            NoopPongEventWriter::new(),
+            events,
+        )
+    }
+
+    fn positions(field: &Field) -> Vec<(String, Vector, Vector)> {
+        field.objs().iter()
+            .map(|o| {
+                let o = o.read().unwrap();
+                (o.id().to_owned(), o.pos().clone(), o.vel().clone())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn should_rebuild_identical_field_from_log() {
+        let events = log(42, 600);
+        let field_a = from_log(&events).unwrap();
+        let field_b = from_log(&events).unwrap();
+        assert_eq!(field_a.frame(), 600);
+        assert_eq!(field_a.game_state, field_b.game_state);
+        assert_eq!(positions(&field_a), positions(&field_b));
+    }
+
+    #[test]
+    fn should_apply_events_one_by_one() {
+        let events = log(7, 120);
+        let rebuilt = from_log(&events).unwrap();
+        let mut field = from_log(&[]).unwrap();
+        for event in events.iter() {
+            field.apply(event).unwrap();
+        }
+        assert_eq!(field.game_state, rebuilt.game_state);
+        assert_eq!(positions(&field), positions(&rebuilt));
+    }
+
+    #[test]
+    fn should_reject_out_of_order_frames() {
+        let mut events = log(1, 2);
+        events.swap(2, 3);
+        assert_eq!(from_log(&events).err(), Some("expected frame 0 but got 1".to_owned()));
+    }
+
+    #[test]
+    fn should_reject_seed_after_first_tick() {
+        let mut events = log(1, 1);
+        events.push(EngineEvent::Seed { seed: 2 });
+        assert!(from_log(&events).is_err());
+    }
+
+    #[test]
+    fn should_ignore_noise_below_quantisation_in_checksum() {
+        let field = from_log(&[]).unwrap();
+        let checksum = field.checksum();
+        place_ball(&field, Vector::new(400.001, 300.), Vector::zero());
+        assert_eq!(field.checksum(), checksum);
+        place_ball(&field, Vector::new(400.1, 300.), Vector::zero());
+        assert_ne!(field.checksum(), checksum);
+    }
+
+    #[test]
+    fn should_include_score_in_checksum() {
+        let mut field = from_log(&[]).unwrap();
+        let checksum = field.checksum();
+        field.game_state.score.player_2 += 1;
+        assert_ne!(field.checksum(), checksum);
+    }
+
+    #[test]
+    fn field_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Field>();
     }
 }
@@ -5,7 +5,7 @@ pub mod game_object {
     use crate::geom::vector::Vector;
     use std::fmt::Debug;
 
-    pub trait GameObject: Debug {
+    pub trait GameObject: Debug + Send + Sync {
         fn id(&self) -> &str;
         fn obj_type(&self) -> &str;
         fn shape(&self) -> &ShapeType;
@@ -82,12 +82,9 @@ pub mod game_object {
             if self.vel() == &Vector::zero() {
                 return;
             }
-            let mut vel = self.vel().clone();
-            vel.scalar_multiplication(ms_diff);
-            let center = self.geom.center_mut();
-            center.add(&vel);
-            let mut updated_orientation = vel.clone();
-            updated_orientation.normalize();
+            let vel = self.vel() * ms_diff;
+            *self.geom.center_mut() += &vel;
+            let updated_orientation = vel.normalized();
             let orientation = self.geom.orientation_mut();
             orientation.x = updated_orientation.x;
             orientation.y = updated_orientation.y;
@@ -164,7 +161,7 @@ pub mod components {
     use crate::geom::vector::Vector;
     use std::fmt::Debug;
 
-    pub trait GeomComp: Debug {
+    pub trait GeomComp: Debug + Send + Sync {
         fn shape(&self) -> &ShapeType;
         fn orientation(&self) -> &Vector;
         fn orientation_mut(&mut self) -> &mut Vector;
@@ -210,7 +207,7 @@ pub mod components {
         }
     }
 
-    pub trait PhysicsComp: Debug {
+    pub trait PhysicsComp: Debug + Send + Sync {
         fn vel(&self) -> &Vector;
         fn vel_mut(&mut self) -> &mut Vector;
         fn is_static(&self) -> bool;
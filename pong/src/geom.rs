@@ -1,5 +1,6 @@
 pub mod vector {
     use serde::Serialize;
+    use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
 
     #[derive(Debug, Clone, Serialize)]
     pub struct Vector {
@@ -162,6 +163,151 @@ pub mod vector {
         }
     }
 
+    // non-mutating counterparts of the methods above.
+    impl Vector {
+        pub fn normalized(&self) -> Vector {
+            let mut normalized = self.clone();
+            normalized.normalize();
+            normalized
+        }
+
+        pub fn rotated(&self, radians: f64) -> Vector {
+            let mut rotated = self.clone();
+            rotated.rotate(radians);
+            rotated
+        }
+
+        pub fn reflected(&self, onto: &Vector) -> Vector {
+            let mut reflected = self.clone();
+            reflected.reflect(onto);
+            reflected
+        }
+
+        /// Rotated by 90 degrees counter clockwise, like `orthogonal_counter_clockwise`.
+        pub fn perpendicular(&self) -> Vector {
+            Vector::new(-self.y, self.x)
+        }
+
+        pub fn len_squared(&self) -> f64 {
+            self.dot(self)
+        }
+
+        pub fn distance(&self, other: &Vector) -> f64 {
+            (other - self).len()
+        }
+
+        /// z component of the 3d cross product, positive if other is counter clockwise of self.
+        pub fn cross(&self, other: &Vector) -> f64 {
+            self.x * other.y - self.y * other.x
+        }
+
+        /// Interpolates linearly, t = 0 is self and t = 1 is other.
+        pub fn lerp(&self, other: &Vector, t: f64) -> Vector {
+            self + &((other - self) * t)
+        }
+
+        pub fn approx_eq(&self, other: &Vector, epsilon: f64) -> bool {
+            (self.x - other.x).abs() <= epsilon && (self.y - other.y).abs() <= epsilon
+        }
+    }
+
+    macro_rules! impl_vector_op {
+        ($op:ident, $fn:ident, $assign_op:ident, $assign_fn:ident, $sym:tt) => {
+            impl $op<&Vector> for &Vector {
+                type Output = Vector;
+
+                fn $fn(self, other: &Vector) -> Vector {
+                    Vector::new(self.x $sym other.x, self.y $sym other.y)
+                }
+            }
+
+            impl $op<Vector> for Vector {
+                type Output = Vector;
+
+                fn $fn(self, other: Vector) -> Vector {
+                    &self $sym &other
+                }
+            }
+
+            impl $op<&Vector> for Vector {
+                type Output = Vector;
+
+                fn $fn(self, other: &Vector) -> Vector {
+                    &self $sym other
+                }
+            }
+
+            impl $assign_op<&Vector> for Vector {
+                fn $assign_fn(&mut self, other: &Vector) {
+                    *self = &*self $sym other;
+                }
+            }
+
+            impl $assign_op<Vector> for Vector {
+                fn $assign_fn(&mut self, other: Vector) {
+                    *self = &*self $sym &other;
+                }
+            }
+        };
+    }
+
+    impl_vector_op!(Add, add, AddAssign, add_assign, +);
+    impl_vector_op!(Sub, sub, SubAssign, sub_assign, -);
+
+    impl Mul<f64> for &Vector {
+        type Output = Vector;
+
+        fn mul(self, n: f64) -> Vector {
+            Vector::new(self.x * n, self.y * n)
+        }
+    }
+
+    impl Mul<f64> for Vector {
+        type Output = Vector;
+
+        fn mul(self, n: f64) -> Vector {
+            &self * n
+        }
+    }
+
+    impl MulAssign<f64> for Vector {
+        fn mul_assign(&mut self, n: f64) {
+            self.scalar_multiplication(n);
+        }
+    }
+
+    impl Div<f64> for &Vector {
+        type Output = Vector;
+
+        fn div(self, n: f64) -> Vector {
+            Vector::new(self.x / n, self.y / n)
+        }
+    }
+
+    impl Div<f64> for Vector {
+        type Output = Vector;
+
+        fn div(self, n: f64) -> Vector {
+            &self / n
+        }
+    }
+
+    impl Neg for &Vector {
+        type Output = Vector;
+
+        fn neg(self) -> Vector {
+            Vector::new(-self.x, -self.y)
+        }
+    }
+
+    impl Neg for Vector {
+        type Output = Vector;
+
+        fn neg(self) -> Vector {
+            -&self
+        }
+    }
+
     impl PartialEq for Vector {
         fn eq(&self, other: &Self) -> bool {
             (self.x * 1000.).round() == (other.x * 1000.).round()
@@ -173,7 +319,7 @@ pub mod vector {
     mod tests {
         use crate::geom::vector::Vector;
         use rstest::rstest;
-        use std::f64::consts::FRAC_PI_4;
+        use std::f64::consts::{FRAC_PI_4, PI};
 
         #[rstest]
         #[case(1., 0., 1.)]
@@ -304,6 +450,55 @@ pub mod vector {
             vector.reflect(&onto);
             assert_eq!(vector, expected);
         }
+
+        #[test]
+        pub fn should_apply_operators_without_mutating_operands() {
+            let a = Vector::new(1., 2.);
+            let b = Vector::new(3., -1.);
+            assert_eq!(&a + &b, Vector::new(4., 1.));
+            assert_eq!(&a - &b, Vector::new(-2., 3.));
+            assert_eq!(&a * 2., Vector::new(2., 4.));
+            assert_eq!(&a / 2., Vector::new(0.5, 1.));
+            assert_eq!(-&a, Vector::new(-1., -2.));
+            assert_eq!(a, Vector::new(1., 2.));
+            assert_eq!(b, Vector::new(3., -1.));
+
+            let mut c = a.clone();
+            c += &b;
+            c -= Vector::new(1., 1.);
+            c *= 3.;
+            assert_eq!(c, Vector::new(9., 0.));
+        }
+
+        #[rstest]
+        #[case(Vector::new(1., 0.), Vector::new(0., 1.), 1.)]
+        #[case(Vector::new(0., 1.), Vector::new(1., 0.), -1.)]
+        #[case(Vector::new(2., 2.), Vector::new(1., 1.), 0.)]
+        pub fn should_calculate_cross_product(#[case] a: Vector, #[case] b: Vector, #[case] expected: f64) {
+            assert_eq!(a.cross(&b), expected);
+        }
+
+        #[rstest]
+        #[case(0., Vector::new(0., 0.))]
+        #[case(0.25, Vector::new(1., 2.))]
+        #[case(1., Vector::new(4., 8.))]
+        pub fn should_lerp(#[case] t: f64, #[case] expected: Vector) {
+            assert_eq!(Vector::zero().lerp(&Vector::new(4., 8.), t), expected);
+        }
+
+        #[test]
+        pub fn should_provide_non_mutating_helpers() {
+            let a = Vector::new(3., 4.);
+            assert_eq!(a.distance(&Vector::zero()), 5.);
+            assert_eq!(a.len_squared(), 25.);
+            assert_eq!(a.normalized(), Vector::new(0.6, 0.8));
+            assert_eq!(a.perpendicular(), Vector::new(-4., 3.));
+            assert_eq!(a.perpendicular().dot(&a), 0.);
+            assert_eq!(a.rotated(PI), Vector::new(-3., -4.));
+            assert_eq!(a, Vector::new(3., 4.));
+            assert!(a.approx_eq(&Vector::new(3.05, 3.95), 0.1));
+            assert!(!a.approx_eq(&Vector::new(3.2, 4.), 0.1));
+        }
     }
 }
 
@@ -367,6 +562,11 @@ pub mod utils {
             self.vert().overlaps(&other.vert()) && self.hor().overlaps(&other.hor())
         }
 
+        /// How deep the boxes overlap along the shallower axis, 0 if they only touch or are apart.
+        pub fn penetration(&self, other: &BoundingBox) -> f64 {
+            self.vert().intersection(&other.vert()).min(self.hor().intersection(&other.hor()))
+        }
+
         pub fn is_point_within(&self, point: &Vector) -> bool {
             return point.x >= self.top_left.x
                 && point.x <= self.top_right.x
@@ -407,6 +607,10 @@ pub mod utils {
 
             return false;
         }
+
+        pub fn intersection(&self, other: &Range) -> f64 {
+            (self.max.min(other.max) - self.min.max(other.min)).max(0.)
+        }
     }
 
     #[cfg(test)]
@@ -457,6 +661,17 @@ pub mod utils {
             let res = bounding_box_a.overlaps(&bounding_box_b);
             assert_eq!(res, expected);
         }
+
+        #[rstest]
+        #[case(Vector::new(10., 10.), 5.)]
+        #[case(Vector::new(8., 9.), 3.)]
+        #[case(Vector::new(5., 5.), 0.)]
+        #[case(Vector::new(20., 20.), 0.)]
+        pub fn should_calculate_penetration(#[case] center_b: Vector, #[case] expected: f64) {
+            let bounding_box_a = BoundingBox::create(&Vector::new(10., 10.), 5., 5.);
+            let bounding_box_b = BoundingBox::create(&center_b, 5., 5.);
+            assert_eq!(bounding_box_a.penetration(&bounding_box_b), expected);
+        }
     }
 }
 
@@ -573,3 +788,245 @@ pub mod shape {
         }
     }
 }
+
+pub mod primitives {
+    use crate::geom::vector::Vector;
+    use serde::Serialize;
+
+    const EPSILON: f64 = 1e-9;
+
+    #[derive(Clone, Debug, PartialEq, Serialize)]
+    pub struct Segment {
+        pub start: Vector,
+        pub end: Vector,
+    }
+
+    impl Segment {
+        pub fn new(start: Vector, end: Vector) -> Segment {
+            Segment { start, end }
+        }
+
+        pub fn direction(&self) -> Vector {
+            &self.end - &self.start
+        }
+
+        pub fn len(&self) -> f64 {
+            self.start.distance(&self.end)
+        }
+
+        pub fn closest_point(&self, point: &Vector) -> Vector {
+            let dir = self.direction();
+            let len_squared = dir.len_squared();
+            if len_squared < EPSILON {
+                return self.start.clone();
+            }
+            let t = ((point - &self.start).dot(&dir) / len_squared).clamp(0., 1.);
+            self.start.lerp(&self.end, t)
+        }
+
+        pub fn distance_to(&self, point: &Vector) -> f64 {
+            self.closest_point(point).distance(point)
+        }
+
+        /// Intersection point with another segment, None for parallel segments.
+        pub fn intersect_segment(&self, other: &Segment) -> Option<Vector> {
+            let (t, u) = line_params(&self.start, &self.direction(), &other.start, &other.direction())?;
+            if (0. ..=1.).contains(&t) && (0. ..=1.).contains(&u) {
+                return Some(self.start.lerp(&self.end, t));
+            }
+            None
+        }
+
+        /// First point of the segment, seen from start, that is within the circle.
+        pub fn intersect_circle(&self, circle: &Circle) -> Option<Vector> {
+            if circle.contains(&self.start) {
+                return Some(self.start.clone());
+            }
+            let t = circle_entry(&self.start, &self.direction(), circle)?;
+            if t <= 1. {
+                return Some(self.start.lerp(&self.end, t));
+            }
+            None
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize)]
+    pub struct Ray {
+        pub origin: Vector,
+        pub dir: Vector,
+    }
+
+    /// Where a ray hits a primitive, distance is measured along the ray, normal faces the origin.
+    #[derive(Clone, Debug, PartialEq, Serialize)]
+    pub struct RayHit {
+        pub distance: f64,
+        pub point: Vector,
+        pub normal: Vector,
+    }
+
+    impl Ray {
+        /// The direction is normalized, so distances along the ray are in field units.
+        pub fn new(origin: Vector, dir: &Vector) -> Ray {
+            Ray { origin, dir: dir.normalized() }
+        }
+
+        pub fn at(&self, distance: f64) -> Vector {
+            &self.origin + &(&self.dir * distance)
+        }
+
+        pub fn intersect_segment(&self, segment: &Segment) -> Option<RayHit> {
+            let (t, u) = line_params(&self.origin, &self.dir, &segment.start, &segment.direction())?;
+            if t < 0. || !(0. ..=1.).contains(&u) {
+                return None;
+            }
+            let mut normal = segment.direction().perpendicular().normalized();
+            if normal.dot(&self.dir) > 0. {
+                normal = -normal;
+            }
+            Some(RayHit { distance: t, point: self.at(t), normal })
+        }
+
+        pub fn intersect_circle(&self, circle: &Circle) -> Option<RayHit> {
+            let t = circle_entry(&self.origin, &self.dir, circle)?;
+            let point = self.at(t);
+            let normal = (&point - &circle.center).normalized();
+            Some(RayHit { distance: t, point, normal })
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize)]
+    pub struct Circle {
+        pub center: Vector,
+        pub radius: f64,
+    }
+
+    impl Circle {
+        pub fn new(center: Vector, radius: f64) -> Circle {
+            Circle { center, radius }
+        }
+
+        pub fn contains(&self, point: &Vector) -> bool {
+            self.center.distance(point) <= self.radius
+        }
+
+        pub fn intersects_circle(&self, other: &Circle) -> bool {
+            self.center.distance(&other.center) <= self.radius + other.radius
+        }
+
+        pub fn intersects_segment(&self, segment: &Segment) -> bool {
+            segment.distance_to(&self.center) <= self.radius
+        }
+    }
+
+    // Solves a + t * dir_a = b + u * dir_b, None if the lines are parallel.
+    fn line_params(a: &Vector, dir_a: &Vector, b: &Vector, dir_b: &Vector) -> Option<(f64, f64)> {
+        let denominator = dir_a.cross(dir_b);
+        if denominator.abs() < EPSILON {
+            return None;
+        }
+        let diff = b - a;
+        Some((diff.cross(dir_b) / denominator, diff.cross(dir_a) / denominator))
+    }
+
+    // Smallest t >= 0 at which origin + t * dir enters the circle.
+    fn circle_entry(origin: &Vector, dir: &Vector, circle: &Circle) -> Option<f64> {
+        let to_origin = origin - &circle.center;
+        let a = dir.len_squared();
+        if a < EPSILON {
+            return None;
+        }
+        let b = 2. * to_origin.dot(dir);
+        let c = to_origin.len_squared() - circle.radius * circle.radius;
+        let discriminant = b * b - 4. * a * c;
+        if discriminant < 0. {
+            return None;
+        }
+        let t = (-b - discriminant.sqrt()) / (2. * a);
+        if t >= 0. {
+            return Some(t);
+        }
+        // the origin is inside the circle.
+        if c <= 0. {
+            return Some(0.);
+        }
+        None
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use crate::geom::primitives::{Circle, Ray, Segment};
+        use crate::geom::vector::Vector;
+        use rstest::rstest;
+
+        #[rstest]
+        #[case(Vector::new(5., 5.), Vector::new(5., 0.))]
+        #[case(Vector::new(-5., 5.), Vector::new(0., 0.))]
+        #[case(Vector::new(15., -5.), Vector::new(10., 0.))]
+        pub fn should_find_closest_point_on_segment(#[case] point: Vector, #[case] expected: Vector) {
+            let segment = Segment::new(Vector::zero(), Vector::new(10., 0.));
+            assert_eq!(segment.closest_point(&point), expected);
+        }
+
+        #[rstest]
+        #[case(Segment::new(Vector::new(5., -5.), Vector::new(5., 5.)), Some(Vector::new(5., 0.)))]
+        #[case(Segment::new(Vector::new(5., 1.), Vector::new(5., 5.)), None)]
+        #[case(Segment::new(Vector::new(0., 1.), Vector::new(10., 1.)), None)]
+        pub fn should_intersect_segments(#[case] other: Segment, #[case] expected: Option<Vector>) {
+            let segment = Segment::new(Vector::zero(), Vector::new(10., 0.));
+            assert_eq!(segment.intersect_segment(&other), expected);
+        }
+
+        #[rstest]
+        #[case(Vector::new(0., 0.), Vector::new(10., 0.), Some(Vector::new(4., 0.)))]
+        #[case(Vector::new(5., 0.), Vector::new(10., 0.), Some(Vector::new(5., 0.)))]
+        #[case(Vector::new(0., 0.), Vector::new(3., 0.), None)]
+        #[case(Vector::new(0., 2.), Vector::new(10., 2.), None)]
+        pub fn should_intersect_segment_with_circle(
+            #[case] start: Vector,
+            #[case] end: Vector,
+            #[case] expected: Option<Vector>,
+        ) {
+            let segment = Segment::new(start, end);
+            let circle = Circle::new(Vector::new(5., 0.), 1.);
+            assert_eq!(segment.intersect_circle(&circle), expected);
+        }
+
+        #[test]
+        pub fn should_hit_segment_with_ray() {
+            let ray = Ray::new(Vector::zero(), &Vector::new(2., 0.));
+            let wall = Segment::new(Vector::new(10., -5.), Vector::new(10., 5.));
+            let hit = ray.intersect_segment(&wall).unwrap();
+            assert_eq!(hit.distance, 10.);
+            assert_eq!(hit.point, Vector::new(10., 0.));
+            assert_eq!(hit.normal, Vector::new(-1., 0.));
+
+            let behind = Segment::new(Vector::new(-10., -5.), Vector::new(-10., 5.));
+            assert_eq!(ray.intersect_segment(&behind), None);
+        }
+
+        #[rstest]
+        #[case(Vector::new(1., 0.), Some(8.))]
+        #[case(Vector::new(1., 1.), None)]
+        #[case(Vector::new(-1., 0.), None)]
+        pub fn should_hit_circle_with_ray(#[case] dir: Vector, #[case] expected: Option<f64>) {
+            let ray = Ray::new(Vector::zero(), &dir);
+            let circle = Circle::new(Vector::new(10., 0.), 2.);
+            let hit = ray.intersect_circle(&circle);
+            assert_eq!(hit.as_ref().map(|h| h.distance), expected);
+            if let Some(hit) = hit {
+                assert_eq!(hit.normal, Vector::new(-1., 0.));
+            }
+        }
+
+        #[test]
+        pub fn should_intersect_circles() {
+            let circle = Circle::new(Vector::zero(), 2.);
+            assert!(circle.contains(&Vector::new(1., 1.)));
+            assert!(!circle.contains(&Vector::new(2., 2.)));
+            assert!(circle.intersects_circle(&Circle::new(Vector::new(3., 0.), 1.)));
+            assert!(!circle.intersects_circle(&Circle::new(Vector::new(3.5, 0.), 1.)));
+            assert!(circle.intersects_segment(&Segment::new(Vector::new(-5., 1.), Vector::new(5., 1.))));
+            assert!(!circle.intersects_segment(&Segment::new(Vector::new(-5., 3.), Vector::new(5., 3.))));
+        }
+    }
+}
@@ -0,0 +1,232 @@
+pub mod interpolation {
+    use crate::game_object::game_object::GameObject;
+    use serde::{Deserialize, Serialize};
+    use std::collections::VecDeque;
+    use std::sync::{Arc, RwLock};
+
+    // how many clock offsets the estimate is based on.
+    const OFFSET_WINDOW: usize = 64;
+
+    /// State of a game object as it is sent in host snapshots.
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct ObjectState {
+        pub id: String,
+        pub x: f64,
+        pub y: f64,
+        pub vel_x: f64,
+        pub vel_y: f64,
+        pub orientation_x: f64,
+        pub orientation_y: f64,
+        pub shape_param_1: f64,
+        pub shape_param_2: f64,
+    }
+
+    impl ObjectState {
+        pub fn from(obj: &Arc<RwLock<Box<dyn GameObject>>>) -> ObjectState {
+            let obj = obj.read().unwrap();
+            let shape = obj.shape();
+            ObjectState {
+                id: obj.id().to_owned(),
+                x: obj.pos().x,
+                y: obj.pos().y,
+                vel_x: obj.vel().x,
+                vel_y: obj.vel().y,
+                orientation_x: obj.orientation().x,
+                orientation_y: obj.orientation().y,
+                shape_param_1: *shape.width(),
+                shape_param_2: *shape.height(),
+            }
+        }
+
+        fn lerp(&self, other: &ObjectState, t: f64) -> ObjectState {
+            let lerp = |a: f64, b: f64| a + (b - a) * t;
+            ObjectState {
+                id: self.id.clone(),
+                x: lerp(self.x, other.x),
+                y: lerp(self.y, other.y),
+                vel_x: lerp(self.vel_x, other.vel_x),
+                vel_y: lerp(self.vel_y, other.vel_y),
+                orientation_x: lerp(self.orientation_x, other.orientation_x),
+                orientation_y: lerp(self.orientation_y, other.orientation_y),
+                shape_param_1: other.shape_param_1,
+                shape_param_2: other.shape_param_2,
+            }
+        }
+
+        fn extrapolate(&self, delta_ms: f64) -> ObjectState {
+            let mut state = self.clone();
+            state.x += self.vel_x * delta_ms / 1000.;
+            state.y += self.vel_y * delta_ms / 1000.;
+            state
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    struct Snapshot {
+        ts: f64,
+        objects: Vec<ObjectState>,
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct InterpolationConfig {
+        // how far the rendered state lags behind the newest snapshot.
+        pub render_delay_ms: f64,
+        // how long objects keep moving once the buffer runs dry.
+        pub max_extrapolation_ms: f64,
+        pub capacity: usize,
+    }
+
+    impl Default for InterpolationConfig {
+        fn default() -> Self {
+            InterpolationConfig {
+                render_delay_ms: 100.,
+                max_extrapolation_ms: 100.,
+                capacity: 32,
+            }
+        }
+    }
+
+    /// Smooths out the jitter of incoming snapshots by rendering slightly in the past,
+    /// between the two snapshots around the render time.
+    /// Snapshots are stamped with the clock of the sender, the offset to the local clock is estimated
+    /// from the fastest snapshot of the recent ones.
+    pub struct InterpolationBuffer {
+        config: InterpolationConfig,
+        snapshots: VecDeque<Snapshot>,
+        offsets: VecDeque<f64>,
+    }
+
+    impl InterpolationBuffer {
+        pub fn new(config: InterpolationConfig) -> InterpolationBuffer {
+            InterpolationBuffer {
+                config,
+                snapshots: VecDeque::new(),
+                offsets: VecDeque::new(),
+            }
+        }
+
+        pub fn set_render_delay(&mut self, render_delay_ms: f64) {
+            self.config.render_delay_ms = render_delay_ms;
+        }
+
+        pub fn push(&mut self, ts: f64, local_ts: f64, objects: Vec<ObjectState>) {
+            self.offsets.push_back(local_ts - ts);
+            if self.offsets.len() > OFFSET_WINDOW {
+                self.offsets.pop_front();
+            }
+            // snapshots may arrive out of order, the buffer is kept sorted by ts.
+            let index = self.snapshots.iter().position(|s| s.ts >= ts).unwrap_or(self.snapshots.len());
+            if self.snapshots.get(index).map(|s| s.ts) == Some(ts) {
+                return;
+            }
+            self.snapshots.insert(index, Snapshot { ts, objects });
+            while self.snapshots.len() > self.config.capacity {
+                self.snapshots.pop_front();
+            }
+        }
+
+        /// Object states for the given local time, empty as long as no snapshot was pushed.
+        pub fn sample(&mut self, local_now: f64) -> Vec<ObjectState> {
+            let render_ts = local_now - self.clock_offset() - self.config.render_delay_ms;
+            // only the newest snapshot before the render time is still needed.
+            while self.snapshots.len() > 1 && self.snapshots[1].ts <= render_ts {
+                self.snapshots.pop_front();
+            }
+            let from = match self.snapshots.front() {
+                Some(from) => from,
+                None => return vec![],
+            };
+            if render_ts <= from.ts {
+                return from.objects.clone();
+            }
+            let to = match self.snapshots.get(1) {
+                Some(to) => to,
+                None => {
+                    let delta_ms = (render_ts - from.ts).min(self.config.max_extrapolation_ms);
+                    return from.objects.iter().map(|o| o.extrapolate(delta_ms)).collect();
+                }
+            };
+            let t = (render_ts - from.ts) / (to.ts - from.ts);
+            to.objects
+                .iter()
+                .map(|o| match from.objects.iter().find(|f| f.id == o.id) {
+                    Some(f) => f.lerp(o, t),
+                    None => o.clone(),
+                })
+                .collect()
+        }
+
+        fn clock_offset(&self) -> f64 {
+            // the snapshot with the lowest offset was delayed the least on its way.
+            self.offsets.iter().cloned().reduce(f64::min).unwrap_or(0.)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use crate::interpolation::interpolation::{InterpolationBuffer, InterpolationConfig, ObjectState};
+        use rstest::rstest;
+
+        fn ball(x: f64, vel_x: f64) -> ObjectState {
+            ObjectState {
+                id: "ball_1".to_owned(),
+                x,
+                y: 0.,
+                vel_x,
+                vel_y: 0.,
+                orientation_x: 1.,
+                orientation_y: 0.,
+                shape_param_1: 5.,
+                shape_param_2: 5.,
+            }
+        }
+
+        fn buffer() -> InterpolationBuffer {
+            let mut buffer = InterpolationBuffer::new(InterpolationConfig {
+                render_delay_ms: 100.,
+                max_extrapolation_ms: 50.,
+                capacity: 8,
+            });
+            // the local clock is 1000ms ahead of the sender.
+            buffer.push(0., 1000., vec![ball(0., 1000.)]);
+            buffer.push(100., 1100., vec![ball(100., 1000.)]);
+            buffer
+        }
+
+        #[rstest]
+        #[case(1050., 0.)]
+        #[case(1100., 0.)]
+        #[case(1150., 50.)]
+        #[case(1200., 100.)]
+        #[case(1230., 130.)]
+        #[case(1400., 150.)]
+        fn should_sample_with_render_delay(#[case] now: f64, #[case] expected_x: f64) {
+            let objects = buffer().sample(now);
+            assert_eq!(objects.len(), 1);
+            assert!((objects[0].x - expected_x).abs() < 1e-9, "{} != {}", objects[0].x, expected_x);
+        }
+
+        #[test]
+        fn should_sort_out_of_order_snapshots() {
+            let mut buffer = InterpolationBuffer::new(InterpolationConfig::default());
+            buffer.push(100., 1100., vec![ball(100., 0.)]);
+            buffer.push(0., 1150., vec![ball(0., 0.)]);
+            assert_eq!(buffer.sample(1150.)[0].x, 50.);
+        }
+
+        #[test]
+        fn should_return_nothing_without_snapshots() {
+            let mut buffer = InterpolationBuffer::new(InterpolationConfig::default());
+            assert!(buffer.sample(0.).is_empty());
+        }
+
+        #[test]
+        fn should_drop_snapshots_beyond_capacity() {
+            let mut buffer = buffer();
+            for i in 2..20 {
+                buffer.push(i as f64 * 100., 1000. + i as f64 * 100., vec![ball(i as f64 * 100., 1000.)]);
+            }
+            assert_eq!(buffer.snapshots.len(), 8);
+        }
+    }
+}
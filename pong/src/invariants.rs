@@ -0,0 +1,317 @@
+pub mod invariants {
+    use crate::collision::collision::Collision;
+    use crate::game_field::GameScore;
+    use crate::game_object::game_object::GameObject;
+    use crate::utils::utils::{Logger, LoggerFactory};
+    use serde::Serialize;
+    use std::collections::{HashMap, HashSet};
+    use std::fmt::{Display, Formatter};
+    use std::sync::{Arc, RwLock};
+
+    // collisions are resolved within the tick they are detected, a few frames of contact are tolerated
+    // for objects that are pushed into each other, e.g. a paddle crushing the ball into a bound.
+    pub const PERSISTENT_OVERLAP_FRAMES: u32 = 5;
+    // objects that rest against each other, like a paddle at a bound, touch but don't overlap.
+    const OVERLAP_TOLERANCE: f64 = 1.;
+
+    #[derive(Debug, Clone, PartialEq, Serialize)]
+    #[serde(tag = "invariant")]
+    pub enum InvariantViolation {
+        OutOfBounds { frame: u64, obj_id: String, x: f64, y: f64 },
+        PersistentOverlap { frame: u64, obj_a: String, obj_b: String, frames: u32 },
+        NonFinite { frame: u64, obj_id: String, vector: String },
+        // the progress of a match are the total sets, games and points, the points of both players are reset once a game is won.
+        ScoreDecreased { frame: u64, before: GameScore, after: GameScore },
+    }
+
+    impl InvariantViolation {
+        pub fn frame(&self) -> u64 {
+            match self {
+                InvariantViolation::OutOfBounds { frame, .. } => *frame,
+                InvariantViolation::PersistentOverlap { frame, .. } => *frame,
+                InvariantViolation::NonFinite { frame, .. } => *frame,
+                InvariantViolation::ScoreDecreased { frame, .. } => *frame,
+            }
+        }
+    }
+
+    impl Display for InvariantViolation {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            match self {
+                InvariantViolation::OutOfBounds { obj_id, x, y, .. } => {
+                    write!(f, "{} is out of bounds at ({}, {})", obj_id, x, y)
+                }
+                InvariantViolation::PersistentOverlap { obj_a, obj_b, frames, .. } => {
+                    write!(f, "{} and {} overlap for {} frames", obj_a, obj_b, frames)
+                }
+                InvariantViolation::NonFinite { obj_id, vector, .. } => {
+                    write!(f, "{} of {} is not finite", vector, obj_id)
+                }
+                InvariantViolation::ScoreDecreased { before, after, .. } => {
+                    write!(f, "score decreased from {:?} to {:?}", before, after)
+                }
+            }
+        }
+    }
+
+    /// Validates the field after each tick.
+    /// A violation is reported once when it occurs and again only after it was resolved in between.
+    pub struct InvariantChecker {
+        logger: Box<dyn Logger>,
+        active: HashSet<String>,
+        overlaps: HashMap<(String, String), u32>,
+        score: Option<GameScore>,
+        violations: Vec<InvariantViolation>,
+    }
+
+    impl InvariantChecker {
+        pub fn new(logger_factory: &dyn LoggerFactory) -> InvariantChecker {
+            InvariantChecker {
+                logger: logger_factory.get("invariants"),
+                active: HashSet::new(),
+                overlaps: HashMap::new(),
+                score: None,
+                violations: vec![],
+            }
+        }
+
+        /// Checks the state after collisions were resolved, overlaps are the collisions that remain.
+        pub fn check(
+            &mut self,
+            frame: u64,
+            dimensions: (f64, f64),
+            objs: &[Arc<RwLock<Box<dyn GameObject>>>],
+            overlaps: &[&Collision],
+            score: &GameScore,
+        ) -> Vec<InvariantViolation> {
+            let mut violations = vec![];
+            let mut active = HashSet::new();
+            for obj in objs {
+                let obj = obj.read().unwrap();
+                for (vector, value) in [("pos", obj.pos()), ("vel", obj.vel()), ("orientation", obj.orientation())] {
+                    if !value.x.is_finite() || !value.y.is_finite() {
+                        active.insert(format!("non_finite:{}:{}", obj.id(), vector));
+                        violations.push(InvariantViolation::NonFinite {
+                            frame,
+                            obj_id: obj.id().to_owned(),
+                            vector: vector.to_owned(),
+                        });
+                    }
+                }
+                let pos = obj.pos();
+                let (width, height) = dimensions;
+                if !obj.is_static() && (!(0. ..=width).contains(&pos.x) || !(0. ..=height).contains(&pos.y)) {
+                    active.insert(format!("out_of_bounds:{}", obj.id()));
+                    violations.push(InvariantViolation::OutOfBounds {
+                        frame,
+                        obj_id: obj.id().to_owned(),
+                        x: pos.x,
+                        y: pos.y,
+                    });
+                }
+            }
+            let mut violations = violations
+                .into_iter()
+                .filter(|v| !self.active.contains(&key(v)))
+                .collect::<Vec<InvariantViolation>>();
+            self.active = active;
+
+            let mut overlaps = overlaps
+                .iter()
+                .filter(|c| penetration(objs, &c.0, &c.1) > OVERLAP_TOLERANCE)
+                .map(|c| {
+                    let pair = (c.0.clone(), c.1.clone());
+                    let frames = self.overlaps.get(&pair).cloned().unwrap_or(0) + 1;
+                    (pair, frames)
+                })
+                .collect::<HashMap<(String, String), u32>>();
+            for ((obj_a, obj_b), frames) in overlaps.iter_mut() {
+                if *frames == PERSISTENT_OVERLAP_FRAMES {
+                    violations.push(InvariantViolation::PersistentOverlap {
+                        frame,
+                        obj_a: obj_a.clone(),
+                        obj_b: obj_b.clone(),
+                        frames: *frames,
+                    });
+                }
+            }
+            self.overlaps = overlaps;
+
+            if let Some(previous) = self.score.replace(score.clone()) {
+                if progress(score) < progress(&previous) {
+                    violations.push(InvariantViolation::ScoreDecreased { frame, before: previous, after: score.clone() });
+                }
+            }
+
+            for violation in violations.iter() {
+                self.logger.warn("invariant violated", &[("frame", &frame), ("violation", violation)]);
+            }
+            self.violations.extend(violations.iter().cloned());
+            violations
+        }
+
+        pub fn violations(&self) -> &[InvariantViolation] {
+            &self.violations
+        }
+
+        pub fn take_violations(&mut self) -> Vec<InvariantViolation> {
+            std::mem::take(&mut self.violations)
+        }
+    }
+
+    fn progress(score: &GameScore) -> [u16; 3] {
+        [
+            score.sets.player_1 + score.sets.player_2,
+            score.games.player_1 + score.games.player_2,
+            score.player_1 + score.player_2,
+        ]
+    }
+
+    fn penetration(objs: &[Arc<RwLock<Box<dyn GameObject>>>], id_a: &str, id_b: &str) -> f64 {
+        let find = |id: &str| objs.iter().find(|o| o.read().unwrap().id() == id).map(|o| o.read().unwrap().bounding_box());
+        match (find(id_a), find(id_b)) {
+            (Some(a), Some(b)) => a.penetration(&b),
+            _ => 0.,
+        }
+    }
+
+    fn key(violation: &InvariantViolation) -> String {
+        match violation {
+            InvariantViolation::OutOfBounds { obj_id, .. } => format!("out_of_bounds:{}", obj_id),
+            InvariantViolation::NonFinite { obj_id, vector, .. } => format!("non_finite:{}:{}", obj_id, vector),
+            _ => String::new(),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use crate::collision::collision::Collision;
+        use crate::game_field::{Field, Input, InputType};
+        use crate::interpolation::interpolation::ObjectState;
+        use crate::invariants::invariants::{InvariantChecker, InvariantViolation, PERSISTENT_OVERLAP_FRAMES};
+        use crate::pong::pong_events::NoopPongEventWriter;
+        use crate::pong::pong_rules::MatchFormat;
+        use crate::utils::utils::DefaultLoggerFactory;
+        use rstest::rstest;
+
+        fn field() -> Field {
+            let mut field = Field::new(DefaultLoggerFactory::noop(), NoopPongEventWriter::new());
+            field.enable_invariant_checks();
+            field
+        }
+
+        fn ball(field: &Field) -> ObjectState {
+            field.objs().into_iter().find(|o| o.read().unwrap().id() == "ball_1").map(ObjectState::from).unwrap()
+        }
+
+        #[rstest]
+        #[case(1)]
+        #[case(2)]
+        #[case(3)]
+        fn should_not_report_regular_match(#[case] seed: u64) {
+            let mut field = field();
+            field.set_seed(seed);
+            field.match_format = MatchFormat::BestOf { points: 3, win_by_two: false, games: 3, sets: 1 };
+            for frame in 0..3_000 {
+                let input = match (frame / 40) % 3 {
+                    0 => InputType::UP,
+                    _ => InputType::DOWN,
+                };
+                let inputs = vec![
+                    Input { input, obj_id: "player_1".to_owned(), player: 1 },
+                    Input { input, obj_id: "player_2".to_owned(), player: 2 },
+                ];
+                field.tick(inputs, 1. / 60.);
+            }
+            assert_eq!(field.invariant_violations(), &[]);
+        }
+
+        #[test]
+        fn should_report_escaped_ball_once() {
+            let mut field = field();
+            let mut state = ball(&field);
+            state.x = -100.;
+            state.vel_x = -500.;
+            field.apply_obj_state(&state).unwrap();
+            for _ in 0..3 {
+                field.tick(vec![], 1. / 60.);
+            }
+            let violations = field.invariant_violations();
+            assert_eq!(violations.len(), 1, "{:?}", violations);
+            assert!(matches!(&violations[0], InvariantViolation::OutOfBounds { frame: 1, obj_id, .. } if obj_id == "ball_1"));
+        }
+
+        #[test]
+        fn should_report_non_finite_velocity() {
+            let mut field = field();
+            let mut state = ball(&field);
+            state.vel_y = f64::NAN;
+            field.apply_obj_state(&state).unwrap();
+            field.tick(vec![], 1. / 60.);
+            assert!(field
+                .invariant_violations()
+                .iter()
+                .any(|v| matches!(v, InvariantViolation::NonFinite { obj_id, vector, .. } if obj_id == "ball_1" && vector == "vel")));
+        }
+
+        #[test]
+        fn should_report_decreasing_score() {
+            let mut field = field();
+            field.game_state.score.player_2 = 3;
+            field.tick(vec![], 1. / 60.);
+            field.game_state.score.player_2 = 1;
+            field.tick(vec![], 1. / 60.);
+            let violations = field.invariant_violations();
+            assert_eq!(violations.len(), 1);
+            assert!(matches!(&violations[0], InvariantViolation::ScoreDecreased { frame: 2, before, after } if before.player_2 == 3 && after.player_2 == 1));
+        }
+
+        #[test]
+        fn should_allow_points_reset_after_won_game() {
+            let mut field = field();
+            field.game_state.score.player_1 = 3;
+            field.game_state.score.player_2 = 2;
+            field.tick(vec![], 1. / 60.);
+            field.game_state.score.player_1 = 0;
+            field.game_state.score.player_2 = 0;
+            field.game_state.score.games.player_1 = 1;
+            field.tick(vec![], 1. / 60.);
+            assert_eq!(field.invariant_violations(), &[]);
+        }
+
+        #[test]
+        fn should_report_persistent_overlap() {
+            let mut field = field();
+            let paddle = field.objs().into_iter().find(|o| o.read().unwrap().id() == "player_1").map(ObjectState::from).unwrap();
+            let mut state = ball(&field);
+            state.x = paddle.x;
+            state.y = paddle.y;
+            field.apply_obj_state(&state).unwrap();
+            let objs = field.objs().into_iter().cloned().collect::<Vec<_>>();
+
+            let mut checker = InvariantChecker::new(&*DefaultLoggerFactory::noop());
+            let collision = Collision::new("ball_1", "player_1");
+            let score = Default::default();
+            let mut reported = vec![];
+            for frame in 0..PERSISTENT_OVERLAP_FRAMES as u64 + 3 {
+                reported.extend(checker.check(frame, (800., 600.), &objs, &[&collision], &score));
+            }
+            assert_eq!(reported.len(), 1);
+            assert_eq!(reported[0].frame(), PERSISTENT_OVERLAP_FRAMES as u64 - 1);
+            // a brief contact after the overlap was resolved starts a new count.
+            checker.check(10, (800., 600.), &objs, &[], &score);
+            assert!(checker.check(11, (800., 600.), &objs, &[&collision], &score).is_empty());
+        }
+
+        #[test]
+        fn should_tolerate_touching_objects() {
+            let field = field();
+            let objs = field.objs().into_iter().cloned().collect::<Vec<_>>();
+            let mut checker = InvariantChecker::new(&*DefaultLoggerFactory::noop());
+            let collision = Collision::new("ball_1", "player_1");
+            for frame in 0..PERSISTENT_OVERLAP_FRAMES as u64 + 3 {
+                assert!(checker.check(frame, (800., 600.), &objs, &[&collision], &Default::default()).is_empty());
+            }
+        }
+    }
+}
@@ -1,7 +1,12 @@
+pub mod codec;
 pub mod collision;
 pub mod event;
 pub mod game_field;
 pub mod game_object;
 pub mod geom;
+pub mod interpolation;
+pub mod invariants;
 pub mod pong;
+pub mod reconciliation;
+pub mod replay;
 pub mod utils;
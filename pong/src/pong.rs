@@ -2,19 +2,18 @@ pub mod pong_collisions {
     use crate::game_object::game_object::GameObject;
     use crate::geom::shape::{get_bounding_box, ShapeType};
     use crate::geom::vector::Vector;
-    use std::cell::{Ref, RefCell, RefMut};
     use std::f64::consts::{FRAC_PI_4};
-    use std::rc::Rc;
+    use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
     use crate::collision::handler::FieldStats;
     use crate::pong::pong_events::GameObjUpdate;
     use crate::utils::number_utils::is_in_range;
 
     pub fn handle_player_ball_collision(
         stats: &FieldStats,
-        ball: &Rc<RefCell<Box<dyn GameObject>>>,
-        player: &Rc<RefCell<Box<dyn GameObject>>>,
+        ball: &Arc<RwLock<Box<dyn GameObject>>>,
+        player: &Arc<RwLock<Box<dyn GameObject>>>,
     ) {
-        let mut ball = RefCell::borrow_mut(&ball);
+        let mut ball = ball.write().unwrap();
         let ball_pos = ball.pos().clone();
         let mut ball_dimensions = ball.shape().dimensions();
         ball_dimensions.scalar_multiplication(0.5);
@@ -22,12 +21,12 @@ pub mod pong_collisions {
 
         // player is crushing the ball out of bounds
         if is_in_range(ball_pos.y, stats.dimensions.1 - ball_height, stats.dimensions.1 + ball_height) || is_in_range(ball_pos.y, 0. - ball_height, 0. + ball_height) {
-            let mut player = player.borrow_mut();
+            let mut player = player.write().unwrap();
             *player.vel_mut() = Vector::zero();
             return;
         }
 
-        let player = player.borrow();
+        let player = player.read().unwrap();
         // reflect
         let ball_vel = ball.vel_mut();
         let mut ball_vel_total = ball_vel.len();
@@ -39,10 +38,7 @@ pub mod pong_collisions {
             ball_vel.y *= 0.50; // friction, if player does not move reduce vertical velocity.
             ball_vel.normalize();
         } else if player.vel().angle(&ball_vel) > FRAC_PI_4 {
-            let mut collision_effect = player.orientation().clone();
-            collision_effect.scalar_multiplication(0.5);
-
-            ball_vel.add(&collision_effect);
+            *ball_vel += player.orientation() * 0.5;
             ball_vel.normalize();
         }
 
@@ -62,13 +58,13 @@ pub mod pong_collisions {
 
     pub fn handle_ball_bounds_collision(
         _stats: &FieldStats,
-        ball: &Rc<RefCell<Box<dyn GameObject>>>,
-        bound: &Rc<RefCell<Box<dyn GameObject>>>,
+        ball: &Arc<RwLock<Box<dyn GameObject>>>,
+        bound: &Arc<RwLock<Box<dyn GameObject>>>,
     ) {
-        let mut ball = RefCell::borrow_mut(&ball);
+        let mut ball = ball.write().unwrap();
         let mut ball_dimensions = ball.shape().dimensions();
         ball_dimensions.scalar_multiplication(0.5);
-        let bound = RefCell::borrow(&bound);
+        let bound = bound.read().unwrap();
         ball.vel_mut().reflect(&bound.orientation());
 
         escape_collision(&mut ball, &bound);
@@ -77,11 +73,11 @@ pub mod pong_collisions {
 
     pub fn handle_player_bound_collision(
         _stats: &FieldStats,
-        player: &Rc<RefCell<Box<dyn GameObject>>>,
-        bound: &Rc<RefCell<Box<dyn GameObject>>>,
+        player: &Arc<RwLock<Box<dyn GameObject>>>,
+        bound: &Arc<RwLock<Box<dyn GameObject>>>,
     ) {
-        let mut player = RefCell::borrow_mut(&player);
-        let bound = RefCell::borrow(&bound);
+        let mut player = player.write().unwrap();
+        let bound = bound.read().unwrap();
         let shape = player.shape().clone();
         let player_orientation = player.orientation().clone();
         let height = match shape {
@@ -90,15 +86,14 @@ pub mod pong_collisions {
         };
         let mut perpendicular = player_orientation.get_opposing_orthogonal(bound.orientation());
         perpendicular.y *= height + 1.;
-        let mut new_pos = bound.pos().clone();
-        new_pos.add(&perpendicular);
+        let new_pos = bound.pos() + &perpendicular;
         let player_pos = player.pos_mut();
         player_pos.y = new_pos.y;
 
         player.set_dirty(true);
     }
 
-    fn escape_collision(game_obj_a: &mut RefMut<Box<dyn GameObject>>, game_obj_b: &Ref<Box<dyn GameObject>>) {
+    fn escape_collision(game_obj_a: &mut RwLockWriteGuard<Box<dyn GameObject>>, game_obj_b: &RwLockReadGuard<Box<dyn GameObject>>) {
         let a_dims = game_obj_a.shape().dimensions();
         let mut a_vel = game_obj_a.vel().clone();
         a_vel.normalize();
@@ -108,9 +103,7 @@ pub mod pong_collisions {
             if tries >= 5 {
                 break;
             }
-            let mut a_pos_updated = game_obj_a.pos().clone();
-            a_pos_updated.add(&a_vel);
-            *game_obj_a.pos_mut() = a_pos_updated;
+            *game_obj_a.pos_mut() += &a_vel;
             let ball_bounding_box = get_bounding_box(game_obj_a.shape());
             let player_bounding_box = get_bounding_box(game_obj_b.shape());
             if !ball_bounding_box.overlaps(&player_bounding_box) {
@@ -123,8 +116,7 @@ pub mod pong_collisions {
     #[cfg(test)]
     mod tests {
         use rstest::rstest;
-        use std::cell::RefCell;
-        use std::rc::Rc;
+        use std::sync::{Arc, RwLock};
         use crate::collision::handler::FieldStats;
         use crate::game_field::{Bound, Field};
         use crate::game_object::game_object::{DefaultGameObject, GameObject};
@@ -167,18 +159,18 @@ pub mod pong_collisions {
         get_bound(Bound::TOP)
         )]
         pub fn should_correctly_handle_player_bounds_collision(
-            #[case] player: Rc<RefCell<Box<dyn GameObject>>>,
-            #[case] bounds: Rc<RefCell<Box<dyn GameObject>>>,
-            #[case] player_expected: Rc<RefCell<Box<dyn GameObject>>>,
-            #[case] bounds_expected: Rc<RefCell<Box<dyn GameObject>>>,
+            #[case] player: Arc<RwLock<Box<dyn GameObject>>>,
+            #[case] bounds: Arc<RwLock<Box<dyn GameObject>>>,
+            #[case] player_expected: Arc<RwLock<Box<dyn GameObject>>>,
+            #[case] bounds_expected: Arc<RwLock<Box<dyn GameObject>>>,
         ) {
             let stats = FieldStats {dimensions: (1000., 1000.)};
             handle_player_bound_collision(&stats, &player, &bounds);
-            assert_eq!(player_expected.borrow().pos(), player.borrow().pos());
-            assert_eq!(bounds_expected.borrow().pos(), bounds.borrow().pos());
+            assert_eq!(player_expected.read().unwrap().pos(), player.read().unwrap().pos());
+            assert_eq!(bounds_expected.read().unwrap().pos(), bounds.read().unwrap().pos());
         }
 
-        fn create_player(id: &str, x: u16, y: u16, orientation: Vector) -> Rc<RefCell<Box<dyn GameObject>>> {
+        fn create_player(id: &str, x: u16, y: u16, orientation: Vector) -> Arc<RwLock<Box<dyn GameObject>>> {
             let logger = DefaultLoggerFactory::noop();
             let event_writer = NoopPongEventWriter::new();
             let field = Field::new(logger, event_writer);
@@ -186,15 +178,15 @@ pub mod pong_collisions {
             let player_orientation = player.orientation_mut();
             player_orientation.x = orientation.x;
             player_orientation.y = orientation.y;
-            Rc::new(RefCell::new(player))
+            Arc::new(RwLock::new(player))
         }
 
-        fn get_bound(bound: Bound) -> Rc<RefCell<Box<dyn GameObject>>> {
+        fn get_bound(bound: Bound) -> Arc<RwLock<Box<dyn GameObject>>> {
             let logger = DefaultLoggerFactory::noop();
             let event_writer = NoopPongEventWriter::new();
             let field = Field::new(logger, event_writer);
             let bounds = DefaultGameObject::bounds(field.width, field.height);
-            return Rc::new(RefCell::new(
+            return Arc::new(RwLock::new(
                 bounds.into_iter().find(|b| b.0 == bound).unwrap().inner(),
             ));
         }
@@ -205,6 +197,7 @@ pub mod pong_events {
     use crate::event::event::{EventWrapper, EventWriter};
     use crate::geom::vector::Vector;
     use serde::Serialize;
+    use std::sync::Mutex;
 
     #[derive(Serialize)]
     pub enum PongEventType<'a> {
@@ -219,12 +212,13 @@ pub mod pong_events {
         pub orientation: &'a Vector,
     }
 
-    pub trait PongEventWriter {
+    pub trait PongEventWriter: Send + Sync {
         fn write(&mut self, event: PongEventType) -> Result<(), String>;
     }
 
+    // EventWriter is only Send, the mutex keeps the pong event writer shareable between threads.
     pub struct DefaultPongEventWriter {
-        writer: EventWriter,
+        writer: Mutex<EventWriter>,
     }
 
     impl PongEventWriter for DefaultPongEventWriter {
@@ -246,7 +240,7 @@ pub mod pong_events {
     impl NoopPongEventWriter {
         pub fn new() -> Box<dyn PongEventWriter> {
             Box::new(DefaultPongEventWriter {
-                writer: EventWriter::noop(),
+                writer: Mutex::new(EventWriter::noop()),
             })
         }
     }
@@ -254,8 +248,611 @@ pub mod pong_events {
     impl DefaultPongEventWriter {
         pub fn new() -> Box<dyn PongEventWriter> {
             Box::new(DefaultPongEventWriter {
-                writer: EventWriter::file(),
+                writer: Mutex::new(EventWriter::file()),
             })
         }
     }
 }
+
+pub mod pong_prediction {
+    use crate::game_object::game_object::GameObject;
+    use crate::geom::shape::ShapeType;
+    use crate::geom::vector::Vector;
+    use serde::Serialize;
+    use std::sync::{Arc, RwLock};
+
+    // Safety net against degenerate setups, e.g. a ball that is stuck between two bounds.
+    const MAX_BOUNCES: usize = 100;
+    const EPSILON: f64 = 1e-9;
+
+    #[derive(Debug, Clone, PartialEq, Serialize)]
+    pub struct BallPath {
+        pub ball_id: String,
+        // current position, every bounce and the position at the end of the horizon or at the goal line.
+        pub points: Vec<Vector>,
+        pub intercepts: Vec<PaddleIntercept>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize)]
+    pub struct PaddleIntercept {
+        pub paddle_id: String,
+        pub time: f64,
+        pub point: Vector,
+    }
+
+    /// Follows the ball until the horizon (in seconds) is reached or it hits a goal line.
+    /// Bounds are reflected the same way as in `handle_ball_bounds_collision`, paddles are ignored.
+    pub fn predict_ball_path(
+        ball: &Arc<RwLock<Box<dyn GameObject>>>,
+        bounds: &[&Arc<RwLock<Box<dyn GameObject>>>],
+        paddles: &[&Arc<RwLock<Box<dyn GameObject>>>],
+        horizon: f64,
+    ) -> BallPath {
+        let ball = ball.read().unwrap();
+        let ball_extents = half_extents(ball.shape());
+        let mut pos = ball.pos().clone();
+        let mut vel = ball.vel().clone();
+        let mut points = vec![pos.clone()];
+        let mut intercepts: Vec<PaddleIntercept> = vec![];
+        let mut elapsed = 0.;
+        let mut last_bound: Option<String> = None;
+
+        if vel == Vector::zero() || horizon <= 0. {
+            return BallPath { ball_id: ball.id().to_owned(), points, intercepts };
+        }
+
+        for _ in 0..MAX_BOUNCES {
+            let remaining = horizon - elapsed;
+            let next_hit = bounds
+                .iter()
+                .map(|b| b.read().unwrap())
+                .filter(|b| last_bound.as_deref() != Some(b.id()))
+                .filter_map(|b| {
+                    let extents = half_extents(b.shape()) + &ball_extents;
+                    time_of_entry(&pos, &vel, b.pos(), &extents)
+                        .map(|t| (t, b.id().to_owned(), b.orientation().clone()))
+                })
+                // degenerate positions or velocities can lead to NaN times.
+                .filter(|(t, _, _)| t.is_finite() && *t <= remaining)
+                .min_by(|a, b| a.0.total_cmp(&b.0));
+
+            let segment_duration = next_hit.as_ref().map_or(remaining, |(t, _, _)| *t);
+            for paddle in paddles.iter().map(|p| p.read().unwrap()) {
+                if intercepts.iter().any(|i| i.paddle_id == paddle.id()) || vel.x == 0. {
+                    continue;
+                }
+                let t = (paddle.pos().x - pos.x) / vel.x;
+                if t < 0. || t > segment_duration {
+                    continue;
+                }
+                intercepts.push(PaddleIntercept {
+                    paddle_id: paddle.id().to_owned(),
+                    time: elapsed + t,
+                    point: position_at(&pos, &vel, t),
+                });
+            }
+
+            pos = position_at(&pos, &vel, segment_duration);
+            elapsed += segment_duration;
+            points.push(pos.clone());
+
+            match next_hit {
+                None => break,
+                Some((_, bound_id, _)) if is_goal_line(&bound_id) => break,
+                Some((_, bound_id, orientation)) => {
+                    vel.reflect(&orientation);
+                    last_bound = Some(bound_id);
+                }
+            }
+        }
+
+        BallPath { ball_id: ball.id().to_owned(), points, intercepts }
+    }
+
+    fn is_goal_line(bound_id: &str) -> bool {
+        bound_id == "bound_left" || bound_id == "bound_right"
+    }
+
+    fn half_extents(shape: &ShapeType) -> Vector {
+        match shape {
+            ShapeType::Rect(_, width, height) => Vector::new(width / 2., height / 2.),
+            ShapeType::Circle(_, radius) => Vector::new(*radius, *radius),
+        }
+    }
+
+    fn position_at(pos: &Vector, vel: &Vector, t: f64) -> Vector {
+        pos + &(vel * t)
+    }
+
+    // Slab test of the moving point against the box, the ball's extents are already added to the box.
+    fn time_of_entry(pos: &Vector, vel: &Vector, center: &Vector, extents: &Vector) -> Option<f64> {
+        let mut t_min = f64::NEG_INFINITY;
+        let mut t_max = f64::INFINITY;
+        for (p, v, c, e) in [(pos.x, vel.x, center.x, extents.x), (pos.y, vel.y, center.y, extents.y)] {
+            if v == 0. {
+                if p < c - e || p > c + e {
+                    return None;
+                }
+                continue;
+            }
+            let t1 = (c - e - p) / v;
+            let t2 = (c + e - p) / v;
+            t_min = t_min.max(t1.min(t2));
+            t_max = t_max.min(t1.max(t2));
+        }
+        if t_min > t_max || t_min < EPSILON {
+            return None;
+        }
+        Some(t_min)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use crate::game_field::Field;
+        use crate::geom::vector::Vector;
+        use crate::pong::pong_prediction::PaddleIntercept;
+
+        fn field_with_ball(vel: Vector) -> Field {
+            let mut field = Field::mock(1000, 1000);
+            field.add_player("player_1", 50, 500);
+            field.add_player("player_2", 950, 500);
+            field.add_ball("ball_1", 500, 500);
+            let objs = field.objs();
+            let ball = objs.iter().find(|o| o.read().unwrap().id() == "ball_1").unwrap();
+            *ball.write().unwrap().vel_mut() = vel;
+            field
+        }
+
+        #[test]
+        fn should_end_path_at_goal_line() {
+            let field = field_with_ball(Vector::new(100., 0.));
+            let path = field.predict_ball_path("ball_1", 10.).unwrap();
+            assert_eq!(path.points, vec![Vector::new(500., 500.), Vector::new(990.667, 500.)]);
+            assert_eq!(
+                path.intercepts,
+                vec![PaddleIntercept { paddle_id: "player_2".to_owned(), time: 4.5, point: Vector::new(950., 500.) }]
+            );
+        }
+
+        #[test]
+        fn should_reflect_on_walls() {
+            let field = field_with_ball(Vector::new(50., 100.));
+            let path = field.predict_ball_path("ball_1", 6.).unwrap();
+            assert_eq!(
+                path.points,
+                vec![Vector::new(500., 500.), Vector::new(745.333, 990.667), Vector::new(800., 881.333)]
+            );
+            assert!(path.intercepts.is_empty());
+        }
+
+        #[test]
+        fn should_predict_intercept_after_bounce() {
+            let field = field_with_ball(Vector::new(-100., -200.));
+            let path = field.predict_ball_path("ball_1", 10.).unwrap();
+            assert_eq!(path.points.len(), 3);
+            assert_eq!(path.points[1], Vector::new(254.667, 9.333));
+            let intercept = &path.intercepts[0];
+            assert_eq!(intercept.paddle_id, "player_1");
+            assert_eq!(intercept.point, Vector::new(50., 418.667));
+        }
+
+        #[test]
+        fn should_not_panic_on_degenerate_velocity() {
+            let field = field_with_ball(Vector::new(f64::NAN, 100.));
+            let path = field.predict_ball_path("ball_1", 10.).unwrap();
+            assert!(!path.points.is_empty());
+        }
+
+        #[test]
+        fn should_not_predict_unknown_ball() {
+            let field = field_with_ball(Vector::new(100., 0.));
+            assert!(field.predict_ball_path("ball_2", 10.).is_none());
+        }
+
+        #[test]
+        fn should_not_move_resting_ball() {
+            let field = field_with_ball(Vector::zero());
+            let path = field.predict_ball_path("ball_1", 10.).unwrap();
+            assert_eq!(path.points, vec![Vector::new(500., 500.)]);
+        }
+    }
+}
+
+pub mod pong_rules {
+    use crate::game_field::{GameScore, GameState, PlayerScore};
+    use serde::{Deserialize, Serialize};
+
+    /// Decides when a game, set or the whole match is over.
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(tag = "format")]
+    pub enum MatchFormat {
+        // the first player that reaches the points wins, optionally with a lead of two points.
+        FirstTo { points: u16, win_by_two: bool },
+        // every game is played first to points, a set is best of games, the match is best of sets.
+        BestOf { points: u16, win_by_two: bool, games: u16, sets: u16 },
+        // the leader after the duration wins, a tie is decided by the next goal.
+        Timed { duration_sec: f64 },
+    }
+
+    impl Default for MatchFormat {
+        fn default() -> Self {
+            MatchFormat::FirstTo { points: 10, win_by_two: false }
+        }
+    }
+
+    pub fn score_goal(format: &MatchFormat, state: &mut GameState, player: &str) {
+        if state.winner.is_some() {
+            return;
+        }
+        let score = &mut state.score;
+        *player_points(score, player) += 1;
+        match format {
+            MatchFormat::FirstTo { points, win_by_two } => {
+                if is_game_won(score, player, *points, *win_by_two) {
+                    state.winner = Some(player.to_owned());
+                }
+            }
+            MatchFormat::BestOf { points, win_by_two, games, sets } => {
+                if !is_game_won(score, player, *points, *win_by_two) {
+                    return;
+                }
+                score.player_1 = 0;
+                score.player_2 = 0;
+                *player_count(&mut score.games, player) += 1;
+                if *player_count(&mut score.games, player) < majority(*games) {
+                    return;
+                }
+                score.games = PlayerScore::default();
+                *player_count(&mut score.sets, player) += 1;
+                if *player_count(&mut score.sets, player) >= majority(*sets) {
+                    state.winner = Some(player.to_owned());
+                }
+            }
+            MatchFormat::Timed { .. } => {
+                if state.sudden_death {
+                    state.winner = Some(player.to_owned());
+                }
+            }
+        }
+    }
+
+    /// Ends timed matches once the time in play is up, or switches to sudden death on a tie.
+    pub fn check_time(format: &MatchFormat, state: &mut GameState) {
+        let duration_sec = match format {
+            MatchFormat::Timed { duration_sec } => *duration_sec,
+            _ => return,
+        };
+        if state.winner.is_some() || state.sudden_death || state.stats.time_in_play < duration_sec {
+            return;
+        }
+        let score = &state.score;
+        if score.player_1 > score.player_2 {
+            state.winner = Some("player_1".to_owned());
+        } else if score.player_2 > score.player_1 {
+            state.winner = Some("player_2".to_owned());
+        } else {
+            state.sudden_death = true;
+        }
+    }
+
+    fn is_game_won(score: &GameScore, player: &str, points: u16, win_by_two: bool) -> bool {
+        let (own, other) = match player {
+            "player_1" => (score.player_1, score.player_2),
+            _ => (score.player_2, score.player_1),
+        };
+        own >= points && (!win_by_two || own >= other + 2)
+    }
+
+    fn majority(best_of: u16) -> u16 {
+        best_of / 2 + 1
+    }
+
+    fn player_points<'a>(score: &'a mut GameScore, player: &str) -> &'a mut u16 {
+        match player {
+            "player_1" => &mut score.player_1,
+            _ => &mut score.player_2,
+        }
+    }
+
+    fn player_count<'a>(score: &'a mut PlayerScore, player: &str) -> &'a mut u16 {
+        match player {
+            "player_1" => &mut score.player_1,
+            _ => &mut score.player_2,
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use crate::game_field::{GameState, PlayerScore};
+        use crate::pong::pong_rules::{check_time, score_goal, MatchFormat};
+        use rstest::rstest;
+
+        fn play(format: &MatchFormat, goals: &[&str]) -> GameState {
+            let mut state = GameState::new();
+            for goal in goals {
+                score_goal(format, &mut state, goal);
+            }
+            state
+        }
+
+        #[rstest]
+        #[case(false, &["player_1", "player_2", "player_1", "player_2", "player_1"], Some("player_1"))]
+        #[case(true, &["player_1", "player_2", "player_1", "player_2", "player_1"], None)]
+        #[case(true, &["player_1", "player_2", "player_1", "player_2", "player_1", "player_1"], Some("player_1"))]
+        #[case(true, &["player_2", "player_2", "player_2"], Some("player_2"))]
+        fn should_end_first_to_match(
+            #[case] win_by_two: bool,
+            #[case] goals: &[&str],
+            #[case] expected: Option<&str>,
+        ) {
+            let format = MatchFormat::FirstTo { points: 3, win_by_two };
+            let state = play(&format, goals);
+            assert_eq!(state.winner.as_deref(), expected);
+        }
+
+        #[test]
+        fn should_count_games_and_sets() {
+            let format = MatchFormat::BestOf { points: 2, win_by_two: false, games: 3, sets: 3 };
+            let state = play(&format, &["player_1", "player_1", "player_2", "player_2", "player_2", "player_2", "player_1"]);
+            assert_eq!(state.score.player_1, 1);
+            assert_eq!(state.score.player_2, 0);
+            assert_eq!(state.score.games, PlayerScore { player_1: 0, player_2: 0 });
+            assert_eq!(state.score.sets, PlayerScore { player_1: 0, player_2: 1 });
+            assert_eq!(state.winner, None);
+        }
+
+        #[test]
+        fn should_end_best_of_match() {
+            let format = MatchFormat::BestOf { points: 1, win_by_two: false, games: 1, sets: 3 };
+            let state = play(&format, &["player_2", "player_1", "player_2"]);
+            assert_eq!(state.score.sets, PlayerScore { player_1: 1, player_2: 2 });
+            assert_eq!(state.winner.as_deref(), Some("player_2"));
+        }
+
+        #[rstest]
+        #[case(&["player_1"], Some("player_1"), false)]
+        #[case(&["player_1", "player_2"], None, true)]
+        fn should_end_timed_match(
+            #[case] goals: &[&str],
+            #[case] expected: Option<&str>,
+            #[case] sudden_death: bool,
+        ) {
+            let format = MatchFormat::Timed { duration_sec: 60. };
+            let mut state = play(&format, goals);
+            state.stats.record_time(59.);
+            check_time(&format, &mut state);
+            assert_eq!(state.winner, None);
+            state.stats.record_time(1.);
+            check_time(&format, &mut state);
+            assert_eq!(state.winner.as_deref(), expected);
+            assert_eq!(state.sudden_death, sudden_death);
+        }
+
+        #[test]
+        fn should_end_sudden_death_on_next_goal() {
+            let format = MatchFormat::Timed { duration_sec: 60. };
+            let mut state = GameState::new();
+            state.stats.record_time(60.);
+            check_time(&format, &mut state);
+            score_goal(&format, &mut state, "player_2");
+            assert_eq!(state.winner.as_deref(), Some("player_2"));
+        }
+    }
+}
+
+pub mod pong_sync {
+    use crate::game_field::Field;
+    use crate::utils::utils::{Logger, LoggerFactory};
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Desync {
+        pub frame: u64,
+        pub local: u32,
+        pub remote: u32,
+    }
+
+    /// Compares the checksums of a remote simulation, e.g. the host, with the local field.
+    /// Only the start and the end of a desync are logged, not every diverging frame.
+    pub struct DesyncDetector {
+        logger: Box<dyn Logger>,
+        desync: Option<Desync>,
+        desyncs: u32,
+    }
+
+    impl DesyncDetector {
+        pub fn new(logger_factory: &dyn LoggerFactory) -> DesyncDetector {
+            DesyncDetector {
+                logger: logger_factory.get("desync_detector"),
+                desync: None,
+                desyncs: 0,
+            }
+        }
+
+        pub fn check(&mut self, field: &Field, remote: u32) -> Option<Desync> {
+            let local = field.checksum();
+            if local == remote {
+                if let Some(desync) = self.desync.take() {
+                    self.logger.info("back in sync", &[("frame", &field.frame()), ("desync_frame", &desync.frame)]);
+                }
+                return None;
+            }
+            let desync = Desync { frame: field.frame(), local, remote };
+            if self.desync.is_none() {
+                self.desyncs += 1;
+                self.logger.warn(
+                    "desync",
+                    &[("frame", &desync.frame), ("local_checksum", &local), ("remote_checksum", &remote)],
+                );
+                self.desync = Some(desync.clone());
+            }
+            Some(desync)
+        }
+
+        pub fn is_in_sync(&self) -> bool {
+            self.desync.is_none()
+        }
+
+        /// Number of desyncs that were detected, consecutive diverging frames count once.
+        pub fn desyncs(&self) -> u32 {
+            self.desyncs
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use crate::game_field::Field;
+        use crate::pong::pong_events::NoopPongEventWriter;
+        use crate::pong::pong_sync::{Desync, DesyncDetector};
+        use crate::utils::utils::DefaultLoggerFactory;
+
+        fn field(seed: u64) -> Field {
+            let mut field = Field::new(DefaultLoggerFactory::noop(), NoopPongEventWriter::new());
+            field.set_seed(seed);
+            field
+        }
+
+        #[test]
+        fn should_accept_matching_checksum() {
+            let mut local = field(3);
+            let mut remote = field(3);
+            let mut detector = DesyncDetector::new(&*DefaultLoggerFactory::noop());
+            for _ in 0..60 {
+                local.tick(vec![], 1. / 60.);
+                remote.tick(vec![], 1. / 60.);
+                assert_eq!(detector.check(&local, remote.checksum()), None);
+            }
+            assert!(detector.is_in_sync());
+        }
+
+        #[test]
+        fn should_count_consecutive_desyncs_once() {
+            let mut local = field(3);
+            let mut detector = DesyncDetector::new(&*DefaultLoggerFactory::noop());
+            local.tick(vec![], 1. / 60.);
+            let res = detector.check(&local, 1);
+            assert_eq!(res, Some(Desync { frame: 1, local: local.checksum(), remote: 1 }));
+            local.tick(vec![], 1. / 60.);
+            assert!(detector.check(&local, 1).is_some());
+            assert_eq!(detector.desyncs(), 1);
+
+            assert_eq!(detector.check(&local, local.checksum()), None);
+            assert!(detector.check(&local, 2).is_some());
+            assert_eq!(detector.desyncs(), 2);
+        }
+    }
+}
+
+pub mod pong_delta {
+    use crate::game_field::Field;
+    use crate::game_object::game_object::GameObject;
+    use std::collections::HashSet;
+    use std::sync::{Arc, RwLock};
+
+    pub struct SnapshotDelta<'a> {
+        pub seq: u64,
+        // seq of the snapshot this delta builds on, None for a full snapshot.
+        pub baseline: Option<u64>,
+        pub objs: Vec<&'a Arc<RwLock<Box<dyn GameObject>>>>,
+    }
+
+    /// Picks the objects of the next snapshot. Every snapshot builds on the previous one,
+    /// so objects are only sent if they were never sent before or changed since.
+    /// There are no acks, snapshots go through kafka to every receiver. The server merges them
+    /// per connection and resyncs clients that miss one, see `SnapshotBaseline` of the server.
+    /// Hosts reset the encoder whenever their own connection was re-established.
+    #[derive(Default)]
+    pub struct DeltaEncoder {
+        seq: u64,
+        baseline: Option<u64>,
+        sent: HashSet<String>,
+    }
+
+    impl DeltaEncoder {
+        pub fn new() -> DeltaEncoder {
+            DeltaEncoder::default()
+        }
+
+        pub fn encode<'a>(&mut self, field: &'a mut Field) -> SnapshotDelta<'a> {
+            let dirty_objs = field.take_dirty_objs();
+            let field: &'a Field = field;
+            let objs = field
+                .objs()
+                .into_iter()
+                .filter(|o| {
+                    let obj = o.read().unwrap();
+                    dirty_objs.contains(obj.id()) || !self.sent.contains(obj.id())
+                })
+                .collect::<Vec<&Arc<RwLock<Box<dyn GameObject>>>>>();
+            for obj in objs.iter() {
+                self.sent.insert(obj.read().unwrap().id().to_owned());
+            }
+            self.seq += 1;
+            let delta = SnapshotDelta {
+                seq: self.seq,
+                baseline: self.baseline,
+                objs,
+            };
+            self.baseline = Some(self.seq);
+            delta
+        }
+
+        /// The next snapshot will contain every object again, e.g. after the receiver lost its baseline.
+        pub fn reset(&mut self) {
+            self.baseline = None;
+            self.sent.clear();
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use crate::game_field::{Field, Input, InputType};
+        use crate::pong::pong_delta::DeltaEncoder;
+        use crate::pong::pong_events::NoopPongEventWriter;
+        use crate::utils::utils::DefaultLoggerFactory;
+
+        fn encode_ids(field: &mut Field, encoder: &mut DeltaEncoder) -> (u64, Option<u64>, Vec<String>) {
+            let delta = encoder.encode(field);
+            let mut ids = delta.objs.iter().map(|o| o.read().unwrap().id().to_owned()).collect::<Vec<String>>();
+            ids.sort();
+            (delta.seq, delta.baseline, ids)
+        }
+
+        #[test]
+        fn should_send_static_objects_once() {
+            let mut field = Field::new(DefaultLoggerFactory::noop(), NoopPongEventWriter::new());
+            let mut encoder = DeltaEncoder::new();
+            let (seq, baseline, first) = encode_ids(&mut field, &mut encoder);
+            assert_eq!((seq, baseline, first.len()), (1, None, 7));
+
+            field.tick(vec![Input { input: InputType::UP, obj_id: "player_1".to_owned(), player: 1 }], 1. / 60.);
+            let (seq, baseline, second) = encode_ids(&mut field, &mut encoder);
+            assert_eq!((seq, baseline), (2, Some(1)));
+            assert_eq!(second, vec!["ball_1", "player_1"]);
+        }
+
+        #[test]
+        fn should_send_stopped_paddle() {
+            let mut field = Field::new(DefaultLoggerFactory::noop(), NoopPongEventWriter::new());
+            let mut encoder = DeltaEncoder::new();
+            encoder.encode(&mut field);
+            field.tick(vec![Input { input: InputType::UP, obj_id: "player_1".to_owned(), player: 1 }], 1. / 60.);
+            encoder.encode(&mut field);
+            field.tick(vec![], 1. / 60.);
+            let (_, _, ids) = encode_ids(&mut field, &mut encoder);
+            assert_eq!(ids, vec!["ball_1", "player_1"]);
+            field.tick(vec![], 1. / 60.);
+            let (_, _, ids) = encode_ids(&mut field, &mut encoder);
+            assert_eq!(ids, vec!["ball_1"]);
+        }
+
+        #[test]
+        fn should_send_everything_after_reset() {
+            let mut field = Field::new(DefaultLoggerFactory::noop(), NoopPongEventWriter::new());
+            let mut encoder = DeltaEncoder::new();
+            encoder.encode(&mut field);
+            encoder.reset();
+            let (seq, baseline, ids) = encode_ids(&mut field, &mut encoder);
+            assert_eq!((seq, baseline, ids.len()), (2, None, 7));
+        }
+    }
+}
@@ -0,0 +1,188 @@
+pub mod reconciliation {
+    use crate::game_field::{Field, GameState, Input};
+    use crate::interpolation::interpolation::ObjectState;
+    use std::collections::VecDeque;
+
+    // about two seconds at 60 fps, older inputs are most likely lost and aren't replayed anymore.
+    const MAX_PENDING_INPUTS: usize = 120;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct PendingInput {
+        seq: u64,
+        inputs: Vec<Input>,
+        delta_sec: f64,
+    }
+
+    /// Moves the paddle of the peer right away instead of waiting for the host's tick.
+    /// Every prediction step gets a sequence number, which is sent along with the inputs and echoed by the host.
+    /// Once the host's state arrives, the field is reset to it and the inputs the host hasn't seen yet are replayed.
+    pub struct PeerPredictor {
+        field: Field,
+        paddle_id: String,
+        next_seq: u64,
+        last_acked_seq: Option<u64>,
+        pending: VecDeque<PendingInput>,
+    }
+
+    impl PeerPredictor {
+        pub fn new(field: Field, paddle_id: &str) -> PeerPredictor {
+            PeerPredictor {
+                field,
+                paddle_id: paddle_id.to_owned(),
+                next_seq: 0,
+                last_acked_seq: None,
+                pending: VecDeque::new(),
+            }
+        }
+
+        /// Applies the inputs of the local paddle and returns the sequence number to send them with.
+        pub fn predict(&mut self, inputs: Vec<Input>, delta_sec: f64) -> u64 {
+            let inputs = inputs.into_iter().filter(|i| i.obj_id == self.paddle_id).collect::<Vec<Input>>();
+            let seq = self.next_seq;
+            self.next_seq += 1;
+            self.field.tick(inputs.clone(), delta_sec);
+            self.pending.push_back(PendingInput { seq, inputs, delta_sec });
+            while self.pending.len() > MAX_PENDING_INPUTS {
+                self.pending.pop_front();
+            }
+            seq
+        }
+
+        /// Resets the field to the host's state and replays the inputs after acked_seq.
+        /// Snapshots that acknowledge less than an earlier one are outdated and ignored.
+        pub fn reconcile(
+            &mut self,
+            acked_seq: Option<u64>,
+            objects: &[ObjectState],
+            state: &GameState,
+        ) -> Result<(), String> {
+            match (acked_seq, self.last_acked_seq) {
+                (Some(acked), Some(last)) if acked < last => return Ok(()),
+                (None, Some(_)) => return Ok(()),
+                _ => {}
+            }
+            if let Some(acked) = acked_seq {
+                self.pending.retain(|p| p.seq > acked);
+                self.last_acked_seq = Some(acked);
+            }
+            for object in objects {
+                self.field.apply_obj_state(object)?;
+            }
+            self.field.game_state = state.clone();
+            for pending in self.pending.iter() {
+                self.field.tick(pending.inputs.clone(), pending.delta_sec);
+            }
+            Ok(())
+        }
+
+        pub fn paddle(&self) -> Option<ObjectState> {
+            self.field
+                .objs()
+                .into_iter()
+                .find(|o| o.read().unwrap().id() == self.paddle_id)
+                .map(ObjectState::from)
+        }
+
+        pub fn pending_inputs(&self) -> usize {
+            self.pending.len()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use crate::game_field::{Field, Input, InputType};
+        use crate::interpolation::interpolation::ObjectState;
+        use crate::pong::pong_events::NoopPongEventWriter;
+        use crate::reconciliation::reconciliation::PeerPredictor;
+        use crate::utils::utils::DefaultLoggerFactory;
+        use rstest::rstest;
+
+        const DELTA_SEC: f64 = 1. / 60.;
+
+        fn field() -> Field {
+            Field::new(DefaultLoggerFactory::noop(), NoopPongEventWriter::new())
+        }
+
+        fn inputs(frame: usize) -> Vec<Input> {
+            let input = match frame % 5 {
+                0 | 1 | 2 => InputType::UP,
+                _ => InputType::DOWN,
+            };
+            vec![
+                Input { input, obj_id: "player_2".to_owned(), player: 2 },
+                Input { input: InputType::UP, obj_id: "player_1".to_owned(), player: 1 },
+            ]
+        }
+
+        fn paddle_inputs(frame: usize) -> Vec<Input> {
+            inputs(frame).into_iter().filter(|i| i.obj_id == "player_2").collect()
+        }
+
+        fn paddle(field: &Field) -> ObjectState {
+            field.objs().into_iter().find(|o| o.read().unwrap().id() == "player_2").map(ObjectState::from).unwrap()
+        }
+
+        fn objects(field: &Field) -> Vec<ObjectState> {
+            field.objs().into_iter().map(ObjectState::from).collect()
+        }
+
+        #[rstest]
+        #[case(0)]
+        #[case(4)]
+        #[case(10)]
+        fn should_replay_unacknowledged_inputs(#[case] acked_frames: usize) {
+            let mut predictor = PeerPredictor::new(field(), "player_2");
+            let mut host = field();
+            let mut reference = field();
+            for frame in 0..10 {
+                let seq = predictor.predict(inputs(frame), DELTA_SEC);
+                assert_eq!(seq, frame as u64);
+                if frame < acked_frames {
+                    host.tick(paddle_inputs(frame), DELTA_SEC);
+                }
+                reference.tick(paddle_inputs(frame), DELTA_SEC);
+            }
+            let acked_seq = acked_frames.checked_sub(1).map(|f| f as u64);
+            predictor.reconcile(acked_seq, &objects(&host), &host.game_state).unwrap();
+            assert_eq!(predictor.pending_inputs(), 10 - acked_frames);
+            let predicted = predictor.paddle().unwrap();
+            let expected = paddle(&reference);
+            assert!((predicted.y - expected.y).abs() < 1e-9, "{} != {}", predicted.y, expected.y);
+            assert_eq!(predicted.vel_y, expected.vel_y);
+        }
+
+        #[test]
+        fn should_correct_misprediction() {
+            let mut predictor = PeerPredictor::new(field(), "player_2");
+            predictor.predict(paddle_inputs(0), DELTA_SEC);
+            // the host never saw the input, its paddle didn't move.
+            let mut host = field();
+            host.tick(vec![], DELTA_SEC);
+            predictor.reconcile(Some(0), &objects(&host), &host.game_state).unwrap();
+            assert_eq!(predictor.paddle().unwrap().y, paddle(&host).y);
+        }
+
+        #[test]
+        fn should_ignore_outdated_snapshots() {
+            let mut predictor = PeerPredictor::new(field(), "player_2");
+            for frame in 0..4 {
+                predictor.predict(paddle_inputs(frame), DELTA_SEC);
+            }
+            let host = field();
+            predictor.reconcile(Some(2), &objects(&host), &host.game_state).unwrap();
+            let before = predictor.paddle().unwrap();
+            predictor.reconcile(Some(1), &[], &host.game_state).unwrap();
+            predictor.reconcile(None, &[], &host.game_state).unwrap();
+            assert_eq!(predictor.pending_inputs(), 1);
+            assert_eq!(predictor.paddle().unwrap(), before);
+        }
+
+        #[test]
+        fn should_reject_unknown_objects() {
+            let mut predictor = PeerPredictor::new(field(), "player_2");
+            let mut object = paddle(&field());
+            object.id = "unknown".to_owned();
+            assert!(predictor.reconcile(None, &[object], &field().game_state).is_err());
+        }
+    }
+}
@@ -0,0 +1,244 @@
+pub mod replay {
+    use crate::event::event::EngineEvent;
+    use crate::game_field::{Field, Input};
+    use crate::pong::pong_events::PongEventWriter;
+    use crate::pong::pong_rules::MatchFormat;
+    use crate::utils::utils::LoggerFactory;
+    use serde::{Deserialize, Serialize};
+
+    pub const REPLAY_VERSION: u16 = 1;
+    // one checksum per second at 60 ticks per second.
+    pub const DEFAULT_CHECKSUM_INTERVAL: u64 = 60;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct Replay {
+        pub version: u16,
+        pub seed: u64,
+        pub rules: MatchFormat,
+        pub frames: Vec<ReplayFrame>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct ReplayFrame {
+        pub inputs: Vec<Input>,
+        pub delta_sec: f64,
+        // checksum of the field after this frame was applied.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub checksum: Option<u32>,
+    }
+
+    impl Replay {
+        /// The replay as engine events, to be folded with `Field::apply`.
+        pub fn events(&self) -> Vec<EngineEvent> {
+            let mut events = vec![
+                EngineEvent::Seed { seed: self.seed },
+                EngineEvent::Rules { format: self.rules.clone() },
+            ];
+            for (frame, replay_frame) in self.frames.iter().enumerate() {
+                events.push(EngineEvent::Tick {
+                    frame: frame as u64,
+                    inputs: replay_frame.inputs.clone(),
+                    delta_sec: replay_frame.delta_sec,
+                });
+            }
+            events
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum ReplayError {
+        UnsupportedVersion(u16),
+        InvalidEvent(String),
+        // frame is the index of the first frame whose checksum doesn't match.
+        Diverged { frame: u64, expected: u32, actual: u32 },
+    }
+
+    /// Records every tick of a field, so that the match can be replayed later.
+    pub struct ReplayRecorder {
+        field: Field,
+        replay: Replay,
+        checksum_interval: u64,
+    }
+
+    impl ReplayRecorder {
+        pub fn new(field: Field, seed: u64) -> Result<ReplayRecorder, String> {
+            ReplayRecorder::with_checksum_interval(field, seed, DEFAULT_CHECKSUM_INTERVAL)
+        }
+
+        pub fn with_checksum_interval(
+            mut field: Field,
+            seed: u64,
+            checksum_interval: u64,
+        ) -> Result<ReplayRecorder, String> {
+            if field.frame() > 0 {
+                return Err(format!("can't record field that is already at frame {}", field.frame()));
+            }
+            if checksum_interval == 0 {
+                return Err("checksum interval must be greater than 0".to_owned());
+            }
+            field.set_seed(seed);
+            let replay = Replay {
+                version: REPLAY_VERSION,
+                seed,
+                rules: field.match_format.clone(),
+                frames: vec![],
+            };
+            Ok(ReplayRecorder { field, replay, checksum_interval })
+        }
+
+        pub fn tick(&mut self, inputs: Vec<Input>, delta_sec: f64) {
+            self.field.tick(inputs.clone(), delta_sec);
+            let checksum = match self.field.frame() % self.checksum_interval {
+                0 => Some(self.field.checksum()),
+                _ => None,
+            };
+            self.replay.frames.push(ReplayFrame { inputs, delta_sec, checksum });
+        }
+
+        pub fn field(&self) -> &Field {
+            &self.field
+        }
+
+        pub fn replay(&self) -> &Replay {
+            &self.replay
+        }
+
+        pub fn finish(self) -> Replay {
+            self.replay
+        }
+    }
+
+    /// Re-runs a recording frame by frame and verifies the embedded checksums and the invariants of the field.
+    pub struct ReplayPlayer {
+        field: Field,
+        events: Vec<EngineEvent>,
+        checksums: Vec<Option<u32>>,
+        next_event: usize,
+    }
+
+    impl ReplayPlayer {
+        pub fn new(
+            replay: &Replay,
+            logger_factory: Box<dyn LoggerFactory>,
+            event_writer: Box<dyn PongEventWriter>,
+        ) -> Result<ReplayPlayer, ReplayError> {
+            if replay.version != REPLAY_VERSION {
+                return Err(ReplayError::UnsupportedVersion(replay.version));
+            }
+            let mut field = Field::new(logger_factory, event_writer);
+            field.enable_invariant_checks();
+            Ok(ReplayPlayer {
+                field,
+                events: replay.events(),
+                checksums: replay.frames.iter().map(|f| f.checksum).collect(),
+                next_event: 0,
+            })
+        }
+
+        /// Applies the next event, returns false once the replay is over.
+        pub fn step(&mut self) -> Result<bool, ReplayError> {
+            let event = match self.events.get(self.next_event) {
+                Some(event) => event,
+                None => return Ok(false),
+            };
+            self.next_event += 1;
+            if let Err(e) = self.field.apply(event) {
+                return Err(ReplayError::InvalidEvent(e));
+            }
+            if let EngineEvent::Tick { frame, .. } = event {
+                if let Some(expected) = self.checksums[*frame as usize] {
+                    let actual = self.field.checksum();
+                    if actual != expected {
+                        return Err(ReplayError::Diverged { frame: *frame, expected, actual });
+                    }
+                }
+            }
+            Ok(true)
+        }
+
+        pub fn run(mut self) -> Result<Field, ReplayError> {
+            while self.step()? {}
+            Ok(self.field)
+        }
+
+        pub fn field(&self) -> &Field {
+            &self.field
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use crate::game_field::{Field, Input, InputType};
+        use crate::pong::pong_events::NoopPongEventWriter;
+        use crate::pong::pong_rules::MatchFormat;
+        use crate::replay::replay::{Replay, ReplayError, ReplayPlayer, ReplayRecorder};
+        use crate::utils::utils::DefaultLoggerFactory;
+
+        fn field() -> Field {
+            let mut field = Field::new(DefaultLoggerFactory::noop(), NoopPongEventWriter::new());
+            field.match_format = MatchFormat::FirstTo { points: 3, win_by_two: false };
+            field
+        }
+
+        fn record(ticks: usize, checksum_interval: u64) -> (Replay, u32) {
+            let mut recorder = ReplayRecorder::with_checksum_interval(field(), 42, checksum_interval).unwrap();
+            for tick in 0..ticks {
+                let input = match tick % 4 {
+                    0 | 1 => InputType::UP,
+                    _ => InputType::DOWN,
+                };
+                let inputs = vec![Input { input, obj_id: "player_2".to_owned(), player: 2 }];
+                recorder.tick(inputs, 1. / 60.);
+            }
+            let checksum = recorder.field().checksum();
+            (recorder.finish(), checksum)
+        }
+
+        fn play(replay: &Replay) -> Result<Field, ReplayError> {
+            ReplayPlayer::new(replay, DefaultLoggerFactory::noop(), NoopPongEventWriter::new())?.run()
+        }
+
+        #[test]
+        fn should_replay_recording() {
+            let (replay, checksum) = record(300, 60);
+            assert_eq!(replay.frames.iter().filter(|f| f.checksum.is_some()).count(), 5);
+            let field = play(&replay).unwrap();
+            assert_eq!(field.frame(), 300);
+            assert_eq!(field.checksum(), checksum);
+            assert_eq!(field.invariant_violations(), &[]);
+        }
+
+        #[test]
+        fn should_survive_serialization() {
+            let (replay, checksum) = record(120, 10);
+            let json = serde_json::to_string(&replay).unwrap();
+            let replay = serde_json::from_str::<Replay>(&json).unwrap();
+            assert_eq!(play(&replay).unwrap().checksum(), checksum);
+        }
+
+        #[test]
+        fn should_report_first_divergent_frame() {
+            let (mut replay, _) = record(120, 10);
+            replay.frames[34].inputs[0].input = match replay.frames[34].inputs[0].input {
+                InputType::UP => InputType::DOWN,
+                InputType::DOWN => InputType::UP,
+            };
+            let res = play(&replay);
+            assert!(matches!(res, Err(ReplayError::Diverged { frame: 39, .. })));
+        }
+
+        #[test]
+        fn should_reject_unknown_version() {
+            let (mut replay, _) = record(1, 1);
+            replay.version = 0;
+            assert_eq!(play(&replay).err(), Some(ReplayError::UnsupportedVersion(0)));
+        }
+
+        #[test]
+        fn should_not_record_running_field() {
+            let mut field = field();
+            field.tick(vec![], 1. / 60.);
+            assert!(ReplayRecorder::new(field, 1).is_err());
+        }
+    }
+}
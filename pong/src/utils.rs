@@ -1,5 +1,9 @@
 pub mod utils {
-    pub trait LoggerFactory {
+    use std::fmt::{Display, Formatter};
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    pub trait LoggerFactory: Send + Sync {
         fn get(&self, name: &str) -> Box<dyn Logger>;
     }
 
@@ -19,6 +23,15 @@ pub mod utils {
         pub fn new(proto: Box<dyn Logger>) -> Box<dyn LoggerFactory> {
             Box::new(DefaultLoggerFactory { proto })
         }
+        /// Loggers only forward records the filter enables for their name.
+        pub fn filtered(proto: Box<dyn Logger>, filter: LogFilter) -> Box<dyn LoggerFactory> {
+            let proto = FilteredLogger {
+                name: String::new(),
+                inner: proto,
+                filter: Arc::new(filter),
+            };
+            DefaultLoggerFactory::new(Box::new(proto))
+        }
         pub fn noop() -> Box<dyn LoggerFactory> {
             Box::new(DefaultLoggerFactory {
                 proto: Box::new(NoopLogger {}),
@@ -26,10 +39,72 @@ pub mod utils {
         }
     }
 
-    pub trait Logger {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub enum LogLevel {
+        Trace,
+        Debug,
+        Info,
+        Warn,
+        Error,
+    }
+
+    impl FromStr for LogLevel {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s.to_lowercase().as_str() {
+                "trace" => Ok(LogLevel::Trace),
+                "debug" => Ok(LogLevel::Debug),
+                "info" => Ok(LogLevel::Info),
+                "warn" => Ok(LogLevel::Warn),
+                "error" => Ok(LogLevel::Error),
+                _ => Err(format!("unknown log level: {}", s)),
+            }
+        }
+    }
+
+    pub type LogField<'a> = (&'a str, &'a dyn Display);
+
+    pub struct LogRecord<'a> {
+        pub level: LogLevel,
+        pub msg: &'a str,
+        pub fields: &'a [LogField<'a>],
+    }
+
+    // renders as `msg key=value key=value`.
+    impl Display for LogRecord<'_> {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}", self.msg)?;
+            for (key, value) in self.fields {
+                write!(f, " {}={}", key, value)?;
+            }
+            Ok(())
+        }
+    }
+
+    pub trait Logger: Send + Sync {
         fn box_clone(&self) -> Box<dyn Logger>;
         fn set_name(&mut self, name: &str);
-        fn log(&self, msg: &str);
+        fn write(&self, record: &LogRecord);
+
+        fn log(&self, msg: &str) {
+            self.info(msg, &[]);
+        }
+        fn trace(&self, msg: &str, fields: &[LogField]) {
+            self.write(&LogRecord { level: LogLevel::Trace, msg, fields });
+        }
+        fn debug(&self, msg: &str, fields: &[LogField]) {
+            self.write(&LogRecord { level: LogLevel::Debug, msg, fields });
+        }
+        fn info(&self, msg: &str, fields: &[LogField]) {
+            self.write(&LogRecord { level: LogLevel::Info, msg, fields });
+        }
+        fn warn(&self, msg: &str, fields: &[LogField]) {
+            self.write(&LogRecord { level: LogLevel::Warn, msg, fields });
+        }
+        fn error(&self, msg: &str, fields: &[LogField]) {
+            self.write(&LogRecord { level: LogLevel::Error, msg, fields });
+        }
     }
 
     #[derive(Clone)]
@@ -42,7 +117,189 @@ pub mod utils {
 
         fn set_name(&mut self, _name: &str) {}
 
-        fn log(&self, _msg: &str) {}
+        fn write(&self, _record: &LogRecord) {}
+    }
+
+    /// Minimum level per logger name, parsed from `default,name=level,...`, e.g. `warn,field=debug`.
+    /// The most specific name prefix wins.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct LogFilter {
+        default: LogLevel,
+        modules: Vec<(String, LogLevel)>,
+    }
+
+    impl LogFilter {
+        pub fn new(default: LogLevel) -> LogFilter {
+            LogFilter { default, modules: vec![] }
+        }
+
+        pub fn with_module(mut self, name: &str, level: LogLevel) -> LogFilter {
+            self.modules.retain(|(module, _)| module != name);
+            self.modules.push((name.to_owned(), level));
+            self
+        }
+
+        pub fn level(&self, name: &str) -> LogLevel {
+            self.modules
+                .iter()
+                .filter(|(module, _)| name.starts_with(module.as_str()))
+                .max_by_key(|(module, _)| module.len())
+                .map(|(_, level)| *level)
+                .unwrap_or(self.default)
+        }
+
+        pub fn enabled(&self, name: &str, level: LogLevel) -> bool {
+            level >= self.level(name)
+        }
+    }
+
+    impl Default for LogFilter {
+        fn default() -> Self {
+            LogFilter::new(LogLevel::Info)
+        }
+    }
+
+    impl FromStr for LogFilter {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let mut filter = LogFilter::default();
+            for directive in s.split(',').map(|d| d.trim()).filter(|d| !d.is_empty()) {
+                match directive.split_once('=') {
+                    Some((name, level)) => {
+                        filter = filter.with_module(name.trim(), LogLevel::from_str(level.trim())?);
+                    }
+                    None => filter.default = LogLevel::from_str(directive)?,
+                }
+            }
+            Ok(filter)
+        }
+    }
+
+    struct FilteredLogger {
+        name: String,
+        inner: Box<dyn Logger>,
+        filter: Arc<LogFilter>,
+    }
+
+    impl Logger for FilteredLogger {
+        fn box_clone(&self) -> Box<dyn Logger> {
+            Box::new(FilteredLogger {
+                name: self.name.clone(),
+                inner: self.inner.box_clone(),
+                filter: self.filter.clone(),
+            })
+        }
+
+        fn set_name(&mut self, name: &str) {
+            self.name = name.to_owned();
+            self.inner.set_name(name);
+        }
+
+        fn write(&self, record: &LogRecord) {
+            if self.filter.enabled(&self.name, record.level) {
+                self.inner.write(record);
+            }
+        }
+    }
+
+    /// Forwards to the `log` facade, with `pong::<name>` as target.
+    #[derive(Clone)]
+    pub struct LogFacadeLogger {
+        target: String,
+    }
+
+    impl LogFacadeLogger {
+        pub fn root() -> LogFacadeLogger {
+            LogFacadeLogger {
+                target: String::from("pong"),
+            }
+        }
+    }
+
+    impl Logger for LogFacadeLogger {
+        fn box_clone(&self) -> Box<dyn Logger> {
+            Box::new(self.clone())
+        }
+
+        fn set_name(&mut self, name: &str) {
+            self.target = format!("pong::{}", name);
+        }
+
+        fn write(&self, record: &LogRecord) {
+            let level = match record.level {
+                LogLevel::Trace => log::Level::Trace,
+                LogLevel::Debug => log::Level::Debug,
+                LogLevel::Info => log::Level::Info,
+                LogLevel::Warn => log::Level::Warn,
+                LogLevel::Error => log::Level::Error,
+            };
+            log::log!(target: &self.target, level, "{}", record);
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use crate::utils::utils::{DefaultLoggerFactory, LogFilter, LogLevel, LogRecord, Logger};
+        use rstest::rstest;
+        use std::str::FromStr;
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Clone)]
+        struct RecordingLogger {
+            name: String,
+            lines: Arc<Mutex<Vec<String>>>,
+        }
+
+        impl Logger for RecordingLogger {
+            fn box_clone(&self) -> Box<dyn Logger> {
+                Box::new(self.clone())
+            }
+
+            fn set_name(&mut self, name: &str) {
+                self.name = name.to_owned();
+            }
+
+            fn write(&self, record: &LogRecord) {
+                let line = format!("{:?} [{}] {}", record.level, self.name, record);
+                self.lines.lock().unwrap().push(line);
+            }
+        }
+
+        #[rstest]
+        #[case("", "field", LogLevel::Info)]
+        #[case("warn", "field", LogLevel::Warn)]
+        #[case("warn,field=debug", "field", LogLevel::Debug)]
+        #[case("warn,field=debug", "collision_handler", LogLevel::Warn)]
+        #[case("error,collision=info,collision_handler=trace", "collision_handler", LogLevel::Trace)]
+        #[case("error,collision=info,collision_handler=trace", "collision_detector", LogLevel::Info)]
+        fn should_resolve_level_by_name(#[case] filter: &str, #[case] name: &str, #[case] expected: LogLevel) {
+            assert_eq!(LogFilter::from_str(filter).unwrap().level(name), expected);
+        }
+
+        #[rstest]
+        #[case("loud")]
+        #[case("info,field=loud")]
+        fn should_reject_invalid_filter(#[case] filter: &str) {
+            assert!(LogFilter::from_str(filter).is_err());
+        }
+
+        #[test]
+        fn should_only_forward_enabled_records() {
+            let lines = Arc::new(Mutex::new(vec![]));
+            let proto = RecordingLogger { name: String::new(), lines: lines.clone() };
+            let factory = DefaultLoggerFactory::filtered(Box::new(proto), LogFilter::from_str("warn,field=debug").unwrap());
+            let field_logger = factory.get("field");
+            let other_logger = factory.get("other");
+            field_logger.trace("hidden", &[]);
+            field_logger.debug("tick", &[("frame", &3), ("objs", &"ball_1")]);
+            other_logger.log("hidden");
+            other_logger.error("failed", &[("reason", &"io")]);
+            assert_eq!(
+                *lines.lock().unwrap(),
+                vec!["Debug [field] tick frame=3 objs=ball_1", "Error [other] failed reason=io"]
+            );
+        }
     }
 }
 
@@ -1,11 +1,15 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::game_loop::SERVER_ACTOR_ID;
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "actor_type")]
 pub enum Actor {
     Player(Player),
-    Observer(Observer)
+    Observer(Observer),
+    // the server itself, e.g. when it closes expired sessions.
+    Server
 }
 
 impl Actor {
@@ -13,6 +17,7 @@ impl Actor {
         match self {
             Actor::Player(p) => &p.id,
             Actor::Observer(o) => &o.id,
+            Actor::Server => SERVER_ACTOR_ID,
         }
     }
 }
@@ -0,0 +1,209 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::Duration;
+
+use crate::event::{ChatContent, ChatEventPayload};
+use crate::utils::env_utils::{secs_from_env, usize_from_env};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatConfig {
+    // in characters, longer messages are rejected.
+    pub max_length: usize,
+    // messages and emotes an actor may send within the rate window.
+    pub rate_limit: usize,
+    pub rate_window: Duration,
+    // words that are masked by the default filter, case-insensitive.
+    pub blocked_words: Vec<String>,
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        ChatConfig {
+            max_length: 200,
+            rate_limit: 5,
+            rate_window: Duration::from_secs(10),
+            blocked_words: vec![],
+        }
+    }
+}
+
+impl ChatConfig {
+    /// Reads CHAT_MAX_LENGTH, CHAT_RATE_LIMIT, CHAT_RATE_WINDOW_SECS and the comma separated CHAT_BLOCKED_WORDS.
+    pub fn from_env() -> Result<ChatConfig, String> {
+        let default = ChatConfig::default();
+        let blocked_words = match std::env::var("CHAT_BLOCKED_WORDS") {
+            Ok(words) => words.split(',').map(|w| w.trim().to_owned()).filter(|w| !w.is_empty()).collect(),
+            Err(_) => default.blocked_words,
+        };
+        Ok(ChatConfig {
+            max_length: usize_from_env("CHAT_MAX_LENGTH", default.max_length)?,
+            rate_limit: usize_from_env("CHAT_RATE_LIMIT", default.rate_limit)?,
+            rate_window: secs_from_env("CHAT_RATE_WINDOW_SECS", default.rate_window)?,
+            blocked_words,
+        })
+    }
+}
+
+/// Hook to screen chat texts before they are published, emotes are not filtered.
+pub trait ChatFilter: Send + Sync {
+    /// The text to publish, e.g. with masked words, or the reason it is rejected.
+    fn filter(&self, text: &str) -> Result<String, String>;
+}
+
+/// Masks blocked words with `*`, the rest of the text is kept as is.
+pub struct BlockedWordsFilter {
+    words: HashSet<String>,
+}
+
+impl BlockedWordsFilter {
+    pub fn new(words: &[String]) -> BlockedWordsFilter {
+        BlockedWordsFilter { words: words.iter().map(|w| w.to_lowercase()).collect() }
+    }
+
+    fn push_word(&self, filtered: &mut String, word: &str) {
+        if self.words.contains(&word.to_lowercase()) {
+            filtered.extend(std::iter::repeat_n('*', word.chars().count()));
+        } else {
+            filtered.push_str(word);
+        }
+    }
+}
+
+impl ChatFilter for BlockedWordsFilter {
+    fn filter(&self, text: &str) -> Result<String, String> {
+        let mut filtered = String::with_capacity(text.len());
+        let mut word = String::new();
+        for c in text.chars() {
+            if c.is_alphanumeric() {
+                word.push(c);
+                continue;
+            }
+            self.push_word(&mut filtered, &word);
+            word.clear();
+            filtered.push(c);
+        }
+        self.push_word(&mut filtered, &word);
+        Ok(filtered)
+    }
+}
+
+/// Checks chat messages of the actors of all sessions: length, filter and a rate limit per actor and session.
+pub struct ChatModerator {
+    config: ChatConfig,
+    filter: Box<dyn ChatFilter>,
+    // ms timestamps of the messages per session and actor within the rate window.
+    sent: HashMap<(String, String), VecDeque<u128>>,
+}
+
+impl ChatModerator {
+    pub fn new(config: ChatConfig) -> ChatModerator {
+        let filter = Box::new(BlockedWordsFilter::new(&config.blocked_words));
+        ChatModerator::with_filter(config, filter)
+    }
+
+    pub fn with_filter(config: ChatConfig, filter: Box<dyn ChatFilter>) -> ChatModerator {
+        ChatModerator { config, filter, sent: HashMap::new() }
+    }
+
+    /// The event to publish, or why the message is rejected. Rejected messages don't count towards the rate limit.
+    pub fn moderate(&mut self, session_id: &str, actor_id: &str, content: ChatContent, now: u128) -> Result<ChatEventPayload, String> {
+        let content = match content {
+            ChatContent::Text { text } => ChatContent::Text { text: self.check_text(&text)? },
+            emote => emote,
+        };
+        let sent = self.sent.entry((session_id.to_owned(), actor_id.to_owned())).or_default();
+        let window = self.config.rate_window.as_millis();
+        while sent.front().is_some_and(|ts| ts + window <= now) {
+            sent.pop_front();
+        }
+        if sent.len() >= self.config.rate_limit {
+            return Err(format!("more than {} messages within {}s", self.config.rate_limit, self.config.rate_window.as_secs()));
+        }
+        sent.push_back(now);
+        Ok(ChatEventPayload {
+            session_id: session_id.to_owned(),
+            actor_id: actor_id.to_owned(),
+            content,
+            ts: now,
+        })
+    }
+
+    pub fn remove_session(&mut self, session_id: &str) {
+        self.sent.retain(|(s, _), _| s != session_id);
+    }
+
+    fn check_text(&self, text: &str) -> Result<String, String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err("empty message".to_owned());
+        }
+        let length = text.chars().count();
+        if length > self.config.max_length {
+            return Err(format!("message of {} characters exceeds the maximum of {}", length, self.config.max_length));
+        }
+        self.filter.filter(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rstest::rstest;
+
+    use crate::chat::{BlockedWordsFilter, ChatConfig, ChatFilter, ChatModerator};
+    use crate::event::{ChatContent, Emote};
+
+    fn text(text: &str) -> ChatContent {
+        ChatContent::Text { text: text.to_owned() }
+    }
+
+    #[rstest]
+    #[case::unchanged("nice shot", "nice shot")]
+    #[case::masked("what a darn shot", "what a **** shot")]
+    #[case::case_insensitive("DARN!", "****!")]
+    #[case::whole_words_only("darned", "darned")]
+    fn should_mask_blocked_words(#[case] text: &str, #[case] expected: &str) {
+        let filter = BlockedWordsFilter::new(&["Darn".to_owned()]);
+        assert_eq!(filter.filter(text), Ok(expected.to_owned()));
+    }
+
+    #[rstest]
+    #[case::trimmed("  hi  ", Some("hi"))]
+    #[case::max_length("abcde", Some("abcde"))]
+    #[case::too_long("abcdef", None)]
+    #[case::multibyte_chars("äöüßé", Some("äöüßé"))]
+    #[case::empty("   ", None)]
+    fn should_check_text_length(#[case] message: &str, #[case] expected: Option<&str>) {
+        let mut moderator = ChatModerator::new(ChatConfig { max_length: 5, ..ChatConfig::default() });
+        let res = moderator.moderate("a", "player_1", text(message), 0).ok().map(|e| e.content);
+        assert_eq!(res, expected.map(text));
+    }
+
+    #[test]
+    fn should_limit_messages_per_actor_within_window() {
+        let mut moderator = ChatModerator::new(ChatConfig { rate_limit: 2, rate_window: Duration::from_secs(1), ..ChatConfig::default() });
+        assert!(moderator.moderate("a", "player_1", text("1"), 0).is_ok());
+        assert!(moderator.moderate("a", "player_1", ChatContent::Emote { emote: Emote::Wave }, 500).is_ok());
+        assert!(moderator.moderate("a", "player_1", text("3"), 999).is_err());
+        // other actors and sessions have their own limit.
+        assert!(moderator.moderate("a", "player_2", text("1"), 999).is_ok());
+        assert!(moderator.moderate("b", "player_1", text("1"), 999).is_ok());
+        assert!(moderator.moderate("a", "player_1", text("4"), 1_000).is_ok());
+        assert!(moderator.moderate("a", "player_1", text("5"), 1_000).is_err());
+    }
+
+    #[test]
+    fn should_not_count_rejected_messages() {
+        struct RejectAll;
+        impl ChatFilter for RejectAll {
+            fn filter(&self, _: &str) -> Result<String, String> {
+                Err("rejected".to_owned())
+            }
+        }
+        let mut moderator = ChatModerator::with_filter(ChatConfig { rate_limit: 1, ..ChatConfig::default() }, Box::new(RejectAll));
+        assert_eq!(moderator.moderate("a", "player_1", text("hi"), 0), Err("rejected".to_owned()));
+        // emotes skip the filter.
+        let emote = moderator.moderate("a", "player_1", ChatContent::Emote { emote: Emote::GoodGame }, 0);
+        assert_eq!(emote.map(|e| e.content), Ok(ChatContent::Emote { emote: Emote::GoodGame }));
+    }
+}
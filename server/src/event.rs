@@ -1,6 +1,7 @@
 use std::str::FromStr;
 use serde::{Deserialize, Serialize};
-use pong::game_field::{GameScore, Input};
+use pong::game_field::{GameScore, GameState, Input};
+use pong::pong::pong_rules::MatchFormat;
 use crate::actor::{Actor, Player};
 use crate::session::Session;
 
@@ -23,7 +24,7 @@ pub enum PongEvent {
     Input(String, InputEventPayload),
     Status(String, StatusEventPayload),
     HeartBeat(String, HeartBeatEventPayload),
-    Session(String, SessionEvent),
+    Session(String, Box<SessionEvent>),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -60,6 +61,9 @@ pub struct InputEventPayload {
     pub inputs: Vec<Input>,
     pub player_id: String,
     pub ts: u128,
+    // sequence number of the inputs, echoed in the host ticks so that the player can reconcile its prediction.
+    #[serde(default)]
+    pub input_seq: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -67,12 +71,69 @@ pub struct StatusEventPayload {
     pub session_id: String,
     pub score: GameScore,
     pub winner: Option<String>,
+    #[serde(default)]
+    pub format: MatchFormat,
+}
+
+impl StatusEventPayload {
+    pub fn from(session: &Session, state: &GameState) -> StatusEventPayload {
+        StatusEventPayload {
+            session_id: session.session_id.clone(),
+            score: state.score.clone(),
+            winner: state.winner.clone(),
+            format: session.format.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct HeartBeatEventPayload {
     pub actor_id: String,
     pub session_id: String,
+    pub ts: u128,
+    // written by the server once the connection of the actor dropped.
+    #[serde(default)]
+    pub disconnected: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Presence {
+    Connected,
+    Lagging, // heartbeats are late
+    Gone,    // heartbeats stopped
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct PresenceEventPayload {
+    pub session_id: String,
+    pub actor_id: String,
+    pub presence: Presence,
+    pub ts: u128
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Emote {
+    Wave,
+    GoodGame,
+    Laugh,
+    Wow,
+    Oops,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind")]
+pub enum ChatContent {
+    Text { text: String },
+    Emote { emote: Emote },
+}
+
+// chat messages and emotes of any actor of the session, written to the chat topic once they passed moderation.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ChatEventPayload {
+    pub session_id: String,
+    pub actor_id: String,
+    #[serde(flatten)]
+    pub content: ChatContent,
     pub ts: u128
 }
 
@@ -82,13 +143,24 @@ pub enum SessionEvent {
     Created(SessionEventPayload),
     Joined(SessionEventPayload),
     ObserverAdded(SessionEventPayload),
+    // a player connected again after its connection dropped.
+    Reconnected(SessionEventPayload),
+    // a player accepted a rematch, the other one still has to.
+    RematchAccepted(SessionEventPayload),
+    // both players accepted, the session links to its rematch.
+    Rematched(SessionEventPayload),
     Closed(SessionEventPayload),
 }
 
 impl SessionEvent {
     pub fn session_id(&self) -> &str {
+        return self.payload().session_id();
+    }
+
+    pub fn payload(&self) -> &SessionEventPayload {
         return match self {
-            SessionEvent::Created(e) | SessionEvent::Joined(e) | SessionEvent::ObserverAdded(e) | SessionEvent::Closed(e) => e.session_id()
+            SessionEvent::Created(e) | SessionEvent::Joined(e) | SessionEvent::ObserverAdded(e) | SessionEvent::Reconnected(e)
+            | SessionEvent::RematchAccepted(e) | SessionEvent::Rematched(e) | SessionEvent::Closed(e) => e
         }
     }
 }
@@ -128,11 +200,16 @@ impl FromStr for SessionEventType {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+    use rstest::rstest;
     use crate::event::{SessionEvent, SessionEventPayload};
     use crate::actor::{Actor, Observer, Player};
-    use crate::session::{Session, SessionState};
+    use crate::session::{Session, SessionMode, SessionState, SessionVisibility};
+    use pong::pong::pong_rules::MatchFormat;
 
-    const SESSION_EVENT_JSON: &str = "{\"event_type\":\"Created\",\"session\":{\"id\":1,\"hash\":\"abc\",\"state\":\"PENDING\",\"players\":[{\"id\":\"player_1\"}],\"observers\":[{\"id\":\"observer_1\"}]},\"actor\":{\"id\":\"player_1\"},\"reason\":\"some reason\"}";
+    const SESSION_EVENT_JSON: &str = "{\"event_type\":\"Created\",\"session\":{\"id\":1,\"session_id\":\"abc\",\"created_at\":0,\"state\":\"PENDING\",\"mode\":\"HOST\",\"format\":{\"format\":\"FirstTo\",\"points\":10,\"win_by_two\":false},\"visibility\":\"PRIVATE\",\"players\":[{\"id\":\"player_1\",\"ip\":\"127.0.0.1\",\"nr\":1}],\"observers\":[{\"id\":\"observer_1\",\"ip\":\"127.0.0.1\"}]},\"actor\":{\"actor_type\":\"Player\",\"id\":\"player_1\",\"ip\":\"127.0.0.1\",\"nr\":1},\"reason\":\"some reason\"}";
+    // written before sessions had a mode, format and visibility, the defaults apply.
+    const LEGACY_SESSION_EVENT_JSON: &str = "{\"event_type\":\"Created\",\"session\":{\"id\":1,\"session_id\":\"abc\",\"state\":\"PENDING\",\"players\":[{\"id\":\"player_1\",\"ip\":\"127.0.0.1\",\"nr\":1}],\"observers\":[{\"id\":\"observer_1\",\"ip\":\"127.0.0.1\"}]},\"actor\":{\"actor_type\":\"Player\",\"id\":\"player_1\",\"ip\":\"127.0.0.1\",\"nr\":1},\"reason\":\"some reason\"}";
 
     #[test]
     pub fn should_serialize_correctly() {
@@ -142,9 +219,11 @@ mod tests {
         assert_eq!(res, SESSION_EVENT_JSON);
     }
 
-    #[test]
-    pub fn should_deserialize_correctly() {
-        let res = serde_json::from_str::<SessionEvent>(&SESSION_EVENT_JSON);
+    #[rstest]
+    #[case::current(SESSION_EVENT_JSON)]
+    #[case::legacy(LEGACY_SESSION_EVENT_JSON)]
+    pub fn should_deserialize_correctly(#[case] json: &str) {
+        let res = serde_json::from_str::<SessionEvent>(json);
         assert_eq!(res.is_ok(), true);
         let res = res.unwrap();
         assert_eq!(res, get_session_event());
@@ -155,9 +234,18 @@ mod tests {
             session: Session {
                 id: 1,
                 session_id: "abc".to_owned(),
+                start_offsets: HashMap::new(),
+                created_at: 0,
+                join_code: None,
                 state: SessionState::PENDING,
+                mode: SessionMode::HOST,
+                format: MatchFormat::default(),
+                visibility: SessionVisibility::PRIVATE,
                 players: vec![Player { id: "player_1".to_owned(), nr: 1, ip: "127.0.0.1".to_owned() }],
-                observers: vec![Observer {id: "observer_1".to_owned(), ip: "127.0.0.1".to_owned()}]
+                observers: vec![Observer {id: "observer_1".to_owned(), ip: "127.0.0.1".to_owned()}],
+                rematch_accepted_by: vec![],
+                rematch_of: None,
+                rematch: None,
             },
             actor: Actor::Player(Player { id: "player_1".to_owned(), nr: 1, ip: "127.0.0.1".to_owned() }),
             reason: "some reason".to_owned(),
@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use crate::session::{Session, SessionState};
+use crate::utils::env_utils::secs_from_env;
+
+/// How long sessions are kept without player activity, per state.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionExpiryConfig {
+    // nobody joined the session.
+    pub pending_ttl: Duration,
+    // none of the players sent a message.
+    pub idle_ttl: Duration,
+    // closed sessions are only kept so that late requests still find them.
+    pub closed_ttl: Duration,
+}
+
+impl Default for SessionExpiryConfig {
+    fn default() -> Self {
+        SessionExpiryConfig {
+            pending_ttl: Duration::from_secs(10 * 60),
+            idle_ttl: Duration::from_secs(2 * 60),
+            closed_ttl: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SessionExpiry {
+    Close(String),
+    Remove,
+}
+
+impl SessionExpiryConfig {
+    /// Reads the ttls in seconds from SESSION_PENDING_TTL_SECS, SESSION_IDLE_TTL_SECS and SESSION_CLOSED_TTL_SECS.
+    pub fn from_env() -> Result<SessionExpiryConfig, String> {
+        let default = SessionExpiryConfig::default();
+        Ok(SessionExpiryConfig {
+            pending_ttl: secs_from_env("SESSION_PENDING_TTL_SECS", default.pending_ttl)?,
+            idle_ttl: secs_from_env("SESSION_IDLE_TTL_SECS", default.idle_ttl)?,
+            closed_ttl: secs_from_env("SESSION_CLOSED_TTL_SECS", default.closed_ttl)?,
+        })
+    }
+
+    pub fn check(&self, session: &Session, idle_for: Duration) -> Option<SessionExpiry> {
+        match session.state {
+            SessionState::PENDING if idle_for > self.pending_ttl => Some(SessionExpiry::Close(
+                format!("expired: nobody joined within {}s", self.pending_ttl.as_secs())
+            )),
+            SessionState::RUNNING if idle_for > self.idle_ttl => Some(SessionExpiry::Close(
+                format!("expired: no player activity for {}s", self.idle_ttl.as_secs())
+            )),
+            SessionState::CLOSED if idle_for > self.closed_ttl => Some(SessionExpiry::Remove),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rstest::rstest;
+
+    use crate::actor::Player;
+    use crate::expiry::{SessionExpiry, SessionExpiryConfig};
+    use crate::session::{Session, SessionMode, SessionState};
+    use pong::pong::pong_rules::MatchFormat;
+
+    #[rstest]
+    #[case(SessionState::PENDING, 599, None)]
+    #[case(SessionState::PENDING, 601, Some(SessionExpiry::Close("expired: nobody joined within 600s".to_owned())))]
+    #[case(SessionState::RUNNING, 119, None)]
+    #[case(SessionState::RUNNING, 121, Some(SessionExpiry::Close("expired: no player activity for 120s".to_owned())))]
+    #[case(SessionState::CLOSED, 59, None)]
+    #[case(SessionState::CLOSED, 61, Some(SessionExpiry::Remove))]
+    fn should_expire_by_state(#[case] state: SessionState, #[case] idle_secs: u64, #[case] expected: Option<SessionExpiry>) {
+        let player = Player { id: "player_1".to_owned(), nr: 1, ip: "127.0.0.1".to_owned() };
+        let mut session = Session::new(1, "abc".to_owned(), player, SessionMode::HOST, MatchFormat::default());
+        session.state = state;
+        let res = SessionExpiryConfig::default().check(&session, Duration::from_secs(idle_secs));
+        assert_eq!(res, expected);
+    }
+}
@@ -0,0 +1,259 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use log::{debug, error, info, trace};
+use pong::game_field::{Field, Input};
+use pong::game_object::game_object::GameObject;
+use pong::pong::pong_delta::DeltaEncoder;
+use pong::pong::pong_events::NoopPongEventWriter;
+use pong::utils::utils::{DefaultLoggerFactory, LogFacadeLogger};
+
+use crate::event::{InputEventPayload, Presence, PresenceEventPayload, SessionEvent, StatusEventPayload};
+use crate::session::Session;
+use crate::session_manager::{SessionReader, SessionWriter};
+use crate::snapshot::SnapshotStore;
+use crate::utils::time_utils::now;
+use crate::websocket_handler::{GameObjectStateDTO, HostSessionSnapshotDTO};
+
+pub const TICK_RATE: u64 = 60;
+pub const SERVER_ACTOR_ID: &str = "server";
+
+// Upper bound of reads per tick, so that a flood of inputs can't stall the loop.
+const MAX_READS_PER_TICK: usize = 10;
+
+/// Runs the engine of a server authoritative session.
+/// Consumes the players' inputs and publishes the resulting state as host ticks.
+/// The game is paused while a player is gone, so that they can reconnect.
+pub struct SessionGameLoop {
+    session: Session,
+    field: Field,
+    reader: SessionReader,
+    writer: SessionWriter,
+    inputs: HashMap<String, Vec<Input>>,
+    input_seqs: HashMap<String, u64>,
+    delta_encoder: DeltaEncoder,
+    snapshot_store: SnapshotStore,
+    gone_players: HashSet<String>,
+}
+
+impl SessionGameLoop {
+    pub fn new(session: Session, reader: SessionReader, writer: SessionWriter, snapshot_store: SnapshotStore) -> SessionGameLoop {
+        let mut field = Field::new(
+            DefaultLoggerFactory::new(Box::new(LogFacadeLogger::root())),
+            NoopPongEventWriter::new(),
+        );
+        field.match_format = session.format.clone();
+        SessionGameLoop {
+            session,
+            field,
+            reader,
+            writer,
+            inputs: HashMap::new(),
+            input_seqs: HashMap::new(),
+            delta_encoder: DeltaEncoder::new(),
+            snapshot_store,
+            gone_players: HashSet::new(),
+        }
+    }
+
+    pub async fn run(mut self) {
+        info!("[{}] starting server game loop with {} ticks/s", self.session.session_id, TICK_RATE);
+        let delta_sec = 1. / TICK_RATE as f64;
+        let mut interval = tokio::time::interval(Duration::from_millis(1_000 / TICK_RATE));
+        loop {
+            interval.tick().await;
+            let is_open = self.consume_events().await;
+            if !is_open {
+                info!("[{}] session was closed, stopping game loop", self.session.session_id);
+                break;
+            }
+            if !self.gone_players.is_empty() {
+                trace!("[{}] game is paused, players are gone: {:?}", self.session.session_id, self.gone_players);
+                continue;
+            }
+            let inputs = self.inputs.values().flatten().cloned().collect::<Vec<Input>>();
+            self.field.tick(inputs.clone(), delta_sec);
+            self.publish_tick(inputs).await;
+            if let Some(winner) = &self.field.game_state.winner {
+                info!("[{}] game is over, winner is {}", self.session.session_id, winner);
+                self.publish_status().await;
+                break;
+            }
+        }
+    }
+
+    async fn consume_events(&mut self) -> bool {
+        for _ in 0..MAX_READS_PER_TICK {
+            let events = self.reader.read_from_session().await;
+            if let Err(e) = events {
+                error!("[{}] failed to read events for game loop: {}", self.session.session_id, e);
+                return true;
+            }
+            let events = events.unwrap();
+            if events.is_empty() {
+                return true;
+            }
+            for event in events {
+                match event.topic.as_str() {
+                    "input" => {
+                        let payload = serde_json::from_str::<InputEventPayload>(&event.event);
+                        if let Err(e) = payload {
+                            error!("[{}] failed to deserialize input event {}: {:?}", self.session.session_id, event.event, e);
+                            continue;
+                        }
+                        let payload = payload.unwrap();
+                        let inputs = player_inputs(&self.session, &payload);
+                        trace!("[{}] updated inputs of player {}: {:?}", self.session.session_id, payload.player_id, inputs);
+                        self.input_seqs.insert(payload.player_id.clone(), payload.input_seq);
+                        self.inputs.insert(payload.player_id, inputs);
+                    }
+                    "presence" => {
+                        let payload = serde_json::from_str::<PresenceEventPayload>(&event.event);
+                        if let Err(e) = payload {
+                            error!("[{}] failed to deserialize presence event {}: {:?}", self.session.session_id, event.event, e);
+                            continue;
+                        }
+                        self.update_presence(payload.unwrap());
+                    }
+                    "session" => {
+                        let session_event = serde_json::from_str::<SessionEvent>(&event.event);
+                        if let Ok(SessionEvent::Closed(_)) = session_event {
+                            return false;
+                        }
+                    }
+                    _ => {
+                        debug!("[{}] game loop ignores event of topic {}", self.session.session_id, event.topic);
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    fn update_presence(&mut self, payload: PresenceEventPayload) {
+        if !self.session.players.iter().any(|p| p.id == payload.actor_id) {
+            return;
+        }
+        if payload.presence == Presence::Gone {
+            // the last inputs would still be held once the player is back, until it sends new ones.
+            self.inputs.remove(&payload.actor_id);
+            if self.gone_players.insert(payload.actor_id.clone()) {
+                info!("[{}] player {} is gone, pausing game", self.session.session_id, payload.actor_id);
+            }
+        } else if self.gone_players.remove(&payload.actor_id) {
+            info!("[{}] player {} is back, resuming game", self.session.session_id, payload.actor_id);
+        }
+    }
+
+    async fn publish_tick(&mut self, inputs: Vec<Input>) {
+        let checksum = self.field.checksum();
+        let state = self.field.game_state.clone();
+        let delta = self.delta_encoder.encode(&mut self.field);
+        let snapshot = HostSessionSnapshotDTO {
+            session_id: self.session.session_id.clone(),
+            inputs,
+            state,
+            objects: delta.objs.into_iter().map(GameObjectStateDTO::from).collect(),
+            player_id: SERVER_ACTOR_ID.to_owned(),
+            ts: now(),
+            checksum: Some(checksum),
+            seq: delta.seq,
+            baseline: delta.baseline,
+            acked_inputs: self.input_seqs.clone(),
+        };
+        self.snapshot_store.update(&snapshot);
+        let json = serde_json::to_string(&snapshot);
+        if let Err(e) = json {
+            error!("[{}] failed to serialize server tick: {:?}", self.session.session_id, e);
+            return;
+        }
+        let json = json.unwrap();
+        let write_res = self.writer.write_to_session("host_tick", vec![&json]).await;
+        if let Err(e) = write_res {
+            error!("[{}] failed to write server tick: {}", self.session.session_id, e);
+        }
+    }
+
+    async fn publish_status(&mut self) {
+        let status = StatusEventPayload::from(&self.session, &self.field.game_state);
+        let json = serde_json::to_string(&status).unwrap();
+        let write_res = self.writer.write_to_session("status", vec![&json]).await;
+        if let Err(e) = write_res {
+            error!("[{}] failed to write final status: {}", self.session.session_id, e);
+        }
+    }
+}
+
+/// Only keeps the inputs that target the paddle of the sending player.
+fn player_inputs(session: &Session, payload: &InputEventPayload) -> Vec<Input> {
+    let player = match session.players.iter().find(|p| p.id == payload.player_id) {
+        Some(player) => player,
+        None => return vec![],
+    };
+    let paddle_id = format!("player_{}", player.nr);
+    payload.inputs.iter().filter(|i| i.obj_id == paddle_id).cloned().collect()
+}
+
+impl GameObjectStateDTO {
+    pub fn from(obj: &Arc<RwLock<Box<dyn GameObject>>>) -> GameObjectStateDTO {
+        let obj = obj.read().unwrap();
+        let shape = obj.shape();
+        GameObjectStateDTO {
+            id: obj.id().to_owned(),
+            orientation_x: obj.orientation().x,
+            orientation_y: obj.orientation().y,
+            shape_param_1: *shape.width(),
+            shape_param_2: *shape.height(),
+            vel_x: obj.vel().x,
+            vel_y: obj.vel().y,
+            x: obj.pos().x,
+            y: obj.pos().y,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pong::game_field::{Input, InputType};
+
+    use crate::actor::Player;
+    use crate::event::InputEventPayload;
+    use crate::game_loop::player_inputs;
+    use crate::session::{Session, SessionMode};
+    use pong::pong::pong_rules::MatchFormat;
+
+    #[test]
+    fn should_drop_inputs_for_foreign_paddles() {
+        let host = Player { id: "host".to_owned(), ip: "127.0.0.1".to_owned(), nr: 1 };
+        let peer = Player { id: "peer".to_owned(), ip: "127.0.0.1".to_owned(), nr: 2 };
+        let mut session = Session::new(1, "abc".to_owned(), host, SessionMode::SERVER, MatchFormat::default());
+        session.join(peer);
+        let payload = InputEventPayload {
+            session_id: "abc".to_owned(),
+            player_id: "peer".to_owned(),
+            ts: 0,
+            input_seq: 0,
+            inputs: vec![
+                Input { input: InputType::UP, obj_id: "player_1".to_owned(), player: 1 },
+                Input { input: InputType::DOWN, obj_id: "player_2".to_owned(), player: 2 },
+            ],
+        };
+        let res = player_inputs(&session, &payload);
+        assert_eq!(res, vec![Input { input: InputType::DOWN, obj_id: "player_2".to_owned(), player: 2 }]);
+    }
+
+    #[test]
+    fn should_drop_inputs_of_unknown_players() {
+        let host = Player { id: "host".to_owned(), ip: "127.0.0.1".to_owned(), nr: 1 };
+        let session = Session::new(1, "abc".to_owned(), host, SessionMode::SERVER, MatchFormat::default());
+        let payload = InputEventPayload {
+            session_id: "abc".to_owned(),
+            player_id: "someone".to_owned(),
+            ts: 0,
+            input_seq: 0,
+            inputs: vec![Input { input: InputType::UP, obj_id: "player_1".to_owned(), player: 1 }],
+        };
+        assert!(player_inputs(&session, &payload).is_empty());
+    }
+}
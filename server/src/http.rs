@@ -2,6 +2,7 @@ use std::convert::Infallible;
 use std::net::SocketAddr;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use futures::{stream::StreamExt};
 use futures::future::err;
@@ -12,17 +13,30 @@ use hyper_tungstenite::HyperWebsocket;
 use hyper_tungstenite::tungstenite::{Error};
 use log::{debug, error, info};
 use tokio::sync::Mutex;
+use pong::codec::codec::WireFormat;
 use crate::actor::Actor;
+use crate::chat::ChatConfig;
+use crate::expiry::SessionExpiryConfig;
+use crate::join_code::JoinCodeConfig;
+use crate::matchmaking::{Matchmaker, MatchmakingConfig};
+use crate::presence::{PresenceConfig, SessionPresenceLoop};
+use crate::session::SessionState;
+use crate::token::TokenSigner;
 
 use crate::request_handler::{DefaultRequestHandler, RequestHandler};
 use crate::session_manager::{SessionManager};
 use crate::utils::http_utils::{build_error_res, get_query_params, read_json_body};
 use crate::websocket_handler::{DefaultWebsocketHandler, WebSocketConnectionType, WebsocketHandler, WebSocketSession};
 
+// how often expired sessions are looked for.
+const SESSION_EXPIRY_INTERVAL: Duration = Duration::from_secs(10);
+
 pub struct HttpServer {
     addr: [u8; 4],
     port: u16,
     session_manager: Arc<Mutex<SessionManager>>,
+    matchmaker: Arc<Mutex<Matchmaker>>,
+    token_signer: Arc<TokenSigner>,
 }
 
 impl HttpServer {
@@ -31,31 +45,63 @@ impl HttpServer {
         port: u16,
         kafka_host: &str,
         kafka_topic_manager_host: &str,
+        expiry_config: SessionExpiryConfig,
+        presence_config: PresenceConfig,
+        join_code_config: JoinCodeConfig,
+        chat_config: ChatConfig,
+        token_signer: TokenSigner,
     ) -> HttpServer {
         let session_manager = Arc::new(Mutex::new(SessionManager::new(
             kafka_host,
             kafka_topic_manager_host,
+            expiry_config,
+            presence_config,
+            join_code_config,
+            chat_config,
         )));
         HttpServer {
             addr,
             port,
             session_manager,
+            matchmaker: Arc::new(Mutex::new(Matchmaker::new(MatchmakingConfig::default()))),
+            token_signer: Arc::new(token_signer),
         }
     }
 
     pub async fn run(self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if let Err(e) = self.session_manager.lock().await.restore_sessions().await {
+            error!("failed to restore sessions, starting without them: {}", e);
+        }
+        let running_sessions = self.session_manager.lock().await.get_sessions_in_state(SessionState::RUNNING);
+        for session in running_sessions {
+            if let Err(e) = SessionPresenceLoop::start(&self.session_manager, &session).await {
+                error!("failed to start presence tracking of restored session {}: {}", session.session_id, e);
+            }
+        }
+        let expiry_session_manager = Arc::clone(&self.session_manager);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SESSION_EXPIRY_INTERVAL);
+            loop {
+                interval.tick().await;
+                expiry_session_manager.lock().await.expire_sessions().await;
+            }
+        });
         let make_svc = make_service_fn(|socket: &AddrStream| {
             let session_manager = Arc::clone(&self.session_manager);
+            let matchmaker = Arc::clone(&self.matchmaker);
+            let token_signer = Arc::clone(&self.token_signer);
             let addr = socket.remote_addr();
             async move {
                 Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
                     let session_manager = Arc::clone(&session_manager);
+                    let matchmaker = Arc::clone(&matchmaker);
+                    let token_signer = Arc::clone(&token_signer);
                     async move {
                         if hyper_tungstenite::is_upgrade_request(&req) {
-                            return handle_potential_ws_upgrade(session_manager, req, addr).await;
+                            return handle_potential_ws_upgrade(session_manager, token_signer, req, addr).await;
                         }
 
-                        return handle_http_request(session_manager, req, addr).await;
+                        return handle_http_request(session_manager, matchmaker, token_signer, req, addr).await;
                     }
                 }))
             }
@@ -71,55 +117,37 @@ impl HttpServer {
 
 }
 
-async fn handle_potential_ws_upgrade(session_manager: Arc<Mutex<SessionManager>>, req: Request<Body>, addr: SocketAddr) -> Result<Response<Body>, Infallible> {
+async fn handle_potential_ws_upgrade(session_manager: Arc<Mutex<SessionManager>>, token_signer: Arc<TokenSigner>, req: Request<Body>, addr: SocketAddr) -> Result<Response<Body>, Infallible> {
     debug!(
         "received request from {:?} to upgrade to websocket connection: {:?}",
         addr, req
     );
     let params = get_query_params(&req);
     debug!("ws request params: {:?}", params);
-    if !params.contains_key("session_id") {
-        error!("Missing session id request param for websocket connection, don't upgrade connection to ws.");
-        return build_error_res(
-            "Missing request param: session_id",
-            StatusCode::BAD_REQUEST,
-        );
-    }
-    if !params.contains_key("actor_id") {
-        error!("Missing player id request param for websocket connection, don't upgrade connection to ws.");
+    if !params.contains_key("token") {
+        error!("Missing token request param for websocket connection, don't upgrade connection to ws.");
         return build_error_res(
-            "Missing request param: player_id",
-            StatusCode::BAD_REQUEST,
+            "Missing request param: token",
+            StatusCode::UNAUTHORIZED,
         );
     }
-    if !params.contains_key("connection_type") {
-        error!("Missing connection type request param for websocket connection, don't upgrade connection to ws.");
-        let res = build_error_res(
-            "Missing request param: connection_type",
-            StatusCode::BAD_REQUEST,
-        );
-        return res;
+    // session, actor and role are taken from the token, it was issued when the actor created, joined or watched the session.
+    let token = token_signer.verify(params.get("token").unwrap());
+    if let Err(e) = token {
+        let error = format!("Invalid session token: {}", e);
+        error!("{}", error);
+        return build_error_res(error.as_str(), StatusCode::UNAUTHORIZED);
     }
-    let request_session_id = *params.get("session_id").unwrap();
-    let request_actor_id = *params.get("actor_id").unwrap();
-    let request_player_ip = addr.ip().to_string();
-    let session = session_manager.lock().await.get_session(request_session_id);
-    if let None = session {
-        let error = format!("Session does not exist: {}", request_session_id);
+    let token = token.unwrap();
+    let request_actor_id = token.actor_id.as_str();
+    let session = session_manager.lock().await.get_session(&token.session_id);
+    if session.is_none() {
+        let error = format!("Session does not exist: {}", token.session_id);
         error!("{}", error);
         return build_error_res(error.as_str(), StatusCode::NOT_FOUND);
     }
     let session = session.unwrap();
-    let connection_type_raw = params.get("connection_type").unwrap();
-    let connection_type =
-        WebSocketConnectionType::from_str(connection_type_raw);
-    if let Err(_) = connection_type {
-        let error =
-            format!("Invalid connection type: {}", connection_type_raw);
-        error!("{}", error);
-        return build_error_res(error.as_str(), StatusCode::BAD_REQUEST);
-    }
-    let connection_type = connection_type.unwrap();
+    let connection_type = token.role;
     let actor = match connection_type {
         WebSocketConnectionType::OBSERVER => {
             let matching_observer = session.observers.iter().find(|o| o.id == request_actor_id);
@@ -137,19 +165,24 @@ async fn handle_potential_ws_upgrade(session_manager: Arc<Mutex<SessionManager>>
                 error!("{}", error);
                 return build_error_res(error.as_str(), StatusCode::FORBIDDEN);
             }
-            let matching_player = matching_player.unwrap();
-            if matching_player.ip != request_player_ip {
-                let error = format!("Player with wrong ip tried to join session: {} (expected) vs {} (actual)", matching_player.ip, request_player_ip);
-                error!("{}", error);
-                return build_error_res(error.as_str(), StatusCode::FORBIDDEN);
-            }
-            Actor::Player(matching_player.clone())
+            Actor::Player(matching_player.unwrap().clone())
         }
     };
+    // json stays the default, binary has to be requested by the client.
+    let wire_format = match params.get("format") {
+        Some(format) => WireFormat::from_str(format),
+        None => Ok(WireFormat::Json)
+    };
+    if let Err(e) = wire_format {
+        error!("{}", e);
+        return build_error_res(e.as_str(), StatusCode::BAD_REQUEST);
+    }
+    let wire_format = wire_format.unwrap();
     let websocket_session = WebSocketSession {
         session: session.clone(),
         connection_type,
-        actor
+        actor,
+        wire_format
     };
     debug!("websocket upgrade request is valid, will now upgrade to websocket: {:?}", req);
 
@@ -159,7 +192,7 @@ async fn handle_potential_ws_upgrade(session_manager: Arc<Mutex<SessionManager>>
     // Spawn a task to handle the websocket connection.
     tokio::spawn(async move {
         if let Err(e) =
-        serve_websocket(websocket_session, websocket, session_manager)
+        serve_websocket(websocket_session, websocket, session_manager, token_signer)
             .await
         {
             error!("Error in websocket connection: {:?}", e);
@@ -176,20 +209,23 @@ async fn serve_websocket(
     websocket_session: WebSocketSession,
     websocket: HyperWebsocket,
     session_manager: Arc<Mutex<SessionManager>>,
+    token_signer: Arc<TokenSigner>,
 ) -> Result<(), Error> {
     let handler = DefaultWebsocketHandler::new(
-        websocket_session, websocket, session_manager,
+        websocket_session, websocket, session_manager, token_signer,
     );
     handler.serve().await
 }
 
 async fn handle_http_request(
     session_manager: Arc<Mutex<SessionManager>>,
+    matchmaker: Arc<Mutex<Matchmaker>>,
+    token_signer: Arc<TokenSigner>,
     req: Request<Body>,
     addr: SocketAddr,
 ) -> Result<Response<Body>, Infallible> {
     let handler = DefaultRequestHandler::new(
-        session_manager
+        session_manager, matchmaker, token_signer
     );
     handler.handle(req, addr).await
 }
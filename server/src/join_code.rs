@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use uuid::Uuid;
+
+use crate::utils::env_utils::secs_from_env;
+
+// no 0/O, 1/I/L, so that codes can be read out and typed without mix-ups.
+const JOIN_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKMNPQRSTUVWXYZ23456789";
+pub const JOIN_CODE_LENGTH: usize = 6;
+// attempts to find a code that is not in use, before issuing fails.
+const MAX_ISSUE_ATTEMPTS: usize = 16;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JoinCodeConfig {
+    // codes can only be used to join within this time after they were issued.
+    pub ttl: Duration,
+}
+
+impl Default for JoinCodeConfig {
+    fn default() -> Self {
+        JoinCodeConfig {
+            ttl: Duration::from_secs(10 * 60),
+        }
+    }
+}
+
+impl JoinCodeConfig {
+    /// Reads the ttl in seconds from JOIN_CODE_TTL_SECS.
+    pub fn from_env() -> Result<JoinCodeConfig, String> {
+        let default = JoinCodeConfig::default();
+        Ok(JoinCodeConfig {
+            ttl: secs_from_env("JOIN_CODE_TTL_SECS", default.ttl)?,
+        })
+    }
+}
+
+struct IssuedJoinCode {
+    session_id: String,
+    expires_at: u128,
+}
+
+/// Short codes to join pending sessions, they are random and independent of the session id.
+pub struct JoinCodes {
+    config: JoinCodeConfig,
+    codes: HashMap<String, IssuedJoinCode>,
+}
+
+impl JoinCodes {
+    pub fn new(config: JoinCodeConfig) -> JoinCodes {
+        JoinCodes { config, codes: HashMap::new() }
+    }
+
+    pub fn issue(&mut self, session_id: &str, now: u128) -> Result<String, String> {
+        self.issue_with(session_id, now, random_code)
+    }
+
+    fn issue_with(&mut self, session_id: &str, now: u128, mut generate: impl FnMut() -> String) -> Result<String, String> {
+        self.remove_expired(now);
+        for _ in 0..MAX_ISSUE_ATTEMPTS {
+            let code = generate();
+            if self.codes.contains_key(&code) {
+                continue;
+            }
+            self.insert(&code, session_id, now);
+            return Ok(code);
+        }
+        Err(format!("failed to find a free join code after {} attempts", MAX_ISSUE_ATTEMPTS))
+    }
+
+    /// Re-registers the code of a restored session, it gets a full ttl.
+    pub fn restore(&mut self, code: &str, session_id: &str, now: u128) {
+        self.insert(code, session_id, now);
+    }
+
+    /// The session of a code that is still valid, codes are case-insensitive.
+    pub fn resolve(&self, code: &str, now: u128) -> Option<String> {
+        self.codes.get(&code.trim().to_uppercase())
+            .filter(|issued| issued.expires_at > now)
+            .map(|issued| issued.session_id.clone())
+    }
+
+    /// Invalidates the code of the session, e.g. once it was joined.
+    pub fn revoke(&mut self, session_id: &str) {
+        self.codes.retain(|_, issued| issued.session_id != session_id);
+    }
+
+    pub fn remove_expired(&mut self, now: u128) {
+        self.codes.retain(|_, issued| issued.expires_at > now);
+    }
+
+    fn insert(&mut self, code: &str, session_id: &str, now: u128) {
+        self.codes.insert(code.to_owned(), IssuedJoinCode {
+            session_id: session_id.to_owned(),
+            expires_at: now + self.config.ttl.as_millis(),
+        });
+    }
+}
+
+// random bytes of a v4 uuid, bytes beyond the last full multiple of the alphabet are skipped to avoid a bias.
+fn random_code() -> String {
+    let limit = (u8::MAX as usize + 1) / JOIN_CODE_ALPHABET.len() * JOIN_CODE_ALPHABET.len();
+    let mut code = String::with_capacity(JOIN_CODE_LENGTH);
+    while code.len() < JOIN_CODE_LENGTH {
+        for byte in Uuid::new_v4().as_bytes() {
+            if (*byte as usize) < limit && code.len() < JOIN_CODE_LENGTH {
+                code.push(JOIN_CODE_ALPHABET[*byte as usize % JOIN_CODE_ALPHABET.len()] as char);
+            }
+        }
+    }
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rstest::rstest;
+
+    use crate::join_code::{random_code, JoinCodeConfig, JoinCodes, JOIN_CODE_ALPHABET, JOIN_CODE_LENGTH};
+
+    #[test]
+    fn should_generate_unambiguous_codes() {
+        for _ in 0..100 {
+            let code = random_code();
+            assert_eq!(code.len(), JOIN_CODE_LENGTH);
+            assert!(code.bytes().all(|c| JOIN_CODE_ALPHABET.contains(&c)), "unexpected character in {}", code);
+        }
+    }
+
+    #[rstest]
+    #[case::same_code("ABC234", 0, Some("a"))]
+    #[case::lowercase("abc234", 0, Some("a"))]
+    #[case::before_expiry("ABC234", 59_999, Some("a"))]
+    #[case::expired("ABC234", 60_000, None)]
+    #[case::unknown("ABC235", 0, None)]
+    fn should_resolve_valid_codes(#[case] code: &str, #[case] now: u128, #[case] expected: Option<&str>) {
+        let mut codes = JoinCodes::new(JoinCodeConfig { ttl: Duration::from_secs(60) });
+        codes.restore("ABC234", "a", 0);
+        assert_eq!(codes.resolve(code, now), expected.map(|s| s.to_owned()));
+    }
+
+    #[test]
+    fn should_skip_codes_in_use() {
+        let mut codes = JoinCodes::new(JoinCodeConfig::default());
+        let mut candidates = vec!["BBBBBB", "AAAAAA", "AAAAAA"];
+        assert_eq!(codes.issue_with("a", 0, || candidates.pop().unwrap().to_owned()), Ok("AAAAAA".to_owned()));
+        assert_eq!(codes.issue_with("b", 0, || candidates.pop().unwrap().to_owned()), Ok("BBBBBB".to_owned()));
+        assert_eq!(codes.resolve("AAAAAA", 0), Some("a".to_owned()));
+        assert_eq!(codes.resolve("BBBBBB", 0), Some("b".to_owned()));
+        assert!(codes.issue_with("c", 0, || "AAAAAA".to_owned()).is_err());
+    }
+
+    #[test]
+    fn should_free_revoked_codes() {
+        let mut codes = JoinCodes::new(JoinCodeConfig::default());
+        codes.restore("AAAAAA", "a", 0);
+        codes.revoke("a");
+        assert_eq!(codes.resolve("AAAAAA", 0), None);
+        assert_eq!(codes.issue_with("b", 0, || "AAAAAA".to_owned()), Ok("AAAAAA".to_owned()));
+    }
+}
@@ -11,7 +11,7 @@ use hyper::{Body, Client, Method, Request, Uri};
 use log::{debug, error, info, trace};
 use rskafka::client::ClientBuilder;
 use rskafka::client::consumer::{StartOffset, StreamConsumer, StreamConsumerBuilder};
-use rskafka::client::partition::{Compression, PartitionClient};
+use rskafka::client::partition::{Compression, OffsetAt, PartitionClient};
 use rskafka::record::Record;
 use rskafka::time::OffsetDateTime;
 use serde::Deserialize;
@@ -104,6 +104,106 @@ async fn write_events(events: Vec<EventWrapper>, producer: &mut PartitionClient)
     res
 }
 
+/// Reads the events that exist in all partitions of the topic at the time of the call.
+/// Events of a partition keep their order, partitions are read one after the other.
+pub async fn read_topic(host: &str, topic: &str) -> Result<Vec<EventWrapper>, String> {
+    let client = ClientBuilder::new(vec![host.to_owned()]).build().await;
+    if let Err(e) = client {
+        let error = format!("Failed to connect to kafka host {}: {:?}", host, e);
+        error!("{}", error);
+        return Err(error);
+    }
+    let client = client.unwrap();
+    let topics = client.list_topics().await;
+    if let Err(e) = topics {
+        let error = format!("Failed to list kafka topics: {:?}", e);
+        error!("{}", error);
+        return Err(error);
+    }
+    let partitions = match topics.unwrap().into_iter().find(|t| t.name == topic) {
+        Some(t) => t.partitions,
+        None => {
+            info!("topic {} does not exist yet, nothing to read", topic);
+            return Ok(vec![]);
+        }
+    };
+    let mut events = vec![];
+    for partition in partitions {
+        let partition_client = client.partition_client(topic.to_owned(), partition).await;
+        if let Err(e) = partition_client {
+            let error = format!("Failed to connect to {} / {}: {:?}", topic, partition, e);
+            error!("{}", error);
+            return Err(error);
+        }
+        let partition_client = partition_client.unwrap();
+        let offset = partition_client.get_offset(OffsetAt::Earliest).await;
+        if let Err(e) = offset {
+            let error = format!("Failed to get earliest offset of {} / {}: {:?}", topic, partition, e);
+            error!("{}", error);
+            return Err(error);
+        }
+        let mut offset = offset.unwrap();
+        loop {
+            let fetched = partition_client.fetch_records(offset, 1..1_000_000, 1_000).await;
+            if let Err(e) = fetched {
+                let error = format!("Failed to fetch records of {} / {} at offset {}: {:?}", topic, partition, offset, e);
+                error!("{}", error);
+                return Err(error);
+            }
+            let (records, high_watermark) = fetched.unwrap();
+            if records.is_empty() {
+                break;
+            }
+            for record in records {
+                offset = record.offset + 1;
+                let event = match record.record.value {
+                    Some(e) => String::from_utf8(e),
+                    None => continue,
+                };
+                if let Err(e) = event {
+                    error!("skipping record of {} / {} that is not utf8: {:?}", topic, partition, e);
+                    continue;
+                }
+                let key = record.record.key.and_then(|k| String::from_utf8(k).ok());
+                events.push(EventWrapper { topic: topic.to_owned(), key, event: event.unwrap() });
+            }
+            if offset >= high_watermark {
+                break;
+            }
+        }
+        debug!("read {} / {} up to offset {}", topic, partition, offset);
+    }
+    Ok(events)
+}
+
+/// The offsets at which the next events of the partition will be written, per topic.
+pub async fn latest_offsets(host: &str, partition: &i32, topics: &[&str]) -> Result<HashMap<String, i64>, String> {
+    let client = ClientBuilder::new(vec![host.to_owned()]).build().await;
+    if let Err(e) = client {
+        let error = format!("Failed to connect to kafka host {}: {:?}", host, e);
+        error!("{}", error);
+        return Err(error);
+    }
+    let client = client.unwrap();
+    let mut offsets = HashMap::new();
+    for topic in topics {
+        let partition_client = client.partition_client(topic.to_string(), *partition).await;
+        if let Err(e) = partition_client {
+            let error = format!("Failed to connect to {} / {}: {:?}", topic, partition, e);
+            error!("{}", error);
+            return Err(error);
+        }
+        let offset = partition_client.unwrap().get_offset(OffsetAt::Latest).await;
+        if let Err(e) = offset {
+            let error = format!("Failed to get latest offset of {} / {}: {:?}", topic, partition, e);
+            error!("{}", error);
+            return Err(error);
+        }
+        offsets.insert(topic.to_string(), offset.unwrap());
+    }
+    Ok(offsets)
+}
+
 pub struct KafkaEventReaderImpl {
     consumer: StreamConsumer,
     topic: String,
@@ -119,6 +219,7 @@ impl KafkaEventReaderImpl {
         host: &str,
         partition: &i32,
         topic: &str,
+        start_offset: StartOffset,
     ) -> Result<KafkaEventReaderImpl, String> {
         debug!("connecting partition specific consumer to kafka host {} with topic {:?} / partition {:?}", host, topic, partition);
         let partition_client = ClientBuilder::new(vec![host.to_owned()]).build().await.unwrap()
@@ -132,7 +233,7 @@ impl KafkaEventReaderImpl {
         let partition_client = Arc::new(partition_client.unwrap());
         let consumer = StreamConsumerBuilder::new(
             partition_client,
-            StartOffset::Earliest
+            start_offset
         ).with_max_wait_ms(1).build();
         debug!("successfully connected partition specific consumer to kafka host {} with topic {:?} / partition {:?}", host, topic, partition);
         Ok(KafkaEventReaderImpl { consumer, topic: topic.to_owned(), partition: partition.clone() })
@@ -198,10 +299,16 @@ impl KafkaSessionEventReaderImpl {
         host: &str,
         session: &Session,
         topics: &[&str],
+        start_offset: StartOffset,
     ) -> Result<KafkaSessionEventReaderImpl, String> {
         let mut reader_map = HashMap::new();
         for topic in topics {
-            let reader = KafkaEventReaderImpl::for_partition(host, &i32::from(session.id), *topic).await;
+            // on a reused partition the events of earlier sessions are skipped.
+            let start_offset = match (start_offset, session.start_offsets.get(*topic)) {
+                (StartOffset::Earliest, Some(offset)) => StartOffset::At(*offset),
+                _ => start_offset,
+            };
+            let reader = KafkaEventReaderImpl::for_partition(host, &i32::from(session.id), *topic, start_offset).await;
             if let Err(_) = reader {
                 return Err("Failed to create kafka session event reader".to_string());
             }
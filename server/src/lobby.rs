@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use pong::pong::pong_rules::MatchFormat;
+
+use crate::session::{Session, SessionMode, SessionState, SessionVisibility};
+
+const DEFAULT_PAGE_SIZE: usize = 20;
+const MAX_PAGE_SIZE: usize = 100;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionSortKey {
+    Created,
+    Observers,
+}
+
+/// Filters, sorting and pagination of the lobby, only public sessions are listed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionQuery {
+    pub state: Option<SessionState>,
+    pub has_free_slot: Option<bool>,
+    pub observable: Option<bool>,
+    pub sort: SessionSortKey,
+    pub descending: bool,
+    pub page: usize,
+    pub page_size: usize,
+}
+
+impl Default for SessionQuery {
+    fn default() -> Self {
+        SessionQuery {
+            state: None,
+            has_free_slot: None,
+            observable: None,
+            sort: SessionSortKey::Created,
+            descending: true,
+            page: 0,
+            page_size: DEFAULT_PAGE_SIZE,
+        }
+    }
+}
+
+impl SessionQuery {
+    /// Reads the query params `state`, `has_free_slot`, `observable`, `sort`, `page` and `page_size`.
+    /// `sort` is `created` or `observers`, prefixed with `-` for descending order. Newest sessions come first by default.
+    pub fn from_params(params: &HashMap<&str, &str>) -> Result<SessionQuery, String> {
+        let mut query = SessionQuery::default();
+        if let Some(state) = params.get("state") {
+            let state = SessionState::from_str(state).map_err(|_| format!("Invalid state: {}", state))?;
+            query.state = Some(state);
+        }
+        query.has_free_slot = parse_param(params, "has_free_slot")?;
+        query.observable = parse_param(params, "observable")?;
+        if let Some(sort) = params.get("sort") {
+            query.descending = sort.starts_with('-');
+            query.sort = match sort.trim_start_matches('-') {
+                "created" => SessionSortKey::Created,
+                "observers" => SessionSortKey::Observers,
+                _ => return Err(format!("Invalid sort: {}", sort)),
+            };
+        }
+        query.page = parse_param(params, "page")?.unwrap_or(0);
+        query.page_size = parse_param(params, "page_size")?.unwrap_or(DEFAULT_PAGE_SIZE);
+        if query.page_size == 0 || query.page_size > MAX_PAGE_SIZE {
+            return Err(format!("Invalid page_size, must be between 1 and {}", MAX_PAGE_SIZE));
+        }
+        Ok(query)
+    }
+
+    pub fn matches(&self, session: &Session) -> bool {
+        session.visibility == SessionVisibility::PUBLIC
+            && self.state.as_ref().is_none_or(|state| session.state == *state)
+            && self.has_free_slot.is_none_or(|free| session.has_free_slot() == free)
+            && self.observable.is_none_or(|observable| session.is_observable() == observable)
+    }
+
+    pub fn apply(&self, sessions: &[Session]) -> SessionPage {
+        let mut matching = sessions.iter().filter(|s| self.matches(s)).collect::<Vec<&Session>>();
+        match self.sort {
+            // partitions are reused, their ids only break ties of sessions created before the time was recorded.
+            SessionSortKey::Created => matching.sort_by_key(|s| (s.created_at, s.id)),
+            // ties are broken by the creation order.
+            SessionSortKey::Observers => matching.sort_by_key(|s| (s.observers.len(), s.created_at, s.id)),
+        }
+        if self.descending {
+            matching.reverse();
+        }
+        let total = matching.len();
+        let sessions = matching.into_iter()
+            // page comes from the query string, pages beyond the last one are empty.
+            .skip(self.page.saturating_mul(self.page_size))
+            .take(self.page_size)
+            .map(SessionSummaryDTO::from)
+            .collect();
+        SessionPage { sessions, page: self.page, page_size: self.page_size, total }
+    }
+}
+
+fn parse_param<T: FromStr>(params: &HashMap<&str, &str>, key: &str) -> Result<Option<T>, String> {
+    match params.get(key) {
+        None => Ok(None),
+        Some(value) => value.parse::<T>()
+            .map(Some)
+            .map_err(|_| format!("Invalid {}: {}", key, value)),
+    }
+}
+
+/// What the lobby shows of a session, the actors' ips are left out.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct SessionSummaryDTO {
+    pub session_id: String,
+    pub state: SessionState,
+    pub mode: SessionMode,
+    pub format: MatchFormat,
+    pub players: usize,
+    pub observers: usize,
+    pub has_free_slot: bool,
+    pub observable: bool,
+}
+
+impl SessionSummaryDTO {
+    pub fn from(session: &Session) -> SessionSummaryDTO {
+        SessionSummaryDTO {
+            session_id: session.session_id.clone(),
+            state: session.state.clone(),
+            mode: session.mode.clone(),
+            format: session.format.clone(),
+            players: session.players.len(),
+            observers: session.observers.len(),
+            has_free_slot: session.has_free_slot(),
+            observable: session.is_observable(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct SessionPage {
+    pub sessions: Vec<SessionSummaryDTO>,
+    pub page: usize,
+    pub page_size: usize,
+    // matching sessions across all pages.
+    pub total: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rstest::rstest;
+
+    use crate::actor::{Observer, Player};
+    use crate::lobby::{SessionQuery, SessionSortKey};
+    use crate::session::{Session, SessionMode, SessionState, SessionVisibility};
+    use pong::pong::pong_rules::MatchFormat;
+
+    fn session(id: u16, state: SessionState, observers: usize, visibility: SessionVisibility) -> Session {
+        let host = Player { id: "host".to_owned(), ip: "127.0.0.1".to_owned(), nr: 1 };
+        let mut session = Session::new(id, format!("s{}", id), host, SessionMode::HOST, MatchFormat::default());
+        if state != SessionState::PENDING {
+            session.join(Player { id: "peer".to_owned(), ip: "127.0.0.1".to_owned(), nr: 2 });
+        }
+        session.state = state;
+        session.created_at = u64::from(id) * 1_000;
+        session.visibility = visibility;
+        session.observers = (0..observers).map(|i| Observer { id: format!("o{}", i), ip: "127.0.0.1".to_owned() }).collect();
+        session
+    }
+
+    fn sessions() -> Vec<Session> {
+        vec![
+            session(1, SessionState::PENDING, 0, SessionVisibility::PUBLIC),
+            session(2, SessionState::RUNNING, 2, SessionVisibility::PUBLIC),
+            session(3, SessionState::RUNNING, 6, SessionVisibility::PUBLIC),
+            session(4, SessionState::PENDING, 0, SessionVisibility::PRIVATE),
+            session(5, SessionState::CLOSED, 1, SessionVisibility::PUBLIC),
+            session(6, SessionState::RUNNING, 0, SessionVisibility::PUBLIC),
+        ]
+    }
+
+    fn ids(query: &SessionQuery) -> Vec<String> {
+        query.apply(&sessions()).sessions.into_iter().map(|s| s.session_id).collect()
+    }
+
+    #[rstest]
+    #[case("", vec!["s6", "s5", "s3", "s2", "s1"])]
+    #[case("state=running", vec!["s6", "s3", "s2"])]
+    #[case("has_free_slot=true", vec!["s1"])]
+    #[case("observable=true", vec!["s6", "s2"])]
+    #[case("observable=false&sort=created", vec!["s1", "s3", "s5"])]
+    #[case("sort=-observers", vec!["s3", "s2", "s5", "s6", "s1"])]
+    #[case("page=1&page_size=2", vec!["s3", "s2"])]
+    #[case("page=3&page_size=2", vec![])]
+    #[case("page=18446744073709551615&page_size=100", vec![])]
+    fn should_filter_sort_and_page_public_sessions(#[case] params: &str, #[case] expected: Vec<&str>) {
+        let params = params.split('&')
+            .filter(|p| !p.is_empty())
+            .map(|p| p.split_once('=').unwrap())
+            .collect::<HashMap<&str, &str>>();
+        let query = SessionQuery::from_params(&params).unwrap();
+        assert_eq!(ids(&query), expected);
+    }
+
+    #[test]
+    fn should_count_all_matching_sessions() {
+        let query = SessionQuery { page_size: 2, ..SessionQuery::default() };
+        let page = query.apply(&sessions());
+        assert_eq!(page.sessions.len(), 2);
+        assert_eq!(page.total, 5);
+    }
+
+    #[rstest]
+    #[case("state", "gone")]
+    #[case("has_free_slot", "yes")]
+    #[case("sort", "players")]
+    #[case("page_size", "0")]
+    #[case("page_size", "101")]
+    fn should_reject_invalid_params(#[case] key: &str, #[case] value: &str) {
+        let params = HashMap::from([(key, value)]);
+        assert!(SessionQuery::from_params(&params).is_err());
+    }
+
+    #[test]
+    fn should_sort_by_creation_time_on_reused_partitions() {
+        let mut sessions = sessions();
+        // the newest session got the partition of a removed one.
+        sessions[0].created_at = 10_000;
+        let page = SessionQuery::default().apply(&sessions);
+        let ids = page.sessions.into_iter().map(|s| s.session_id).collect::<Vec<String>>();
+        assert_eq!(ids, vec!["s1", "s6", "s5", "s3", "s2"]);
+    }
+
+    #[test]
+    fn should_default_to_newest_first() {
+        let query = SessionQuery::from_params(&HashMap::new()).unwrap();
+        assert_eq!(query.sort, SessionSortKey::Created);
+        assert!(query.descending);
+    }
+}
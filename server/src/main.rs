@@ -10,9 +10,13 @@ use log4rs::append::rolling_file::RollingFileAppender;
 use log4rs::Config;
 use log4rs::config::{Appender, Logger, Root};
 use log4rs::encode::pattern::PatternEncoder;
+use crate::chat::ChatConfig;
+use crate::expiry::SessionExpiryConfig;
+use crate::join_code::JoinCodeConfig;
 use crate::http::HttpServer;
+use crate::presence::PresenceConfig;
+use crate::token::TokenSigner;
 
-mod hash;
 pub mod http;
 pub mod kafka;
 mod session_manager;
@@ -22,6 +26,16 @@ mod request_handler;
 mod event;
 mod actor;
 mod session;
+mod game_loop;
+mod snapshot;
+mod expiry;
+mod presence;
+mod lobby;
+mod matchmaking;
+mod token;
+mod rematch;
+mod join_code;
+mod chat;
 
 #[tokio::main]
 pub async fn main() {
@@ -48,6 +62,13 @@ pub async fn main() {
                 .additive(true)
                 .build("server", LevelFilter::Trace)
         )
+        .logger(
+            // engine diagnostics, targets are pong::<logger name>.
+            Logger::builder()
+                .appender("logfile")
+                .additive(true)
+                .build("pong", LevelFilter::Debug)
+        )
         .build(Root::builder()
             .appender("stdout")
             .build(LevelFilter::Info)).unwrap();
@@ -72,8 +93,43 @@ pub async fn main() {
     };
     info!("KAFKA_TOPIC_MANAGER_HOST={}", kafka_topic_manager_host);
 
+    let expiry_config = SessionExpiryConfig::from_env();
+    if let Err(e) = expiry_config {
+        error!("{}", e);
+        return;
+    }
+    let expiry_config = expiry_config.unwrap();
+    info!("session expiry: {:?}", expiry_config);
+    let presence_config = PresenceConfig::from_env();
+    if let Err(e) = presence_config {
+        error!("{}", e);
+        return;
+    }
+    let presence_config = presence_config.unwrap();
+    info!("presence: {:?}", presence_config);
+    let join_code_config = JoinCodeConfig::from_env();
+    if let Err(e) = join_code_config {
+        error!("{}", e);
+        return;
+    }
+    let join_code_config = join_code_config.unwrap();
+    info!("join codes: {:?}", join_code_config);
+    let chat_config = ChatConfig::from_env();
+    if let Err(e) = chat_config {
+        error!("{}", e);
+        return;
+    }
+    let chat_config = chat_config.unwrap();
+    info!("chat: {:?}", chat_config);
+    let token_signer = TokenSigner::from_env();
+    if let Err(e) = token_signer {
+        error!("{}", e);
+        return;
+    }
+    let token_signer = token_signer.unwrap();
+
     info!("booting up server");
-    HttpServer::new([0, 0, 0, 0], 4000, &kafka_host, &kafka_topic_manager_host)
+    HttpServer::new([0, 0, 0, 0], 4000, &kafka_host, &kafka_topic_manager_host, expiry_config, presence_config, join_code_config, chat_config, token_signer)
         .run()
         .await
         .expect("failed to run server");
@@ -0,0 +1,390 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, Notify};
+use uuid::Uuid;
+
+use pong::pong::pong_rules::MatchFormat;
+
+use crate::actor::{Actor, Player};
+use crate::event::SessionEvent;
+use crate::presence::SessionPresenceLoop;
+use crate::session::{Session, SessionMode, SessionVisibility};
+use crate::session_manager::SessionManager;
+use crate::token::TokenSigner;
+use crate::utils::time_utils::now;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchmakingConfig {
+    // queued tickets that are not polled for this long are dropped, so are unfetched matches.
+    pub ticket_ttl: Duration,
+    // max rating difference of two players, it grows while they wait.
+    pub rating_window: u32,
+    pub rating_window_growth_per_sec: u32,
+}
+
+impl Default for MatchmakingConfig {
+    fn default() -> Self {
+        MatchmakingConfig {
+            ticket_ttl: Duration::from_secs(30),
+            rating_window: 100,
+            rating_window_growth_per_sec: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MatchmakingRequest {
+    #[serde(default)]
+    pub mode: SessionMode,
+    #[serde(default)]
+    pub format: MatchFormat,
+    // players with a rating are only paired with players of a similar rating.
+    #[serde(default)]
+    pub rating: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchmakingTicket {
+    pub ticket_id: String,
+    pub ip: String,
+    pub request: MatchmakingRequest,
+    pub queued_at: u128,
+    last_poll: u128,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub enum MatchRole {
+    HOST,
+    PEER,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status")]
+pub enum MatchmakingStatus {
+    Queued { ticket_id: String },
+    Matched { ticket_id: String, role: MatchRole, session: Session, actor: Actor, token: String },
+    Failed { ticket_id: String, reason: String },
+}
+
+impl MatchmakingStatus {
+    pub fn ticket_id(&self) -> &str {
+        match self {
+            MatchmakingStatus::Queued { ticket_id }
+            | MatchmakingStatus::Matched { ticket_id, .. }
+            | MatchmakingStatus::Failed { ticket_id, .. } => ticket_id,
+        }
+    }
+}
+
+/// Players waiting for an opponent, in the order they queued.
+pub struct MatchmakingQueue {
+    config: MatchmakingConfig,
+    tickets: Vec<MatchmakingTicket>,
+}
+
+impl MatchmakingQueue {
+    pub fn new(config: MatchmakingConfig) -> MatchmakingQueue {
+        MatchmakingQueue { config, tickets: vec![] }
+    }
+
+    pub fn enqueue(&mut self, ip: String, request: MatchmakingRequest, now: u128) -> String {
+        let ticket_id = Uuid::new_v4().to_string();
+        self.tickets.push(MatchmakingTicket { ticket_id: ticket_id.clone(), ip, request, queued_at: now, last_poll: now });
+        ticket_id
+    }
+
+    /// Keeps the ticket queued, returns false if it is not queued.
+    pub fn touch(&mut self, ticket_id: &str, now: u128) -> bool {
+        match self.tickets.iter_mut().find(|t| t.ticket_id == ticket_id) {
+            Some(ticket) => {
+                ticket.last_poll = now;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove(&mut self, ticket_id: &str) -> bool {
+        let queued = self.tickets.len();
+        self.tickets.retain(|t| t.ticket_id != ticket_id);
+        self.tickets.len() != queued
+    }
+
+    pub fn remove_stale(&mut self, now: u128) -> Vec<String> {
+        let ttl = self.config.ticket_ttl.as_millis();
+        let (stale, active) = self.tickets.drain(..).partition(|t| now.saturating_sub(t.last_poll) > ttl);
+        self.tickets = active;
+        stale.into_iter().map(|t: MatchmakingTicket| t.ticket_id).collect()
+    }
+
+    /// Takes the ticket and its closest compatible opponent out of the queue, the one that waited longer hosts.
+    pub fn take_match(&mut self, ticket_id: &str, now: u128) -> Option<(MatchmakingTicket, MatchmakingTicket)> {
+        let ticket = self.tickets.iter().find(|t| t.ticket_id == ticket_id)?;
+        let opponent = self.tickets.iter()
+            .filter(|t| t.ticket_id != ticket_id && self.is_compatible(ticket, t, now))
+            .min_by_key(|t| (rating_distance(ticket, t), t.queued_at))?
+            .ticket_id.clone();
+        let ticket_id = ticket_id.to_owned();
+        let mut pair = self.tickets.iter()
+            .filter(|t| t.ticket_id == ticket_id || t.ticket_id == opponent)
+            .cloned()
+            .collect::<Vec<MatchmakingTicket>>();
+        self.tickets.retain(|t| t.ticket_id != ticket_id && t.ticket_id != opponent);
+        // tickets are kept in queue order.
+        let peer = pair.pop().unwrap();
+        let host = pair.pop().unwrap();
+        Some((host, peer))
+    }
+
+    fn is_compatible(&self, a: &MatchmakingTicket, b: &MatchmakingTicket, now: u128) -> bool {
+        if a.request.mode != b.request.mode || a.request.format != b.request.format {
+            return false;
+        }
+        match (a.request.rating, b.request.rating) {
+            (Some(_), Some(_)) => rating_distance(a, b) <= self.rating_window(a, now).max(self.rating_window(b, now)),
+            _ => true,
+        }
+    }
+
+    fn rating_window(&self, ticket: &MatchmakingTicket, now: u128) -> u32 {
+        let waited_secs = (now.saturating_sub(ticket.queued_at) / 1_000) as u32;
+        self.config.rating_window.saturating_add(self.config.rating_window_growth_per_sec.saturating_mul(waited_secs))
+    }
+}
+
+// unrated players are as close to everyone.
+fn rating_distance(a: &MatchmakingTicket, b: &MatchmakingTicket) -> u32 {
+    match (a.request.rating, b.request.rating) {
+        (Some(a), Some(b)) => a.abs_diff(b),
+        _ => 0,
+    }
+}
+
+/// The queue and the outcomes of matched tickets until their players fetched them.
+pub struct Matchmaker {
+    queue: MatchmakingQueue,
+    // tickets taken out of the queue whose session is still being started, they are reported as queued.
+    matching: HashSet<String>,
+    outcomes: HashMap<String, (MatchmakingStatus, u128)>,
+    ticket_ttl: Duration,
+    // woken whenever tickets were matched, waiting players check their ticket then.
+    notify: Arc<Notify>,
+}
+
+impl Matchmaker {
+    pub fn new(config: MatchmakingConfig) -> Matchmaker {
+        Matchmaker {
+            ticket_ttl: config.ticket_ttl,
+            queue: MatchmakingQueue::new(config),
+            matching: HashSet::new(),
+            outcomes: HashMap::new(),
+            notify: Arc::new(Notify::new()),
+        }
+    }
+
+    pub fn enqueue(&mut self, ip: String, request: MatchmakingRequest) -> MatchmakingStatus {
+        let now = now();
+        self.remove_stale(now);
+        let ticket_id = self.queue.enqueue(ip, request, now);
+        MatchmakingStatus::Queued { ticket_id }
+    }
+
+    /// The outcome of a ticket is handed out once, None if the ticket is unknown.
+    pub fn status(&mut self, ticket_id: &str) -> Option<MatchmakingStatus> {
+        let now = now();
+        self.remove_stale(now);
+        if let Some((status, _)) = self.outcomes.remove(ticket_id) {
+            return Some(status);
+        }
+        if self.matching.contains(ticket_id) || self.queue.touch(ticket_id, now) {
+            return Some(MatchmakingStatus::Queued { ticket_id: ticket_id.to_owned() });
+        }
+        None
+    }
+
+    pub fn leave(&mut self, ticket_id: &str) -> bool {
+        self.queue.remove(ticket_id)
+    }
+
+    // the pair stays matching until it is resolved.
+    fn take_match(&mut self, ticket_id: &str) -> Option<(MatchmakingTicket, MatchmakingTicket)> {
+        let (host, peer) = self.queue.take_match(ticket_id, now())?;
+        self.matching.insert(host.ticket_id.clone());
+        self.matching.insert(peer.ticket_id.clone());
+        Some((host, peer))
+    }
+
+    fn resolve(&mut self, outcomes: Vec<MatchmakingStatus>) {
+        let now = now();
+        for outcome in outcomes {
+            self.matching.remove(outcome.ticket_id());
+            self.outcomes.insert(outcome.ticket_id().to_owned(), (outcome, now));
+        }
+        self.notify.notify_waiters();
+    }
+
+    fn remove_stale(&mut self, now: u128) {
+        for ticket_id in self.queue.remove_stale(now) {
+            info!("dropped stale matchmaking ticket {}", ticket_id);
+        }
+        let ttl = self.ticket_ttl.as_millis();
+        self.outcomes.retain(|_, (_, resolved_at)| now.saturating_sub(*resolved_at) <= ttl);
+    }
+}
+
+/// Pairs the ticket if there is an opponent and starts their session.
+/// Both players learn about the match by polling their ticket.
+pub async fn try_match(matchmaker: &Arc<Mutex<Matchmaker>>, session_manager: &Arc<Mutex<SessionManager>>, token_signer: &TokenSigner, ticket_id: &str) {
+    let (host, peer) = match matchmaker.lock().await.take_match(ticket_id) {
+        Some(pair) => pair,
+        None => return,
+    };
+    info!("matched ticket {} (host) with ticket {} (peer)", host.ticket_id, peer.ticket_id);
+    let outcomes = match start_session(session_manager, token_signer, &host, &peer).await {
+        Ok(outcomes) => outcomes,
+        Err(e) => {
+            error!("failed to start session of matched tickets {} and {}: {}", host.ticket_id, peer.ticket_id, e);
+            [host, peer].into_iter()
+                .map(|t| MatchmakingStatus::Failed { ticket_id: t.ticket_id, reason: e.clone() })
+                .collect()
+        }
+    };
+    matchmaker.lock().await.resolve(outcomes);
+}
+
+async fn start_session(
+    session_manager: &Arc<Mutex<SessionManager>>,
+    token_signer: &TokenSigner,
+    host: &MatchmakingTicket,
+    peer: &MatchmakingTicket,
+) -> Result<Vec<MatchmakingStatus>, String> {
+    let host_player = Player::new(1, host.ip.clone());
+    let peer_player = Player::new(2, peer.ip.clone());
+    let joined = {
+        let mut locked = session_manager.lock().await;
+        let created = locked.create_session(
+            host_player.clone(),
+            host.request.mode.clone(),
+            host.request.format.clone(),
+            SessionVisibility::PRIVATE,
+        ).await?;
+        locked.join_session(created.session_id().to_owned(), peer_player.clone()).await?
+    };
+    let session = match joined {
+        SessionEvent::Joined(payload) => payload.session,
+        other => return Err(format!("unexpected session event after join: {:?}", other)),
+    };
+    if let Err(e) = SessionPresenceLoop::start(session_manager, &session).await {
+        error!("failed to start presence tracking: {}", e);
+    }
+    let (host_actor, peer_actor) = (Actor::Player(host_player), Actor::Player(peer_player));
+    let host_token = token_signer.issue(&session.session_id, &host_actor);
+    let peer_token = token_signer.issue(&session.session_id, &peer_actor);
+    Ok(vec![
+        MatchmakingStatus::Matched { ticket_id: host.ticket_id.clone(), role: MatchRole::HOST, session: session.clone(), actor: host_actor, token: host_token },
+        MatchmakingStatus::Matched { ticket_id: peer.ticket_id.clone(), role: MatchRole::PEER, session, actor: peer_actor, token: peer_token },
+    ])
+}
+
+/// Long-poll of a ticket, returns once it is matched or the timeout passed.
+pub async fn wait_for_match(matchmaker: &Arc<Mutex<Matchmaker>>, ticket_id: &str, timeout: Duration) -> Option<MatchmakingStatus> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        let notify = Arc::clone(&matchmaker.lock().await.notify);
+        // created before the check, so that a match in between still wakes it.
+        let notified = notify.notified();
+        let status = matchmaker.lock().await.status(ticket_id);
+        match status {
+            Some(MatchmakingStatus::Queued { .. }) if tokio::time::Instant::now() < deadline => {
+                let _ = tokio::time::timeout_at(deadline, notified).await;
+            }
+            status => return status,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::matchmaking::{Matchmaker, MatchmakingConfig, MatchmakingQueue, MatchmakingRequest, MatchmakingStatus};
+    use crate::session::SessionMode;
+    use pong::pong::pong_rules::MatchFormat;
+
+    fn request(mode: SessionMode, rating: Option<u32>) -> MatchmakingRequest {
+        MatchmakingRequest { mode, format: MatchFormat::default(), rating }
+    }
+
+    #[test]
+    fn should_pair_with_longest_waiting_player_as_host() {
+        let mut queue = MatchmakingQueue::new(MatchmakingConfig::default());
+        let first = queue.enqueue("1".to_owned(), request(SessionMode::HOST, None), 0);
+        assert!(queue.take_match(&first, 0).is_none());
+        let second = queue.enqueue("2".to_owned(), request(SessionMode::HOST, None), 10);
+        let third = queue.enqueue("3".to_owned(), request(SessionMode::HOST, None), 20);
+        let (host, peer) = queue.take_match(&third, 20).unwrap();
+        assert_eq!((host.ticket_id, peer.ticket_id), (first, third));
+        assert!(queue.take_match(&second, 20).is_none());
+    }
+
+    #[test]
+    fn should_only_pair_same_mode() {
+        let mut queue = MatchmakingQueue::new(MatchmakingConfig::default());
+        queue.enqueue("1".to_owned(), request(SessionMode::HOST, None), 0);
+        let server = queue.enqueue("2".to_owned(), request(SessionMode::SERVER, None), 0);
+        assert!(queue.take_match(&server, 0).is_none());
+    }
+
+    #[rstest]
+    #[case(1_000, 1_080, 0, true)]
+    #[case(1_000, 1_150, 0, false)]
+    // the window grows by 10 per second of waiting.
+    #[case(1_000, 1_150, 5_000, true)]
+    fn should_pair_similar_ratings(#[case] a: u32, #[case] b: u32, #[case] now: u128, #[case] expected: bool) {
+        let mut queue = MatchmakingQueue::new(MatchmakingConfig::default());
+        queue.enqueue("1".to_owned(), request(SessionMode::HOST, Some(a)), 0);
+        let ticket = queue.enqueue("2".to_owned(), request(SessionMode::HOST, Some(b)), now);
+        assert_eq!(queue.take_match(&ticket, now).is_some(), expected);
+    }
+
+    #[test]
+    fn should_prefer_closest_rating() {
+        let mut queue = MatchmakingQueue::new(MatchmakingConfig::default());
+        queue.enqueue("1".to_owned(), request(SessionMode::HOST, Some(1_090)), 0);
+        let close = queue.enqueue("2".to_owned(), request(SessionMode::HOST, Some(1_010)), 0);
+        let ticket = queue.enqueue("3".to_owned(), request(SessionMode::HOST, Some(1_000)), 0);
+        let (host, _) = queue.take_match(&ticket, 0).unwrap();
+        assert_eq!(host.ticket_id, close);
+    }
+
+    #[test]
+    fn should_report_matched_tickets_as_queued_until_resolved() {
+        let mut matchmaker = Matchmaker::new(MatchmakingConfig::default());
+        let first = matchmaker.enqueue("1".to_owned(), request(SessionMode::HOST, None)).ticket_id().to_owned();
+        let second = matchmaker.enqueue("2".to_owned(), request(SessionMode::HOST, None)).ticket_id().to_owned();
+        assert!(matchmaker.take_match(&second).is_some());
+        // the session of the pair is still being started.
+        assert!(matches!(matchmaker.status(&first), Some(MatchmakingStatus::Queued { .. })));
+        assert!(matches!(matchmaker.status(&second), Some(MatchmakingStatus::Queued { .. })));
+        matchmaker.resolve(vec![
+            MatchmakingStatus::Failed { ticket_id: first.clone(), reason: "test".to_owned() },
+            MatchmakingStatus::Failed { ticket_id: second.clone(), reason: "test".to_owned() },
+        ]);
+        assert!(matches!(matchmaker.status(&first), Some(MatchmakingStatus::Failed { .. })));
+        assert!(matchmaker.status(&first).is_none());
+    }
+
+    #[test]
+    fn should_drop_tickets_that_are_not_polled() {
+        let mut queue = MatchmakingQueue::new(MatchmakingConfig::default());
+        let polled = queue.enqueue("1".to_owned(), request(SessionMode::HOST, None), 0);
+        let stale = queue.enqueue("2".to_owned(), request(SessionMode::HOST, None), 0);
+        assert!(queue.touch(&polled, 20_000));
+        assert_eq!(queue.remove_stale(31_000), vec![stale]);
+        assert!(queue.touch(&polled, 31_000));
+    }
+}
@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{debug, error, info};
+use tokio::sync::Mutex;
+
+use crate::actor::Actor;
+use crate::event::{HeartBeatEventPayload, Presence, PresenceEventPayload, SessionEvent};
+use crate::session::Session;
+use crate::session_manager::{SessionManager, SessionReader, SessionWriter};
+use crate::utils::env_utils::secs_from_env;
+use crate::utils::time_utils::now;
+
+const PRESENCE_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+// Upper bound of reads per check, like in the game loop.
+const MAX_READS_PER_CHECK: usize = 10;
+
+/// When actors without heartbeats count as lagging or gone, clients send one every second.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PresenceConfig {
+    pub lagging_after: Duration,
+    pub gone_after: Duration,
+    // how long a player may be gone before the session is closed, players can reconnect meanwhile.
+    pub close_grace: Duration,
+}
+
+impl Default for PresenceConfig {
+    fn default() -> Self {
+        PresenceConfig {
+            lagging_after: Duration::from_secs(3),
+            gone_after: Duration::from_secs(10),
+            close_grace: Duration::from_secs(30),
+        }
+    }
+}
+
+impl PresenceConfig {
+    /// Reads the durations in seconds from PRESENCE_LAGGING_AFTER_SECS, PRESENCE_GONE_AFTER_SECS and PRESENCE_CLOSE_GRACE_SECS.
+    pub fn from_env() -> Result<PresenceConfig, String> {
+        let default = PresenceConfig::default();
+        Ok(PresenceConfig {
+            lagging_after: secs_from_env("PRESENCE_LAGGING_AFTER_SECS", default.lagging_after)?,
+            gone_after: secs_from_env("PRESENCE_GONE_AFTER_SECS", default.gone_after)?,
+            close_grace: secs_from_env("PRESENCE_CLOSE_GRACE_SECS", default.close_grace)?,
+        })
+    }
+}
+
+struct ActorPresence {
+    last_seen: u128,
+    presence: Presence,
+    // set once the connection of the actor dropped, until it sends a heartbeat again.
+    disconnected_at: Option<u128>,
+}
+
+impl ActorPresence {
+    fn gone_since(&self, gone_after: Duration) -> u128 {
+        self.disconnected_at.unwrap_or(self.last_seen + gone_after.as_millis())
+    }
+}
+
+/// Presence of the actors of a session, derived from the time of their last heartbeat.
+pub struct PresenceTracker {
+    config: PresenceConfig,
+    players: Vec<String>,
+    actors: HashMap<String, ActorPresence>,
+}
+
+impl PresenceTracker {
+    /// The players count as connected from now on, observers once they send a heartbeat.
+    pub fn new(config: PresenceConfig, session: &Session, now: u128) -> PresenceTracker {
+        let players = session.players.iter().map(|p| p.id.clone()).collect::<Vec<String>>();
+        let actors = players.iter()
+            .map(|id| (id.clone(), ActorPresence { last_seen: now, presence: Presence::Connected, disconnected_at: None }))
+            .collect();
+        PresenceTracker { config, players, actors }
+    }
+
+    /// Returns the new presence if the actor was not connected before.
+    pub fn heartbeat(&mut self, actor_id: &str, now: u128) -> Option<Presence> {
+        let actor = self.actors.entry(actor_id.to_owned())
+            .or_insert(ActorPresence { last_seen: now, presence: Presence::Gone, disconnected_at: None });
+        actor.last_seen = actor.last_seen.max(now);
+        actor.disconnected_at = None;
+        if actor.presence == Presence::Connected {
+            return None;
+        }
+        actor.presence = Presence::Connected;
+        Some(Presence::Connected)
+    }
+
+    /// The connection of the actor dropped, it is gone without waiting for its heartbeats to be overdue.
+    /// Returns the new presence if the actor was not gone before.
+    pub fn disconnect(&mut self, actor_id: &str, now: u128) -> Option<Presence> {
+        let actor = self.actors.get_mut(actor_id)?;
+        actor.disconnected_at.get_or_insert(now);
+        if actor.presence == Presence::Gone {
+            return None;
+        }
+        actor.presence = Presence::Gone;
+        Some(Presence::Gone)
+    }
+
+    /// Actors whose presence changed because their heartbeats are overdue.
+    pub fn update(&mut self, now: u128) -> Vec<(String, Presence)> {
+        let mut changes = vec![];
+        for (actor_id, actor) in self.actors.iter_mut().filter(|(_, a)| a.disconnected_at.is_none()) {
+            let silent_for = now.saturating_sub(actor.last_seen);
+            let presence = if silent_for > self.config.gone_after.as_millis() {
+                Presence::Gone
+            } else if silent_for > self.config.lagging_after.as_millis() {
+                Presence::Lagging
+            } else {
+                Presence::Connected
+            };
+            if presence != actor.presence {
+                actor.presence = presence;
+                changes.push((actor_id.clone(), presence));
+            }
+        }
+        changes.sort_by(|a, b| a.0.cmp(&b.0));
+        changes
+    }
+
+    /// Set once a player is gone for longer than the grace period.
+    pub fn close_reason(&self, now: u128) -> Option<String> {
+        let close_grace = self.config.close_grace.as_millis();
+        self.players.iter()
+            .find(|id| self.actors.get(*id).is_some_and(|a| now.saturating_sub(a.gone_since(self.config.gone_after)) > close_grace))
+            .map(|id| format!("player {} is gone for more than {}s", id, self.config.close_grace.as_secs()))
+    }
+}
+
+/// Consumes the heartbeats of a session, publishes presence changes and closes the session when a player is gone.
+pub struct SessionPresenceLoop {
+    session: Session,
+    tracker: PresenceTracker,
+    reader: SessionReader,
+    writer: SessionWriter,
+    session_manager: Arc<Mutex<SessionManager>>,
+}
+
+impl SessionPresenceLoop {
+    pub async fn start(session_manager: &Arc<Mutex<SessionManager>>, session: &Session) -> Result<(), String> {
+        let (reader, writer, config) = {
+            let locked = session_manager.lock().await;
+            let (reader, writer) = locked.split(&session.session_id, &["heart_beat", "session"]).await?;
+            (reader, writer, locked.presence_config())
+        };
+        let presence_loop = SessionPresenceLoop {
+            session: session.clone(),
+            tracker: PresenceTracker::new(config, session, now()),
+            reader,
+            writer,
+            session_manager: Arc::clone(session_manager),
+        };
+        tokio::spawn(presence_loop.run());
+        info!("started presence tracking for session {}", session.session_id);
+        Ok(())
+    }
+
+    async fn run(mut self) {
+        let mut interval = tokio::time::interval(PRESENCE_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            let is_open = self.consume_events().await;
+            if !is_open {
+                info!("[{}] session was closed, stopping presence tracking", self.session.session_id);
+                break;
+            }
+            let now = now();
+            for (actor_id, presence) in self.tracker.update(now) {
+                self.publish(&actor_id, presence, now).await;
+            }
+            if let Some(reason) = self.tracker.close_reason(now) {
+                info!("[{}] closing session: {}", self.session.session_id, reason);
+                let res = self.session_manager.lock().await.close_session(&self.session.session_id, Actor::Server, &reason).await;
+                if let Err(e) = res {
+                    error!("[{}] failed to close session of gone player: {}", self.session.session_id, e);
+                }
+                break;
+            }
+        }
+    }
+
+    async fn consume_events(&mut self) -> bool {
+        for _ in 0..MAX_READS_PER_CHECK {
+            let events = self.reader.read_from_session().await;
+            if let Err(e) = events {
+                error!("[{}] failed to read events for presence tracking: {}", self.session.session_id, e);
+                return true;
+            }
+            let events = events.unwrap();
+            if events.is_empty() {
+                return true;
+            }
+            for event in events {
+                match event.topic.as_str() {
+                    "heart_beat" => {
+                        let heartbeat = serde_json::from_str::<HeartBeatEventPayload>(&event.event);
+                        if let Err(e) = heartbeat {
+                            error!("[{}] failed to deserialize heartbeat {}: {:?}", self.session.session_id, event.event, e);
+                            continue;
+                        }
+                        let heartbeat = heartbeat.unwrap();
+                        // heartbeats are timed on arrival, client clocks can't be trusted.
+                        let now = now();
+                        let presence = if heartbeat.disconnected {
+                            self.tracker.disconnect(&heartbeat.actor_id, now)
+                        } else {
+                            self.tracker.heartbeat(&heartbeat.actor_id, now)
+                        };
+                        if let Some(presence) = presence {
+                            self.publish(&heartbeat.actor_id, presence, now).await;
+                        }
+                    }
+                    "session" => {
+                        let session_event = serde_json::from_str::<SessionEvent>(&event.event);
+                        if let Ok(SessionEvent::Closed(_)) = session_event {
+                            return false;
+                        }
+                    }
+                    _ => {
+                        debug!("[{}] presence tracking ignores event of topic {}", self.session.session_id, event.topic);
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    async fn publish(&mut self, actor_id: &str, presence: Presence, now: u128) {
+        debug!("[{}] actor {} is {:?}", self.session.session_id, actor_id, presence);
+        let event = PresenceEventPayload {
+            session_id: self.session.session_id.clone(),
+            actor_id: actor_id.to_owned(),
+            presence,
+            ts: now,
+        };
+        let json = serde_json::to_string(&event).unwrap();
+        let write_res = self.writer.write_to_session("presence", vec![&json]).await;
+        if let Err(e) = write_res {
+            error!("[{}] failed to write presence event: {}", self.session.session_id, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::actor::{Player};
+    use crate::event::Presence;
+    use crate::presence::{PresenceConfig, PresenceTracker};
+    use crate::session::{Session, SessionMode};
+    use pong::pong::pong_rules::MatchFormat;
+
+    fn tracker() -> PresenceTracker {
+        let host = Player { id: "host".to_owned(), ip: "127.0.0.1".to_owned(), nr: 1 };
+        let peer = Player { id: "peer".to_owned(), ip: "127.0.0.1".to_owned(), nr: 2 };
+        let mut session = Session::new(1, "abc".to_owned(), host, SessionMode::HOST, MatchFormat::default());
+        session.join(peer);
+        PresenceTracker::new(PresenceConfig::default(), &session, 0)
+    }
+
+    #[rstest]
+    #[case(2_000, vec![])]
+    #[case(5_000, vec![("peer".to_owned(), Presence::Lagging)])]
+    #[case(11_000, vec![("peer".to_owned(), Presence::Gone)])]
+    fn should_derive_presence_from_last_heartbeat(#[case] now: u128, #[case] expected: Vec<(String, Presence)>) {
+        let mut tracker = tracker();
+        tracker.heartbeat("host", now);
+        assert_eq!(tracker.update(now), expected);
+    }
+
+    #[test]
+    fn should_report_reconnect_once() {
+        let mut tracker = tracker();
+        assert_eq!(tracker.heartbeat("peer", 1_000), None);
+        assert_eq!(tracker.update(5_000), vec![("host".to_owned(), Presence::Lagging), ("peer".to_owned(), Presence::Lagging)]);
+        assert_eq!(tracker.heartbeat("peer", 5_500), Some(Presence::Connected));
+        assert_eq!(tracker.heartbeat("peer", 6_000), None);
+        assert_eq!(tracker.update(6_000), vec![]);
+    }
+
+    #[test]
+    fn should_track_observers_once_they_send_heartbeats() {
+        let mut tracker = tracker();
+        assert_eq!(tracker.heartbeat("observer", 1_000), Some(Presence::Connected));
+        tracker.heartbeat("host", 60_000);
+        tracker.heartbeat("peer", 60_000);
+        assert_eq!(tracker.update(60_000), vec![("observer".to_owned(), Presence::Gone)]);
+        assert_eq!(tracker.close_reason(60_000), None);
+    }
+
+    #[test]
+    fn should_close_after_grace_period() {
+        let mut tracker = tracker();
+        tracker.heartbeat("host", 39_000);
+        assert_eq!(tracker.close_reason(39_000), None);
+        assert_eq!(tracker.close_reason(40_001), Some("player peer is gone for more than 30s".to_owned()));
+    }
+
+    #[test]
+    fn should_start_grace_period_on_disconnect() {
+        let mut tracker = tracker();
+        tracker.heartbeat("host", 2_000);
+        assert_eq!(tracker.disconnect("peer", 2_000), Some(Presence::Gone));
+        assert_eq!(tracker.disconnect("peer", 2_500), None);
+        tracker.heartbeat("host", 32_000);
+        assert_eq!(tracker.update(32_000), vec![]);
+        assert_eq!(tracker.close_reason(32_000), None);
+        assert_eq!(tracker.close_reason(32_501), Some("player peer is gone for more than 30s".to_owned()));
+    }
+
+    #[test]
+    fn should_reconnect_within_grace_period() {
+        let mut tracker = tracker();
+        tracker.disconnect("peer", 2_000);
+        assert_eq!(tracker.heartbeat("peer", 20_000), Some(Presence::Connected));
+        tracker.heartbeat("host", 40_000);
+        assert_eq!(tracker.close_reason(40_000), None);
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use log::error;
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+use crate::actor::{Actor, Player};
+use crate::presence::SessionPresenceLoop;
+use crate::session::{Session, SessionState};
+use crate::session_manager::SessionManager;
+use crate::token::TokenSigner;
+
+/// Progress of a rematch after a player accepted it.
+#[derive(Debug, PartialEq)]
+pub enum RematchProgress {
+    // the other player did not accept yet, carries the previous session.
+    Pending(Session),
+    // both players accepted, carries the rematch that was just created.
+    Created(Session),
+    // the rematch was created before, e.g. when the player asks again.
+    Existing(Session),
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "status")]
+pub enum RematchStatus {
+    Pending { session_id: String, accepted_by: Vec<String> },
+    // the place of the actor in the rematch, the token authorises its websocket connection.
+    Created { session: Session, actor: Actor, token: String },
+}
+
+/// A rematch can only be asked for once the game is over, i.e. the session has a winner.
+pub fn check_rematch(session: &Session, winner: Option<&str>) -> Result<(), String> {
+    if session.state == SessionState::PENDING {
+        return Err(format!("Can't rematch session that is still PENDING: {}", session.session_id));
+    }
+    if winner.is_none() {
+        return Err(format!("Can't rematch session before it has a result: {}", session.session_id));
+    }
+    Ok(())
+}
+
+/// The next session of a series: the same players with host and peer swapped, observers are carried over.
+pub fn rematch_session(previous: &Session, id: u16, session_id: String) -> Session {
+    let mut players: Vec<Player> = previous.players.iter()
+        .map(|p| Player { nr: if p.nr == 1 { 2 } else { 1 }, ..p.clone() })
+        .collect();
+    players.sort_by_key(|p| p.nr);
+    Session {
+        id,
+        session_id,
+        start_offsets: HashMap::new(),
+        created_at: 0,
+        join_code: None,
+        state: SessionState::RUNNING,
+        mode: previous.mode.clone(),
+        format: previous.format.clone(),
+        visibility: previous.visibility.clone(),
+        players,
+        observers: previous.observers.clone(),
+        rematch_accepted_by: vec![],
+        rematch_of: Some(previous.session_id.clone()),
+        rematch: None,
+    }
+}
+
+/// The status of an actor of the previous session in the rematch, `None` if it wasn't carried over.
+pub fn created_status(rematch: &Session, actor_id: &str, token_signer: &TokenSigner) -> Option<RematchStatus> {
+    let actor = rematch.players.iter().find(|p| p.id == actor_id).map(|p| Actor::Player(p.clone()))
+        .or_else(|| rematch.observers.iter().find(|o| o.id == actor_id).map(|o| Actor::Observer(o.clone())))?;
+    Some(RematchStatus::Created {
+        token: token_signer.issue(&rematch.session_id, &actor),
+        session: rematch.clone(),
+        actor,
+    })
+}
+
+/// Accepts a rematch on behalf of a player, shared by the rematch endpoint and the websocket message.
+pub async fn accept_rematch(
+    session_manager: &Arc<Mutex<SessionManager>>,
+    token_signer: &TokenSigner,
+    session_id: &str,
+    player_id: &str,
+) -> Result<RematchStatus, String> {
+    let progress = session_manager.lock().await.accept_rematch(session_id, player_id).await?;
+    let rematch = match progress {
+        RematchProgress::Pending(previous) => {
+            return Ok(RematchStatus::Pending { session_id: previous.session_id, accepted_by: previous.rematch_accepted_by });
+        }
+        RematchProgress::Created(rematch) => {
+            if let Err(e) = SessionPresenceLoop::start(session_manager, &rematch).await {
+                error!("failed to start presence tracking of rematch {}: {}", rematch.session_id, e);
+            }
+            rematch
+        }
+        RematchProgress::Existing(rematch) => rematch,
+    };
+    created_status(&rematch, player_id, token_signer)
+        .ok_or(format!("Player {} is not part of rematch {}", player_id, rematch.session_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use pong::pong::pong_rules::MatchFormat;
+    use rstest::rstest;
+    use crate::actor::{Actor, Observer, Player};
+    use crate::rematch::{check_rematch, created_status, rematch_session, RematchStatus};
+    use crate::session::{Session, SessionMode, SessionState};
+    use crate::token::TokenSigner;
+
+    #[test]
+    fn should_swap_host_and_peer_and_keep_observers() {
+        let host = Player { id: "player_1".to_owned(), nr: 1, ip: "127.0.0.1".to_owned() };
+        let peer = Player { id: "player_2".to_owned(), nr: 2, ip: "127.0.0.2".to_owned() };
+        let observer = Observer { id: "observer_1".to_owned(), ip: "127.0.0.3".to_owned() };
+        let mut previous = Session::new(1, "a".to_owned(), host, SessionMode::SERVER, MatchFormat::default());
+        previous.players.push(peer);
+        previous.observers.push(observer.clone());
+        previous.state = SessionState::RUNNING;
+        previous.rematch_accepted_by = vec!["player_1".to_owned(), "player_2".to_owned()];
+
+        let rematch = rematch_session(&previous, 2, "b".to_owned());
+
+        assert_eq!(rematch.session_id, "b");
+        assert_eq!(rematch.state, SessionState::RUNNING);
+        assert_eq!(rematch.mode, SessionMode::SERVER);
+        assert_eq!(rematch.players, vec![
+            Player { id: "player_2".to_owned(), nr: 1, ip: "127.0.0.2".to_owned() },
+            Player { id: "player_1".to_owned(), nr: 2, ip: "127.0.0.1".to_owned() },
+        ]);
+        assert_eq!(rematch.observers, vec![observer]);
+        assert_eq!(rematch.rematch_of, Some("a".to_owned()));
+        assert!(rematch.rematch_accepted_by.is_empty());
+    }
+
+    #[rstest]
+    #[case::finished(SessionState::RUNNING, Some("player_1"), true)]
+    #[case::closed_after_result(SessionState::CLOSED, Some("player_2"), true)]
+    #[case::unfinished(SessionState::RUNNING, None, false)]
+    #[case::abandoned(SessionState::CLOSED, None, false)]
+    #[case::pending(SessionState::PENDING, None, false)]
+    fn should_only_rematch_finished_games(#[case] state: SessionState, #[case] winner: Option<&str>, #[case] expected: bool) {
+        let host = Player { id: "player_1".to_owned(), nr: 1, ip: "127.0.0.1".to_owned() };
+        let mut session = Session::new(1, "a".to_owned(), host, SessionMode::SERVER, MatchFormat::default());
+        session.state = state;
+        assert_eq!(check_rematch(&session, winner).is_ok(), expected);
+    }
+
+    #[test]
+    fn should_only_create_status_for_actors_of_the_rematch() {
+        let signer = TokenSigner::new(b"0123456789abcdef0123456789abcdef");
+        let host = Player { id: "player_1".to_owned(), nr: 1, ip: "127.0.0.1".to_owned() };
+        let observer = Observer { id: "observer_1".to_owned(), ip: "127.0.0.1".to_owned() };
+        let mut rematch = Session::new(2, "b".to_owned(), host.clone(), SessionMode::HOST, MatchFormat::default());
+        rematch.observers.push(observer.clone());
+
+        let status = created_status(&rematch, "observer_1", &signer);
+        match status {
+            Some(RematchStatus::Created { actor, token, .. }) => {
+                assert_eq!(actor, Actor::Observer(observer));
+                assert_eq!(signer.verify(&token).map(|t| t.session_id), Ok("b".to_owned()));
+            }
+            other => panic!("expected created status, got {:?}", other),
+        }
+        assert!(created_status(&rematch, "player_1", &signer).is_some());
+        assert!(created_status(&rematch, "observer_2", &signer).is_none());
+    }
+}
@@ -1,16 +1,28 @@
 use std::convert::Infallible;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use hyper::{Body, Method, Request, Response, StatusCode};
 use async_trait::async_trait;
 use log::{debug, error, info};
-use serde_json::json;
+use serde_json::{json, Value};
 use tokio::sync::Mutex;
 use serde::{Deserialize};
 use crate::event::{SessionEvent, SessionEventPayload, SessionEventType};
 use crate::actor::{Actor, Observer, Player};
+use crate::presence::SessionPresenceLoop;
+use crate::rematch::accept_rematch;
+use crate::lobby::SessionQuery;
+use crate::matchmaking::{try_match, wait_for_match, Matchmaker, MatchmakingRequest, MatchmakingStatus};
+use crate::session::{SessionMode, SessionVisibility};
+use pong::pong::pong_rules::MatchFormat;
 use crate::session_manager::SessionManager;
-use crate::utils::http_utils::{build_error_res, build_success_res, get_query_params, read_json_body};
+use crate::token::TokenSigner;
+use crate::websocket_handler::WebSocketConnectionType;
+use crate::utils::http_utils::{build_error_res, build_success_res, get_query_params, read_json_body, read_json_body_raw};
+
+// how long a matchmaking poll waits for a match before it reports the ticket as still queued.
+const MATCHMAKING_POLL_TIMEOUT: Duration = Duration::from_secs(20);
 
 #[async_trait]
 pub trait RequestHandler {
@@ -18,15 +30,21 @@ pub trait RequestHandler {
 }
 
 pub struct DefaultRequestHandler {
-    session_manager: Arc<Mutex<SessionManager>>
+    session_manager: Arc<Mutex<SessionManager>>,
+    matchmaker: Arc<Mutex<Matchmaker>>,
+    token_signer: Arc<TokenSigner>
 }
 
 impl DefaultRequestHandler {
     pub fn new(
-        session_manager: Arc<Mutex<SessionManager>>
+        session_manager: Arc<Mutex<SessionManager>>,
+        matchmaker: Arc<Mutex<Matchmaker>>,
+        token_signer: Arc<TokenSigner>
     ) -> DefaultRequestHandler {
         DefaultRequestHandler {
-            session_manager
+            session_manager,
+            matchmaker,
+            token_signer
         }
     }
 }
@@ -37,11 +55,16 @@ impl RequestHandler for DefaultRequestHandler {
         info!("called route {} {}", req.method(), req.uri());
         match (req.method(), req.uri().path()) {
             (&Method::GET, "/session") => handle_get_session(&self.session_manager, req).await,
+            (&Method::GET, "/sessions") => handle_get_sessions(&self.session_manager, req).await,
             (&Method::POST, "/create_session") => {
-                handle_session_create(&self.session_manager, req, addr).await
+                handle_session_create(&self.session_manager, &self.token_signer, req, addr).await
             }
-            (&Method::POST, "/join_session") => handle_session_join(&self.session_manager, req, addr).await,
-            (&Method::POST, "/watch_session") => handle_session_watch(&self.session_manager, req, addr).await,
+            (&Method::POST, "/join_session") => handle_session_join(&self.session_manager, &self.token_signer, req, addr).await,
+            (&Method::POST, "/watch_session") => handle_session_watch(&self.session_manager, &self.token_signer, req, addr).await,
+            (&Method::POST, "/matchmake") => handle_matchmake(&self.session_manager, &self.matchmaker, &self.token_signer, req, addr).await,
+            (&Method::GET, "/matchmake") => handle_matchmake_poll(&self.session_manager, &self.matchmaker, &self.token_signer, req).await,
+            (&Method::DELETE, "/matchmake") => handle_matchmake_leave(&self.matchmaker, req).await,
+            (&Method::POST, "/rematch") => handle_rematch(&self.session_manager, &self.token_signer, req).await,
             _ => Ok(Response::new("unknown".into())),
         }
     }
@@ -70,16 +93,44 @@ async fn handle_get_session(
     return build_success_res(&serde_json::to_string(&session.unwrap()).unwrap());
 }
 
-async fn handle_session_create(
+async fn handle_get_sessions(
     session_manager: &Arc<Mutex<SessionManager>>,
     req: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    info!("called get_sessions");
+    let query = SessionQuery::from_params(&get_query_params(&req));
+    if let Err(e) = query {
+        error!("invalid sessions query: {}", e);
+        return build_error_res(&e, StatusCode::BAD_REQUEST);
+    }
+    let query = query.unwrap();
+    let page = session_manager.lock().await.find_sessions(&query);
+    debug!("found {} sessions for query {:?}", page.total, query);
+    return build_success_res(&serde_json::to_string(&page).unwrap());
+}
+
+async fn handle_session_create(
+    session_manager: &Arc<Mutex<SessionManager>>,
+    token_signer: &TokenSigner,
+    mut req: Request<Body>,
     addr: SocketAddr,
 ) -> Result<Response<Body>, Infallible> {
     info!("called create_session");
     debug!("req: {:?}", req);
+    // the body is optional, without it a host session is created.
+    let body = read_json_body_raw(&mut req).await;
+    let body = match body.trim() {
+        "" => Ok(SessionCreateDto::default()),
+        raw => serde_json::from_str::<SessionCreateDto>(raw)
+    };
+    if let Err(e) = body {
+        error!("invalid create session body: {:?}", e);
+        return build_error_res("Invalid create session request", StatusCode::BAD_REQUEST);
+    }
+    let body = body.unwrap();
     let mut locked = session_manager.lock().await;
     let player = Player::new(1, addr.ip().to_string());
-    let session_create_res = locked.create_session(player.clone()).await;
+    let session_create_res = locked.create_session(player.clone(), body.mode, body.format, body.visibility).await;
     if let Err(e) = session_create_res {
         error!("failed to create session: {:?}", e);
         return Ok(Response::builder()
@@ -89,12 +140,13 @@ async fn handle_session_create(
     }
     let session_event = session_create_res.unwrap();
     info!("session created: {:?}", session_event);
-    let serialized = json!(session_event);
+    let serialized = with_token(&session_event, token_signer);
     return build_success_res(&serialized.to_string());
 }
 
 async fn handle_session_join(
     session_manager: &Arc<Mutex<SessionManager>>,
+    token_signer: &TokenSigner,
     mut req: Request<Body>,
     addr: SocketAddr,
 ) -> Result<Response<Body>, Infallible> {
@@ -102,8 +154,16 @@ async fn handle_session_join(
     debug!("req: {:?}", req);
     let mut locked = session_manager.lock().await;
     let body = read_json_body::<SessionJoinDto>(&mut req).await;
+    let session_id = match &body.join_code {
+        Some(join_code) => locked.resolve_join_code(join_code),
+        None => Some(body.session_id)
+    };
+    if session_id.is_none() {
+        error!("join code {:?} is unknown or expired", body.join_code);
+        return build_error_res("Unknown or expired join code", StatusCode::NOT_FOUND);
+    }
     let player = Player::new(2, addr.ip().to_string());
-    let session_join_res = locked.join_session(body.session_id, player.clone()).await;
+    let session_join_res = locked.join_session(session_id.unwrap(), player.clone()).await;
     if let Err(e) = session_join_res {
         error!("Failed to join session: {:?}", e);
         return Ok(Response::builder()
@@ -113,13 +173,20 @@ async fn handle_session_join(
     }
     let session_event = session_join_res.unwrap();
     info!("player {:?} successfully joined session: {:?}", player, session_event);
+    drop(locked);
+    if let SessionEvent::Joined(payload) = &session_event {
+        if let Err(e) = SessionPresenceLoop::start(session_manager, &payload.session).await {
+            error!("failed to start presence tracking: {}", e);
+        }
+    }
     let reason = format!("player {:?} joined session", player);
-    let serialized = json!(session_event);
+    let serialized = with_token(&session_event, token_signer);
     return build_success_res(&serialized.to_string());
 }
 
 async fn handle_session_watch(
     session_manager: &Arc<Mutex<SessionManager>>,
+    token_signer: &TokenSigner,
     mut req: Request<Body>,
     addr: SocketAddr,
 ) -> Result<Response<Body>, Infallible> {
@@ -139,11 +206,139 @@ async fn handle_session_watch(
     let session_event = sesssion_add_observer_res.unwrap();
     info!("observer {:?} successfully joined session: {:?}", observer, session_event);
     let reason = format!("observer {:?} joined session", observer);
-    let serialized = json!(session_event);
+    let serialized = with_token(&session_event, token_signer);
     return build_success_res(&serialized.to_string());
 }
 
+async fn handle_matchmake(
+    session_manager: &Arc<Mutex<SessionManager>>,
+    matchmaker: &Arc<Mutex<Matchmaker>>,
+    token_signer: &TokenSigner,
+    mut req: Request<Body>,
+    addr: SocketAddr,
+) -> Result<Response<Body>, Infallible> {
+    info!("called matchmake");
+    // the body is optional, without it a host session is looked for.
+    let body = read_json_body_raw(&mut req).await;
+    let body = match body.trim() {
+        "" => Ok(MatchmakingRequest::default()),
+        raw => serde_json::from_str::<MatchmakingRequest>(raw)
+    };
+    if let Err(e) = body {
+        error!("invalid matchmaking body: {:?}", e);
+        return build_error_res("Invalid matchmaking request", StatusCode::BAD_REQUEST);
+    }
+    let queued = matchmaker.lock().await.enqueue(addr.ip().to_string(), body.unwrap());
+    info!("queued for matchmaking: {:?}", queued);
+    try_match(matchmaker, session_manager, token_signer, queued.ticket_id()).await;
+    let status = matchmaker.lock().await.status(queued.ticket_id()).unwrap_or(queued);
+    return build_success_res(&serde_json::to_string(&status).unwrap());
+}
+
+async fn handle_matchmake_poll(
+    session_manager: &Arc<Mutex<SessionManager>>,
+    matchmaker: &Arc<Mutex<Matchmaker>>,
+    token_signer: &TokenSigner,
+    req: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let query_params = get_query_params(&req);
+    let ticket_id = query_params.get("ticket_id");
+    if ticket_id.is_none() {
+        error!("ticket id was not provided");
+        return build_error_res("Please provide a valid ticket id", StatusCode::BAD_REQUEST);
+    }
+    let ticket_id = ticket_id.unwrap();
+    // rating windows grow while players wait, so an opponent might fit now.
+    try_match(matchmaker, session_manager, token_signer, ticket_id).await;
+    let status = wait_for_match(matchmaker, ticket_id, MATCHMAKING_POLL_TIMEOUT).await;
+    if status.is_none() {
+        error!("matchmaking ticket {} does not exist", ticket_id);
+        return build_error_res("Unable to find matchmaking ticket for given id", StatusCode::NOT_FOUND);
+    }
+    let status = status.unwrap();
+    if let MatchmakingStatus::Matched { session, .. } = &status {
+        info!("ticket {} was matched into session {}", ticket_id, session.session_id);
+    }
+    return build_success_res(&serde_json::to_string(&status).unwrap());
+}
+
+async fn handle_matchmake_leave(
+    matchmaker: &Arc<Mutex<Matchmaker>>,
+    req: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let query_params = get_query_params(&req);
+    let ticket_id = query_params.get("ticket_id");
+    if ticket_id.is_none() {
+        error!("ticket id was not provided");
+        return build_error_res("Please provide a valid ticket id", StatusCode::BAD_REQUEST);
+    }
+    let ticket_id = ticket_id.unwrap();
+    if !matchmaker.lock().await.leave(ticket_id) {
+        return build_error_res("Unable to find queued matchmaking ticket for given id", StatusCode::NOT_FOUND);
+    }
+    info!("ticket {} left matchmaking", ticket_id);
+    return build_success_res(&json!({"ticket_id": ticket_id}).to_string());
+}
+
+async fn handle_rematch(
+    session_manager: &Arc<Mutex<SessionManager>>,
+    token_signer: &TokenSigner,
+    mut req: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    info!("called rematch");
+    let body = serde_json::from_str::<RematchDto>(&read_json_body_raw(&mut req).await);
+    if let Err(e) = body {
+        error!("invalid rematch body: {:?}", e);
+        return build_error_res("Invalid rematch request", StatusCode::BAD_REQUEST);
+    }
+    // the token of the previous session tells which player accepts.
+    let token = token_signer.verify(&body.unwrap().token);
+    if let Err(e) = token {
+        error!("invalid token for rematch: {}", e);
+        return build_error_res("Invalid session token", StatusCode::UNAUTHORIZED);
+    }
+    let token = token.unwrap();
+    if token.role == WebSocketConnectionType::OBSERVER {
+        return build_error_res("Only players can accept a rematch", StatusCode::FORBIDDEN);
+    }
+    let status = accept_rematch(session_manager, token_signer, &token.session_id, &token.actor_id).await;
+    if let Err(e) = status {
+        error!("failed to accept rematch: {}", e);
+        return build_error_res(&e, StatusCode::CONFLICT);
+    }
+    let status = status.unwrap();
+    info!("player {} accepted rematch of session {}: {:?}", token.actor_id, token.session_id, status);
+    return build_success_res(&serde_json::to_string(&status).unwrap());
+}
+
+// the token authorises the websocket connection of the actor the event is about.
+fn with_token(session_event: &SessionEvent, token_signer: &TokenSigner) -> Value {
+    let payload = session_event.payload();
+    let mut serialized = json!(session_event);
+    serialized["token"] = json!(token_signer.issue(payload.session_id(), &payload.actor));
+    serialized
+}
+
+#[derive(Deserialize)]
+struct RematchDto {
+    pub token: String
+}
+
 #[derive(Deserialize)]
 struct SessionJoinDto {
-    pub session_id: String
+    #[serde(default)]
+    pub session_id: String,
+    // private sessions are joined with the code their host shared.
+    #[serde(default)]
+    pub join_code: Option<String>
+}
+
+#[derive(Deserialize, Default)]
+struct SessionCreateDto {
+    #[serde(default)]
+    pub mode: SessionMode,
+    #[serde(default)]
+    pub format: MatchFormat,
+    #[serde(default)]
+    pub visibility: SessionVisibility
 }
@@ -1,30 +1,82 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
 use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use pong::pong::pong_rules::MatchFormat;
 use crate::actor::{Actor, Observer, Player};
 
+pub const MAX_OBSERVERS: usize = 6;
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Session {
-    pub id: u16, // internal id
-    pub session_id: String,
+    pub id: u16, // internal id, the kafka partition
+    pub session_id: String, // public id, random so that sessions can't be enumerated
+    // offsets per topic at which the events of the session start, only set if the partition was reused.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub start_offsets: HashMap<String, i64>,
+    // ms timestamp of the creation, 0 for sessions created before it was recorded.
+    // u64, session events are internally tagged and serde can't buffer u128 values for those.
+    #[serde(default)]
+    pub created_at: u64,
+    // short code to join the pending session, see `JoinCodes`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub join_code: Option<String>,
     pub state: SessionState,
+    #[serde(default)]
+    pub mode: SessionMode,
+    #[serde(default)]
+    pub format: MatchFormat,
+    #[serde(default)]
+    pub visibility: SessionVisibility,
     pub players: Vec<Player>,
-    pub observers: Vec<Observer>
+    pub observers: Vec<Observer>,
+    // players that accepted a rematch, once both did the rematch is created.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rematch_accepted_by: Vec<String>,
+    // links of a series: the session this one is a rematch of and the rematch that followed it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rematch_of: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rematch: Option<String>,
 }
 
 impl Session {
-    pub fn new(id: u16, hash: String, player: Player) -> Session {
+    pub fn new(id: u16, session_id: String, player: Player, mode: SessionMode, format: MatchFormat) -> Session {
         Session {
             players: vec![player],
             id,
-            session_id: hash,
+            session_id,
+            start_offsets: HashMap::new(),
+            created_at: 0,
+            join_code: None,
             state: SessionState::PENDING,
-            observers: vec![]
+            mode,
+            format,
+            visibility: SessionVisibility::default(),
+            observers: vec![],
+            rematch_accepted_by: vec![],
+            rematch_of: None,
+            rematch: None,
         }
     }
 
+    pub fn generate_id() -> String {
+        Uuid::new_v4().simple().to_string()
+    }
+
     pub fn can_be_joined(&self) -> bool {
         self.players.len() == 1
     }
 
+    pub fn has_free_slot(&self) -> bool {
+        self.state == SessionState::PENDING && self.can_be_joined()
+    }
+
+    pub fn is_observable(&self) -> bool {
+        self.state == SessionState::RUNNING && self.observers.len() < MAX_OBSERVERS
+    }
+
     pub fn join(&mut self, player: Player) -> bool {
         if !self.can_be_joined() {
             return false;
@@ -41,3 +93,29 @@ pub enum SessionState {
     CLOSED,  // store is over
 }
 
+impl FromStr for SessionState {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "pending" => Ok(SessionState::PENDING),
+            "running" => Ok(SessionState::RUNNING),
+            "closed" => Ok(SessionState::CLOSED),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub enum SessionMode {
+    #[default]
+    HOST,   // host browser runs the engine, server relays snapshots
+    SERVER, // server runs the engine, players only send inputs
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub enum SessionVisibility {
+    PUBLIC,  // listed in the lobby
+    #[default]
+    PRIVATE, // only reachable by its link
+}
@@ -1,35 +1,155 @@
 use std::collections::HashMap;
+use std::time::Duration;
 use futures::future::err;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use serde::{Deserialize, Serialize};
 
 use pong::event::event::{EventWrapper, EventReader, EventWriter};
+use rskafka::client::consumer::StartOffset;
 
-use crate::hash::Hasher;
 use crate::kafka::{
-    KafkaSessionEventReaderImpl, KafkaSessionEventWriterImpl,
+    latest_offsets, read_topic, KafkaSessionEventReaderImpl, KafkaSessionEventWriterImpl,
     KafkaTopicManager,
 };
 use crate::actor::{Actor, Observer, Player};
-use crate::event::{SessionEvent, SessionEventPayload};
-use crate::session::{Session, SessionState};
+use crate::chat::{ChatConfig, ChatModerator};
+use crate::event::{ChatContent, ChatEventPayload, SessionEvent, SessionEventPayload};
+use crate::expiry::{SessionExpiry, SessionExpiryConfig};
+use crate::join_code::{JoinCodeConfig, JoinCodes};
+use crate::presence::PresenceConfig;
+use crate::game_loop::SessionGameLoop;
+use crate::lobby::{SessionPage, SessionQuery};
+use crate::rematch::{check_rematch, rematch_session, RematchProgress};
+use crate::session::{Session, SessionMode, SessionState, SessionVisibility, MAX_OBSERVERS};
+use crate::snapshot::SnapshotStore;
+use crate::utils::time_utils::now;
+use pong::pong::pong_rules::MatchFormat;
+
+// topics every session writes its events to, each session has its own partition.
+const SESSION_TOPICS: [&str; 8] = ["host_tick", "peer_tick", "input", "session", "heart_beat", "status", "presence", "chat"];
 
 pub struct SessionManager {
     kafka_host: String,
     sessions: Vec<Session>,
+    // ms timestamp of the last player activity per session.
+    last_activity: HashMap<String, u128>,
+    expiry_config: SessionExpiryConfig,
+    presence_config: PresenceConfig,
+    // generation of the current ws connection per session and actor, it increases with every reconnect.
+    connections: HashMap<(String, String), u64>,
+    snapshot_store: SnapshotStore,
+    join_codes: JoinCodes,
+    chat: ChatModerator,
+    // partitions of removed sessions, kafka can't delete partitions so they are reused by new sessions.
+    free_partitions: Vec<u16>,
     topic_manager: KafkaTopicManager,
 }
 
-// TODO: On startup read the session events from kafka to restore the session id <-> hash mappings.
 impl SessionManager {
-    pub fn new(kafka_host: &str, kafka_topic_manager_host: &str) -> SessionManager {
+    pub fn new(
+        kafka_host: &str,
+        kafka_topic_manager_host: &str,
+        expiry_config: SessionExpiryConfig,
+        presence_config: PresenceConfig,
+        join_code_config: JoinCodeConfig,
+        chat_config: ChatConfig,
+    ) -> SessionManager {
         SessionManager {
             kafka_host: kafka_host.to_owned(),
             sessions: vec![],
+            last_activity: HashMap::new(),
+            expiry_config,
+            presence_config,
+            connections: HashMap::new(),
+            snapshot_store: SnapshotStore::new(),
+            join_codes: JoinCodes::new(join_code_config),
+            chat: ChatModerator::new(chat_config),
+            free_partitions: vec![],
             topic_manager: KafkaTopicManager::from(kafka_topic_manager_host)
         }
     }
 
+    /// Rebuilds the sessions by replaying the session topic, to be called once on startup.
+    /// Game loops of running server sessions are restarted, their engine starts from a fresh field.
+    pub async fn restore_sessions(&mut self) -> Result<(), String> {
+        info!("restoring sessions from the session topic");
+        let events = read_topic(&self.kafka_host, "session").await?;
+        let event_count = events.len();
+        let session_events = events
+            .into_iter()
+            .filter_map(|e| match serde_json::from_str::<SessionEvent>(&e.event) {
+                Ok(event) => Some(event),
+                Err(err) => {
+                    warn!("skipping session event that can't be deserialized {}: {:?}", e.event, err);
+                    None
+                }
+            })
+            .collect();
+        self.sessions = replay_session_events(session_events);
+        // restored sessions get a full ttl, their players might reconnect.
+        let restored_at = now();
+        self.last_activity = self.sessions.iter().map(|s| (s.session_id.clone(), restored_at)).collect();
+        for session in self.sessions.iter().filter(|s| s.state == SessionState::PENDING) {
+            if let Some(code) = &session.join_code {
+                self.join_codes.restore(code, &session.session_id, restored_at);
+            }
+        }
+        info!("restored {} sessions from {} session events", self.sessions.len(), event_count);
+        let running_server_sessions = self.sessions
+            .iter()
+            .filter(|s| s.mode == SessionMode::SERVER && s.state == SessionState::RUNNING)
+            .cloned()
+            .collect::<Vec<Session>>();
+        for session in running_server_sessions {
+            if let Err(e) = self.start_game_loop(&session).await {
+                error!("failed to restart game loop of restored session {}: {}", session.session_id, e);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_sessions_in_state(&self, state: SessionState) -> Vec<Session> {
+        self.sessions.iter().filter(|s| s.state == state).cloned().collect()
+    }
+
+    pub fn presence_config(&self) -> PresenceConfig {
+        self.presence_config.clone()
+    }
+
+    pub fn snapshot_store(&self) -> SnapshotStore {
+        self.snapshot_store.clone()
+    }
+
+    /// Registers a ws connection of the actor, it replaces the previous connection.
+    /// Returns the generation of the connection and whether the actor was connected before.
+    pub fn connect(&mut self, session_id: &str, actor_id: &str) -> (u64, bool) {
+        let key = (session_id.to_owned(), actor_id.to_owned());
+        let previous = self.connections.get(&key).cloned();
+        let generation = previous.map_or(0, |g| g + 1);
+        self.connections.insert(key, generation);
+        (generation, previous.is_some())
+    }
+
+    /// Whether the dropped connection was still the current one of the actor, replaced connections are ignored.
+    pub fn is_current_connection(&self, session_id: &str, actor_id: &str, generation: u64) -> bool {
+        self.connections.get(&(session_id.to_owned(), actor_id.to_owned())) == Some(&generation)
+    }
+
+    /// Public sessions that match the query, for the lobby.
+    pub fn find_sessions(&self, query: &SessionQuery) -> SessionPage {
+        query.apply(&self.sessions)
+    }
+
+    /// The pending session a join code was issued for, as long as the code did not expire.
+    pub fn resolve_join_code(&self, join_code: &str) -> Option<String> {
+        self.join_codes.resolve(join_code, now())
+    }
+
+    /// The chat event to publish for a message of the actor, or why it is rejected.
+    pub fn moderate_chat(&mut self, session_id: &str, actor_id: &str, content: ChatContent) -> Result<ChatEventPayload, String> {
+        self.chat.moderate(session_id, actor_id, content, now())
+    }
+
     pub fn get_session(&self, session_id: &str) -> Option<Session> {
         self.sessions
             .iter()
@@ -37,18 +157,29 @@ impl SessionManager {
             .map_or_else(|| None, |s| Some(s.clone()))
     }
 
-    pub async fn create_session(&mut self, player: Player) -> Result<SessionEvent, String> {
-        info!("called to create new session by player {:?}", player);
-        let add_partition_res = self.topic_manager.add_partition().await;
+    pub async fn create_session(&mut self, player: Player, mode: SessionMode, format: MatchFormat, visibility: SessionVisibility) -> Result<SessionEvent, String> {
+        info!("called to create new {:?} {:?} session by player {:?}", visibility, mode, player);
+        let add_partition_res = self.allocate_partition().await;
         if let Err(e) = add_partition_res {
             error!("failed to create partition: {}", e);
             return Err(e);
         }
-        let session_partition_id = add_partition_res.unwrap();
-        let session_id = Hasher::hash(session_partition_id);
-        let session = Session::new(session_partition_id, session_id.clone(), player.clone());
+        let (session_partition_id, start_offsets) = add_partition_res.unwrap();
+        let session_id = Session::generate_id();
+        let join_code = self.join_codes.issue(&session_id, now());
+        if let Err(e) = join_code {
+            error!("failed to issue join code: {}", e);
+            self.release_partition(session_partition_id);
+            return Err(e);
+        }
+        let mut session = Session::new(session_partition_id, session_id.clone(), player.clone(), mode, format);
+        session.visibility = visibility;
+        session.start_offsets = start_offsets;
+        session.created_at = now() as u64;
+        session.join_code = Some(join_code.unwrap());
         info!("successfully created session: {:?}", session);
         self.sessions.push(session.clone());
+        self.touch(&session_id);
         let session_created = SessionEvent::Created(SessionEventPayload {
             session: session.clone(),
             actor: Actor::Player(player),
@@ -60,11 +191,15 @@ impl SessionManager {
             if let Some(i) = index {
                 debug!("session create event could not be persisted - remove session from cache.");
                 self.sessions.remove(i);
+                self.last_activity.remove(&session_id);
+                self.join_codes.revoke(&session_id);
+                self.release_partition(session_partition_id);
             }
             error!(
                 "failed to write session created event for {:?} to producer: {}",
                 session, e
             );
+            return Err(e);
         }
         info!("successfully persisted create session event.");
         Ok(session_created)
@@ -101,6 +236,9 @@ impl SessionManager {
             session.state = SessionState::RUNNING;
             session.clone()
         };
+        // the session is full, its code can be given to another one.
+        self.join_codes.revoke(&session_id);
+        self.touch(&session_id);
         let session_joined_event = SessionEvent::Joined(SessionEventPayload {
             session: updated_session.clone(),
             reason: "session joined".to_owned(),
@@ -116,6 +254,9 @@ impl SessionManager {
                 );
             }
         };
+        if updated_session.mode == SessionMode::SERVER {
+            self.start_game_loop(&updated_session).await?;
+        }
         println!("sessions = {:?}", self.sessions);
         Ok(session_joined_event)
     }
@@ -140,8 +281,8 @@ impl SessionManager {
                 let error = format!("Can't add observer to session {} that is already registered as an observer: {:?}", session_id, observer);
                 return Err(error);
             }
-            if session.observers.len() > 5 {
-                let error = format!("Can't have more than 5 observers in session: {}", session_id);
+            if session.observers.len() >= MAX_OBSERVERS {
+                let error = format!("Can't have more than {} observers in session: {}", MAX_OBSERVERS, session_id);
                 return Err(error);
             }
             session.observers.push(observer.clone());
@@ -166,6 +307,215 @@ impl SessionManager {
         Ok(session_joined_event)
     }
 
+    /// Re-binds a player of a running session whose connection dropped.
+    pub async fn reconnect_session(&mut self, session_id: &str, player: Actor) -> Result<SessionEvent, String> {
+        let session = self.find_session(session_id);
+        if session.is_none() {
+            return Err(format!("Can't reconnect to session that does not exist: {}", session_id));
+        }
+        let session = session.unwrap();
+        if session.state != SessionState::RUNNING {
+            return Err(format!("Can't reconnect to session that is not RUNNING: {}", session_id));
+        }
+        if !session.players.iter().any(|p| p.id == player.id()) {
+            return Err(format!("Can't reconnect actor {} that is not a player of session {}", player.id(), session_id));
+        }
+        self.touch(session_id);
+        info!("player {} reconnected to session {}", player.id(), session_id);
+        let session_reconnected_event = SessionEvent::Reconnected(SessionEventPayload {
+            session,
+            actor: player,
+            reason: "player reconnected".to_owned(),
+        });
+        if let Err(e) = self.write_to_producer(&session_reconnected_event).await {
+            error!("failed to write session reconnected event for {}: {}", session_id, e);
+        }
+        Ok(session_reconnected_event)
+    }
+
+    /// Records that a player accepts a rematch of a finished game, the rematch is created once both players did.
+    /// The previous session is closed and links to the rematch, the rematch links back to it.
+    pub async fn accept_rematch(&mut self, session_id: &str, player_id: &str) -> Result<RematchProgress, String> {
+        let previous = self.find_session(session_id);
+        if previous.is_none() {
+            return Err(format!("Can't rematch session that does not exist: {}", session_id));
+        }
+        let mut previous = previous.unwrap();
+        if let Some(rematch_id) = &previous.rematch {
+            return self.find_session(rematch_id)
+                .map(RematchProgress::Existing)
+                .ok_or(format!("Rematch {} of session {} does not exist anymore", rematch_id, session_id));
+        }
+        // the latest snapshot carries the result, of the host in HOST mode and of the game loop in SERVER mode.
+        check_rematch(&previous, self.snapshot_store.winner(session_id).as_deref())?;
+        let player = previous.players.iter().find(|p| p.id == player_id).cloned();
+        if player.is_none() {
+            return Err(format!("Can't accept rematch of session {}, {} is not a player of it", session_id, player_id));
+        }
+        let player = player.unwrap();
+        if !previous.rematch_accepted_by.contains(&player.id) {
+            previous.rematch_accepted_by.push(player.id.clone());
+        }
+        self.touch(session_id);
+        if previous.rematch_accepted_by.len() < previous.players.len() {
+            self.replace_session(previous.clone());
+            info!("player {} accepted a rematch of session {}", player.id, session_id);
+            let rematch_accepted_event = SessionEvent::RematchAccepted(SessionEventPayload {
+                session: previous.clone(),
+                actor: Actor::Player(player),
+                reason: "rematch accepted".to_owned(),
+            });
+            if let Err(e) = self.write_to_producer(&rematch_accepted_event).await {
+                error!("failed to write rematch accepted event for {}: {}", session_id, e);
+            }
+            return Ok(RematchProgress::Pending(previous));
+        }
+
+        let add_partition_res = self.allocate_partition().await;
+        if let Err(e) = add_partition_res {
+            error!("failed to create partition for rematch of {}: {}", session_id, e);
+            return Err(e);
+        }
+        let (rematch_partition_id, start_offsets) = add_partition_res.unwrap();
+        let mut rematch = rematch_session(&previous, rematch_partition_id, Session::generate_id());
+        rematch.start_offsets = start_offsets;
+        rematch.created_at = now() as u64;
+        info!("both players accepted, created rematch {} of session {}", rematch.session_id, session_id);
+        self.sessions.push(rematch.clone());
+        self.touch(&rematch.session_id);
+        let rematch_created_event = SessionEvent::Created(SessionEventPayload {
+            session: rematch.clone(),
+            actor: Actor::Player(player.clone()),
+            reason: format!("rematch of session {}", session_id),
+        });
+        if let Err(e) = self.write_to_producer(&rematch_created_event).await {
+            error!("failed to write created event of rematch {}: {}", rematch.session_id, e);
+        }
+        previous.rematch = Some(rematch.session_id.clone());
+        self.replace_session(previous.clone());
+        let rematched_event = SessionEvent::Rematched(SessionEventPayload {
+            session: previous.clone(),
+            actor: Actor::Player(player.clone()),
+            reason: format!("rematched as session {}", rematch.session_id),
+        });
+        if let Err(e) = self.write_to_producer(&rematched_event).await {
+            error!("failed to write rematched event for {}: {}", session_id, e);
+        }
+        if previous.state == SessionState::RUNNING {
+            if let Err(e) = self.close_session(session_id, Actor::Player(player), "rematch").await {
+                error!("failed to close session {} after its rematch: {}", session_id, e);
+            }
+        }
+        if rematch.mode == SessionMode::SERVER {
+            self.start_game_loop(&rematch).await?;
+        }
+        Ok(RematchProgress::Created(rematch))
+    }
+
+    /// Records player activity, sessions without activity expire.
+    pub fn touch(&mut self, session_id: &str) {
+        self.last_activity.insert(session_id.to_owned(), now());
+    }
+
+    pub async fn close_session(&mut self, session_id: &str, actor: Actor, reason: &str) -> Result<SessionEvent, String> {
+        let updated_session = {
+            let session = self.sessions.iter_mut().find(|s| s.session_id == session_id);
+            if let None = session {
+                return Err(format!("Can't close session that does not exist: {}", session_id));
+            }
+            let session = session.unwrap();
+            if session.state == SessionState::CLOSED {
+                return Err(format!("Can't close session that is already CLOSED: {}", session_id));
+            }
+            session.state = SessionState::CLOSED;
+            session.clone()
+        };
+        self.join_codes.revoke(session_id);
+        // the closed ttl starts now.
+        self.touch(session_id);
+        let session_closed_event = SessionEvent::Closed(SessionEventPayload {
+            session: updated_session,
+            actor,
+            reason: reason.to_owned(),
+        });
+        if let Err(e) = self.write_to_producer(&session_closed_event).await {
+            error!("failed to write session closed event for {}: {}", session_id, e);
+        }
+        info!("closed session {}: {}", session_id, reason);
+        Ok(session_closed_event)
+    }
+
+    /// Closes sessions whose ttl passed and forgets closed ones.
+    /// Game loops and websockets of the session stop once they read the closed event.
+    pub async fn expire_sessions(&mut self) -> Vec<SessionEvent> {
+        let now = now();
+        self.join_codes.remove_expired(now);
+        let mut to_close = vec![];
+        let mut to_remove = vec![];
+        for session in self.sessions.iter() {
+            let last_activity = self.last_activity.get(&session.session_id).cloned().unwrap_or(now);
+            let idle_for = Duration::from_millis(now.saturating_sub(last_activity) as u64);
+            match self.expiry_config.check(session, idle_for) {
+                Some(SessionExpiry::Close(reason)) => to_close.push((session.session_id.clone(), reason)),
+                Some(SessionExpiry::Remove) => to_remove.push(session.session_id.clone()),
+                None => {}
+            }
+        }
+        for session_id in to_remove {
+            debug!("removing expired session {}", session_id);
+            let partition_id = self.find_session(&session_id).map(|s| s.id);
+            self.sessions.retain(|s| s.session_id != session_id);
+            if let Some(partition_id) = partition_id {
+                self.release_partition(partition_id);
+            }
+            self.last_activity.remove(&session_id);
+            self.connections.retain(|(s, _), _| *s != session_id);
+            self.snapshot_store.remove(&session_id);
+            self.chat.remove_session(&session_id);
+        }
+        let mut closed = vec![];
+        for (session_id, reason) in to_close {
+            match self.close_session(&session_id, Actor::Server, &reason).await {
+                Ok(event) => closed.push(event),
+                Err(e) => error!("failed to close expired session {}: {}", session_id, e),
+            }
+        }
+        closed
+    }
+
+    // a free partition of a removed session, a new one only if there is none.
+    // on a reused partition the session starts at the latest offsets, past the events of earlier sessions.
+    async fn allocate_partition(&mut self) -> Result<(u16, HashMap<String, i64>), String> {
+        let partition_id = match self.free_partitions.pop() {
+            Some(partition_id) => partition_id,
+            None => return self.topic_manager.add_partition().await.map(|id| (id, HashMap::new())),
+        };
+        debug!("reusing partition {}", partition_id);
+        let offsets = latest_offsets(&self.kafka_host, &i32::from(partition_id), &SESSION_TOPICS).await;
+        if let Err(e) = offsets {
+            self.free_partitions.push(partition_id);
+            return Err(e);
+        }
+        Ok((partition_id, offsets.unwrap()))
+    }
+
+    // partitions are only freed once no session uses them, restored sessions can share one.
+    fn release_partition(&mut self, partition_id: u16) {
+        if self.sessions.iter().any(|s| s.id == partition_id) || self.free_partitions.contains(&partition_id) {
+            return;
+        }
+        debug!("freeing partition {}", partition_id);
+        self.free_partitions.push(partition_id);
+    }
+
+    async fn start_game_loop(&self, session: &Session) -> Result<(), String> {
+        let (reader, writer) = self.split(&session.session_id, &["input", "session", "presence"]).await?;
+        let game_loop = SessionGameLoop::new(session.clone(), reader, writer, self.snapshot_store.clone());
+        tokio::spawn(game_loop.run());
+        info!("started game loop for server session {}", session.session_id);
+        Ok(())
+    }
+
     async fn write_to_producer(&mut self, session_event: &SessionEvent) -> Result<(), String>
     {
         let session_id = session_event.session_id();
@@ -194,7 +544,17 @@ impl SessionManager {
         session_id: &str,
         read_topics: &[&str],
     ) -> Result<(SessionReader, SessionWriter), String> {
-        let reader = self.get_session_reader(session_id, read_topics).await;
+        self.split_from(session_id, read_topics, StartOffset::Earliest).await
+    }
+
+    /// Like `split`, but the reader starts at the given offset, e.g. at the latest events for reconnecting players.
+    pub async fn split_from(
+        &self,
+        session_id: &str,
+        read_topics: &[&str],
+        start_offset: StartOffset,
+    ) -> Result<(SessionReader, SessionWriter), String> {
+        let reader = self.get_session_reader(session_id, read_topics, start_offset).await;
         if let Err(e) = reader {
             error!("Failed to create session reader for session {}: {:?}", session_id, e);
             return Err("Failed to create session reader".to_string());
@@ -211,13 +571,14 @@ impl SessionManager {
         &self,
         session_id: &str,
         topics: &[&str],
+        start_offset: StartOffset,
     ) -> Result<SessionReader, String> {
         let session = self.find_session(&session_id);
         if let None = session {
             return Err(format!("Unable to find session with hash {}", session_id));
         }
         let session = session.unwrap();
-        let kafka_reader = KafkaSessionEventReaderImpl::new(&self.kafka_host, &session, topics).await;
+        let kafka_reader = KafkaSessionEventReaderImpl::new(&self.kafka_host, &session, topics, start_offset).await;
         if let Err(_) = kafka_reader {
             return Err("Unable to create kafka reader.".to_string());
         }
@@ -235,7 +596,7 @@ impl SessionManager {
             return Err(format!("Unable to find session with hash {}", session_id));
         }
         let session = session.unwrap();
-        let writer = KafkaSessionEventWriterImpl::new(&self.kafka_host, vec!["host_tick", "peer_tick", "session", "heart_beat"], &i32::from(session.id)).await;
+        let writer = KafkaSessionEventWriterImpl::new(&self.kafka_host, SESSION_TOPICS.to_vec(), &i32::from(session.id)).await;
         let event_writer =
             EventWriter::new(Box::new(writer));
         Ok(SessionWriter {
@@ -244,6 +605,12 @@ impl SessionManager {
         })
     }
 
+    fn replace_session(&mut self, session: Session) {
+        if let Some(existing) = self.sessions.iter_mut().find(|s| s.session_id == session.session_id) {
+            *existing = session;
+        }
+    }
+
     fn find_session(&self, session_id: &str) -> Option<Session> {
         self.sessions
             .iter()
@@ -252,6 +619,37 @@ impl SessionManager {
     }
 }
 
+/// Applies the session events in the order they were persisted.
+/// Every event carries the session as it was after the event, so the last one wins.
+fn replay_session_events(events: Vec<SessionEvent>) -> Vec<Session> {
+    let mut sessions: Vec<Session> = vec![];
+    for event in events {
+        let existing = sessions.iter().position(|s| s.session_id == event.session_id());
+        match (event, existing) {
+            (SessionEvent::Created(payload), None) => sessions.push(payload.session),
+            (SessionEvent::Created(payload), Some(i)) => {
+                warn!("session {} was created more than once, keeping the latest", payload.session_id());
+                sessions[i] = payload.session;
+            }
+            (SessionEvent::Joined(payload), Some(i))
+            | (SessionEvent::ObserverAdded(payload), Some(i))
+            | (SessionEvent::Reconnected(payload), Some(i))
+            | (SessionEvent::RematchAccepted(payload), Some(i))
+            | (SessionEvent::Rematched(payload), Some(i)) => {
+                sessions[i] = payload.session;
+            }
+            (SessionEvent::Closed(payload), Some(i)) => {
+                sessions[i] = payload.session;
+                sessions[i].state = SessionState::CLOSED;
+            }
+            (event, None) => {
+                warn!("ignoring session event for unknown session {}: {:?}", event.session_id(), event);
+            }
+        }
+    }
+    sessions
+}
+
 pub struct SessionWriter {
     session: Session,
     writer: EventWriter,
@@ -260,9 +658,10 @@ pub struct SessionWriter {
 impl SessionWriter {
     pub async fn write_to_session(&mut self, topic: &str, messages: Vec<&str>) -> Result<(), String> {
         let events = messages.into_iter().map(|e| {
+            // partitions are reused, the key tells the events of the sessions apart.
             EventWrapper {
                 event: e.to_owned(),
-                key: Some(self.session.id.to_string()),
+                key: Some(self.session.session_id.clone()),
                 topic: topic.to_owned(),
             }
         }).collect();
@@ -271,13 +670,151 @@ impl SessionWriter {
 }
 
 pub struct SessionReader {
-    #[allow(dead_code)]
     session: Session,
     reader: EventReader,
 }
 
 impl SessionReader {
+    /// Events of the session, those of earlier sessions on the same partition are skipped.
+    /// Empty only if there is no record to read, not if all records read belonged to other sessions.
     pub async fn read_from_session(&mut self) -> Result<Vec<EventWrapper>, String> {
-        self.reader.read().await
+        loop {
+            let events = self.reader.read().await?;
+            if events.is_empty() {
+                return Ok(events);
+            }
+            let events: Vec<EventWrapper> = events.into_iter().filter(|e| is_event_of(e, &self.session)).collect();
+            if !events.is_empty() {
+                return Ok(events);
+            }
+        }
+    }
+}
+
+// events written before partitions were reused are keyed with the partition id. such keys are only
+// accepted on partitions that weren't reused, there they can only belong to the session itself.
+fn is_event_of(event: &EventWrapper, session: &Session) -> bool {
+    match event.key.as_deref() {
+        Some(key) if key == session.session_id => true,
+        Some(key) => session.start_offsets.is_empty() && key == session.id.to_string(),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use crate::actor::{Actor, Observer, Player};
+    use crate::event::{SessionEvent, SessionEventPayload};
+    use crate::session::{Session, SessionMode, SessionState};
+    use crate::expiry::SessionExpiryConfig;
+    use crate::presence::PresenceConfig;
+    use crate::join_code::JoinCodeConfig;
+    use crate::chat::ChatConfig;
+    use crate::rematch::rematch_session;
+    use crate::session_manager::{is_event_of, replay_session_events, SessionManager};
+    use pong::event::event::EventWrapper;
+    use pong::pong::pong_rules::MatchFormat;
+
+    #[test]
+    fn should_replay_session_events() {
+        let host = Player { id: "player_1".to_owned(), nr: 1, ip: "127.0.0.1".to_owned() };
+        let peer = Player { id: "player_2".to_owned(), nr: 2, ip: "127.0.0.1".to_owned() };
+        let observer = Observer { id: "observer_1".to_owned(), ip: "127.0.0.1".to_owned() };
+        let mut a = Session::new(1, "a".to_owned(), host.clone(), SessionMode::HOST, MatchFormat::default());
+        let b = Session::new(2, "b".to_owned(), host.clone(), SessionMode::SERVER, MatchFormat::default());
+        let created_a = event(SessionEvent::Created, &a, Actor::Player(host.clone()));
+        let created_b = event(SessionEvent::Created, &b, Actor::Player(host.clone()));
+        a.players.push(peer.clone());
+        a.state = SessionState::RUNNING;
+        let joined_a = event(SessionEvent::Joined, &a, Actor::Player(peer.clone()));
+        a.observers.push(observer.clone());
+        let watched_a = event(SessionEvent::ObserverAdded, &a, Actor::Observer(observer));
+        let mut closed_b = b.clone();
+        closed_b.state = SessionState::CLOSED;
+        let closed_b = event(SessionEvent::Closed, &closed_b, Actor::Player(host.clone()));
+        let unknown = Session::new(3, "c".to_owned(), host.clone(), SessionMode::HOST, MatchFormat::default());
+        let joined_unknown = event(SessionEvent::Joined, &unknown, Actor::Player(peer));
+
+        let sessions = replay_session_events(vec![created_a, created_b, joined_a, joined_unknown, watched_a, closed_b]);
+
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0], a);
+        assert_eq!(sessions[0].state, SessionState::RUNNING);
+        assert_eq!(sessions[0].players.len(), 2);
+        assert_eq!(sessions[0].observers.len(), 1);
+        assert_eq!(sessions[1].session_id, "b");
+        assert_eq!(sessions[1].state, SessionState::CLOSED);
+    }
+
+    #[test]
+    fn should_replay_rematch_links() {
+        let host = Player { id: "player_1".to_owned(), nr: 1, ip: "127.0.0.1".to_owned() };
+        let peer = Player { id: "player_2".to_owned(), nr: 2, ip: "127.0.0.1".to_owned() };
+        let mut a = Session::new(1, "a".to_owned(), host.clone(), SessionMode::HOST, MatchFormat::default());
+        let created_a = event(SessionEvent::Created, &a, Actor::Player(host.clone()));
+        a.players.push(peer.clone());
+        a.state = SessionState::RUNNING;
+        let joined_a = event(SessionEvent::Joined, &a, Actor::Player(peer.clone()));
+        a.rematch_accepted_by.push(host.id.clone());
+        let accepted_a = event(SessionEvent::RematchAccepted, &a, Actor::Player(host.clone()));
+        a.rematch_accepted_by.push(peer.id.clone());
+        let b = rematch_session(&a, 2, "b".to_owned());
+        let created_b = event(SessionEvent::Created, &b, Actor::Player(peer.clone()));
+        a.rematch = Some("b".to_owned());
+        let rematched_a = event(SessionEvent::Rematched, &a, Actor::Player(peer.clone()));
+        let closed_a = event(SessionEvent::Closed, &a, Actor::Player(peer.clone()));
+
+        let sessions = replay_session_events(vec![created_a, joined_a, accepted_a, created_b, rematched_a, closed_a]);
+
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].state, SessionState::CLOSED);
+        assert_eq!(sessions[0].rematch, Some("b".to_owned()));
+        assert_eq!(sessions[0].rematch_accepted_by, vec!["player_1".to_owned(), "player_2".to_owned()]);
+        assert_eq!(sessions[1].state, SessionState::RUNNING);
+        assert_eq!(sessions[1].rematch_of, Some("a".to_owned()));
+        assert_eq!(sessions[1].players[0].id, "player_2");
+    }
+
+    #[test]
+    fn should_only_treat_latest_connection_as_current() {
+        let mut session_manager = SessionManager::new("localhost:9092", "localhost:7243", SessionExpiryConfig::default(), PresenceConfig::default(), JoinCodeConfig::default(), ChatConfig::default());
+        assert_eq!(session_manager.connect("a", "player_1"), (0, false));
+        assert_eq!(session_manager.connect("a", "player_2"), (0, false));
+        assert_eq!(session_manager.connect("a", "player_1"), (1, true));
+        assert!(!session_manager.is_current_connection("a", "player_1", 0));
+        assert!(session_manager.is_current_connection("a", "player_1", 1));
+        assert!(session_manager.is_current_connection("a", "player_2", 0));
+    }
+
+    #[test]
+    fn should_only_free_partitions_without_sessions() {
+        let mut session_manager = SessionManager::new("localhost:9092", "localhost:7243", SessionExpiryConfig::default(), PresenceConfig::default(), JoinCodeConfig::default(), ChatConfig::default());
+        let host = Player { id: "player_1".to_owned(), nr: 1, ip: "127.0.0.1".to_owned() };
+        session_manager.sessions.push(Session::new(1, "a".to_owned(), host, SessionMode::HOST, MatchFormat::default()));
+        session_manager.release_partition(1);
+        session_manager.release_partition(2);
+        session_manager.release_partition(2);
+        assert_eq!(session_manager.free_partitions, vec![2]);
+    }
+
+    #[test]
+    fn should_skip_events_of_earlier_sessions_on_partition() {
+        let host = Player { id: "player_1".to_owned(), nr: 1, ip: "127.0.0.1".to_owned() };
+        let session = Session::new(1, "b".to_owned(), host, SessionMode::HOST, MatchFormat::default());
+        let event = |key: Option<&str>| EventWrapper { topic: "session".to_owned(), key: key.map(|k| k.to_owned()), event: "{}".to_owned() };
+        assert!(is_event_of(&event(Some("b")), &session));
+        assert!(!is_event_of(&event(Some("a")), &session));
+        assert!(!is_event_of(&event(None), &session));
+        // keyed with the partition id before partitions were reused.
+        assert!(is_event_of(&event(Some("1")), &session));
+        assert!(!is_event_of(&event(Some("2")), &session));
+        let reused = Session { start_offsets: HashMap::from([("session".to_owned(), 42)]), ..session };
+        assert!(is_event_of(&event(Some("b")), &reused));
+        assert!(!is_event_of(&event(Some("1")), &reused));
+    }
+
+    fn event(event_type: fn(SessionEventPayload) -> SessionEvent, session: &Session, actor: Actor) -> SessionEvent {
+        event_type(SessionEventPayload { session: session.clone(), actor, reason: "test".to_owned() })
     }
 }
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::websocket_handler::{GameObjectStateDTO, HostSessionSnapshotDTO};
+
+/// The objects a receiver knows after applying every host snapshot up to `seq`.
+/// Host snapshots only contain changed objects, see `pong::pong::pong_delta`.
+#[derive(Default)]
+pub struct SnapshotBaseline {
+    seq: Option<u64>,
+    objects: HashMap<String, GameObjectStateDTO>,
+    order: Vec<String>,
+}
+
+impl SnapshotBaseline {
+    pub fn new() -> SnapshotBaseline {
+        SnapshotBaseline::default()
+    }
+
+    /// Merges the snapshot into the baseline and returns the snapshot to forward.
+    /// Deltas that build on the baseline are forwarded as is, every other snapshot is
+    /// replaced by the full state, so that the receiver can start over from there.
+    pub fn apply(&mut self, mut snapshot: HostSessionSnapshotDTO) -> HostSessionSnapshotDTO {
+        let continues_baseline = snapshot.baseline.is_some() && snapshot.baseline == self.seq;
+        if snapshot.baseline.is_none() {
+            self.objects.clear();
+            self.order.clear();
+        }
+        for object in snapshot.objects.iter() {
+            if !self.objects.contains_key(&object.id) {
+                self.order.push(object.id.clone());
+            }
+            self.objects.insert(object.id.clone(), object.clone());
+        }
+        self.seq = Some(snapshot.seq);
+        if snapshot.baseline.is_none() || continues_baseline {
+            return snapshot;
+        }
+        snapshot.baseline = None;
+        snapshot.objects = self.objects();
+        snapshot
+    }
+
+    fn objects(&self) -> Vec<GameObjectStateDTO> {
+        self.order.iter().map(|id| self.objects[id].clone()).collect()
+    }
+}
+
+struct LatestSnapshot {
+    baseline: SnapshotBaseline,
+    // the latest snapshot without its objects, they are kept in the baseline.
+    snapshot: HostSessionSnapshotDTO,
+}
+
+/// The latest host snapshot of every session, so that players who reconnect get the full state right away.
+#[derive(Clone, Default)]
+pub struct SnapshotStore {
+    sessions: Arc<Mutex<HashMap<String, LatestSnapshot>>>,
+}
+
+impl SnapshotStore {
+    pub fn new() -> SnapshotStore {
+        SnapshotStore::default()
+    }
+
+    pub fn update(&self, snapshot: &HostSessionSnapshotDTO) {
+        let mut sessions = self.sessions.lock().unwrap();
+        let latest = sessions.entry(snapshot.session_id.clone()).or_insert_with(|| LatestSnapshot {
+            baseline: SnapshotBaseline::new(),
+            snapshot: snapshot.clone(),
+        });
+        latest.baseline.apply(snapshot.clone());
+        latest.snapshot = HostSessionSnapshotDTO { objects: vec![], ..snapshot.clone() };
+    }
+
+    /// The latest snapshot of the session with every object.
+    pub fn latest(&self, session_id: &str) -> Option<HostSessionSnapshotDTO> {
+        let sessions = self.sessions.lock().unwrap();
+        sessions.get(session_id).map(|latest| HostSessionSnapshotDTO {
+            baseline: None,
+            objects: latest.baseline.objects(),
+            ..latest.snapshot.clone()
+        })
+    }
+
+    /// The winner of the session once the latest snapshot has one.
+    pub fn winner(&self, session_id: &str) -> Option<String> {
+        let sessions = self.sessions.lock().unwrap();
+        sessions.get(session_id).and_then(|latest| latest.snapshot.state.winner.clone())
+    }
+
+    pub fn remove(&self, session_id: &str) {
+        self.sessions.lock().unwrap().remove(session_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use pong::game_field::GameState;
+
+    use crate::snapshot::{SnapshotBaseline, SnapshotStore};
+    use crate::websocket_handler::{GameObjectStateDTO, HostSessionSnapshotDTO};
+
+    fn object(id: &str, x: f64) -> GameObjectStateDTO {
+        GameObjectStateDTO {
+            id: id.to_owned(),
+            orientation_x: 0.,
+            orientation_y: 0.,
+            shape_param_1: 10.,
+            shape_param_2: 10.,
+            vel_x: 0.,
+            vel_y: 0.,
+            x,
+            y: 0.,
+        }
+    }
+
+    fn snapshot(seq: u64, baseline: Option<u64>, objects: Vec<GameObjectStateDTO>) -> HostSessionSnapshotDTO {
+        HostSessionSnapshotDTO {
+            session_id: "abc".to_owned(),
+            inputs: vec![],
+            state: GameState::new(),
+            objects,
+            player_id: "host".to_owned(),
+            ts: 0,
+            checksum: None,
+            seq,
+            baseline,
+            acked_inputs: HashMap::new(),
+        }
+    }
+
+    fn ids(snapshot: &HostSessionSnapshotDTO) -> Vec<(String, f64)> {
+        snapshot.objects.iter().map(|o| (o.id.clone(), o.x)).collect()
+    }
+
+    #[test]
+    fn should_forward_continuous_deltas() {
+        let mut baseline = SnapshotBaseline::new();
+        baseline.apply(snapshot(1, None, vec![object("bound", 0.), object("ball", 1.)]));
+        let res = baseline.apply(snapshot(2, Some(1), vec![object("ball", 2.)]));
+        assert_eq!(res.baseline, Some(1));
+        assert_eq!(ids(&res), vec![("ball".to_owned(), 2.)]);
+    }
+
+    #[test]
+    fn should_send_full_state_on_gap() {
+        let mut baseline = SnapshotBaseline::new();
+        baseline.apply(snapshot(1, None, vec![object("bound", 0.), object("ball", 1.)]));
+        let res = baseline.apply(snapshot(3, Some(2), vec![object("ball", 3.)]));
+        assert_eq!(res.baseline, None);
+        assert_eq!(ids(&res), vec![("bound".to_owned(), 0.), ("ball".to_owned(), 3.)]);
+    }
+
+    #[test]
+    fn should_start_over_on_full_snapshot() {
+        let mut baseline = SnapshotBaseline::new();
+        baseline.apply(snapshot(1, None, vec![object("bound", 0.), object("ball", 1.)]));
+        baseline.apply(snapshot(1, None, vec![object("ball", 5.)]));
+        let res = baseline.apply(snapshot(4, Some(3), vec![]));
+        assert_eq!(ids(&res), vec![("ball".to_owned(), 5.)]);
+    }
+
+    #[test]
+    fn should_keep_latest_full_snapshot() {
+        let store = SnapshotStore::new();
+        assert!(store.latest("abc").is_none());
+        store.update(&snapshot(1, None, vec![object("bound", 0.), object("ball", 1.)]));
+        store.update(&snapshot(2, Some(1), vec![object("ball", 2.)]));
+        let res = store.latest("abc").unwrap();
+        assert_eq!(res.seq, 2);
+        assert_eq!(res.baseline, None);
+        assert_eq!(ids(&res), vec![("bound".to_owned(), 0.), ("ball".to_owned(), 2.)]);
+        store.remove("abc");
+        assert!(store.latest("abc").is_none());
+    }
+}
@@ -0,0 +1,163 @@
+use std::str::FromStr;
+use hmac::{Hmac, Mac};
+use log::warn;
+use sha2::Sha256;
+use uuid::Uuid;
+use crate::actor::Actor;
+use crate::websocket_handler::WebSocketConnectionType;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const MIN_SECRET_LENGTH: usize = 32;
+
+/// What a session token grants: the actor may open a websocket connection of the given role to the session.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionToken {
+    pub session_id: String,
+    pub actor_id: String,
+    pub role: WebSocketConnectionType,
+}
+
+impl SessionToken {
+    pub fn for_actor(session_id: &str, actor: &Actor) -> SessionToken {
+        let role = match actor {
+            Actor::Player(p) if p.nr == 1 => WebSocketConnectionType::HOST,
+            Actor::Player(_) => WebSocketConnectionType::PEER,
+            _ => WebSocketConnectionType::OBSERVER,
+        };
+        SessionToken {
+            session_id: session_id.to_owned(),
+            actor_id: actor.id().to_owned(),
+            role,
+        }
+    }
+
+    fn payload(&self) -> String {
+        format!("{}.{}.{}", self.session_id, self.actor_id, self.role)
+    }
+}
+
+/// Signs session tokens as `<session_id>.<actor_id>.<role>.<hex hmac-sha256>`.
+#[derive(Clone)]
+pub struct TokenSigner {
+    secret: Vec<u8>,
+}
+
+impl TokenSigner {
+    pub fn new(secret: &[u8]) -> TokenSigner {
+        TokenSigner { secret: secret.to_vec() }
+    }
+
+    /// Reads the secret from SESSION_TOKEN_SECRET. Without it a random secret is used,
+    /// tokens then don't survive a restart and clients have to join restored sessions again.
+    pub fn from_env() -> Result<TokenSigner, String> {
+        match std::env::var("SESSION_TOKEN_SECRET") {
+            Ok(secret) if secret.len() < MIN_SECRET_LENGTH => {
+                Err(format!("SESSION_TOKEN_SECRET must be at least {} bytes long", MIN_SECRET_LENGTH))
+            }
+            Ok(secret) => Ok(TokenSigner::new(secret.as_bytes())),
+            Err(_) => {
+                warn!("SESSION_TOKEN_SECRET is not set, using a random secret");
+                let secret = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+                Ok(TokenSigner::new(secret.as_bytes()))
+            }
+        }
+    }
+
+    pub fn sign(&self, token: &SessionToken) -> String {
+        let payload = token.payload();
+        let signature = self.mac(&payload).finalize().into_bytes();
+        let signature: String = signature.iter().map(|b| format!("{:02x}", b)).collect();
+        format!("{}.{}", payload, signature)
+    }
+
+    pub fn issue(&self, session_id: &str, actor: &Actor) -> String {
+        self.sign(&SessionToken::for_actor(session_id, actor))
+    }
+
+    pub fn verify(&self, raw: &str) -> Result<SessionToken, String> {
+        let (payload, signature) = raw.rsplit_once('.').ok_or("malformed token")?;
+        let signature = decode_hex(signature).ok_or("malformed token signature")?;
+        // compares in constant time.
+        if self.mac(payload).verify_slice(&signature).is_err() {
+            return Err("invalid token signature".to_owned());
+        }
+        let parts: Vec<&str> = payload.split('.').collect();
+        if parts.len() != 3 {
+            return Err("malformed token payload".to_owned());
+        }
+        let role = WebSocketConnectionType::from_str(parts[2]).map_err(|_| format!("invalid token role: {}", parts[2]))?;
+        Ok(SessionToken {
+            session_id: parts[0].to_owned(),
+            actor_id: parts[1].to_owned(),
+            role,
+        })
+    }
+
+    fn mac(&self, payload: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.secret).expect("hmac accepts keys of any size");
+        mac.update(payload.as_bytes());
+        mac
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use crate::actor::{Actor, Observer, Player};
+    use crate::token::{SessionToken, TokenSigner};
+    use crate::websocket_handler::WebSocketConnectionType;
+
+    const SECRET: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+    #[rstest]
+    #[case(Actor::Player(Player::new(1, "127.0.0.1".to_owned())), WebSocketConnectionType::HOST)]
+    #[case(Actor::Player(Player::new(2, "127.0.0.1".to_owned())), WebSocketConnectionType::PEER)]
+    #[case(Actor::Observer(Observer::new("127.0.0.1".to_owned())), WebSocketConnectionType::OBSERVER)]
+    fn should_verify_issued_tokens(#[case] actor: Actor, #[case] expected_role: WebSocketConnectionType) {
+        let signer = TokenSigner::new(SECRET);
+        let token = signer.issue("abc", &actor);
+        let verified = signer.verify(&token);
+        assert_eq!(verified, Ok(SessionToken {
+            session_id: "abc".to_owned(),
+            actor_id: actor.id().to_owned(),
+            role: expected_role
+        }));
+    }
+
+    #[rstest]
+    #[case::other_role("abc.player_1.host", "abc.player_1.peer")]
+    #[case::other_actor("abc.player_1.host", "abc.player_2.host")]
+    #[case::other_session("abc.player_1.host", "abd.player_1.host")]
+    fn should_reject_tampered_tokens(#[case] signed: &str, #[case] tampered: &str) {
+        let signer = TokenSigner::new(SECRET);
+        let token = signer.sign(&SessionToken {
+            session_id: "abc".to_owned(),
+            actor_id: "player_1".to_owned(),
+            role: WebSocketConnectionType::HOST
+        });
+        assert!(token.starts_with(signed));
+        let tampered = token.replacen(signed, tampered, 1);
+        assert!(signer.verify(&tampered).is_err());
+    }
+
+    #[rstest]
+    #[case::other_secret(TokenSigner::new(b"fedcba9876543210fedcba9876543210").issue("abc", &Actor::Server))]
+    #[case::no_signature("abc.player_1.host".to_owned())]
+    #[case::odd_signature("abc.player_1.host.abc".to_owned())]
+    #[case::empty("".to_owned())]
+    fn should_reject_invalid_tokens(#[case] token: String) {
+        let signer = TokenSigner::new(SECRET);
+        assert!(signer.verify(&token).is_err());
+    }
+}
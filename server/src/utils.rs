@@ -102,3 +102,26 @@ pub mod json_utils {
         return json.replace("\\\"", "\"")
     }
 }
+
+pub mod env_utils {
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    /// Duration in seconds from the env var, the default if it is not set.
+    pub fn secs_from_env(key: &str, default: Duration) -> Result<Duration, String> {
+        match std::env::var(key) {
+            Ok(val) => u64::from_str(&val)
+                .map(Duration::from_secs)
+                .map_err(|e| format!("invalid {}={}: {}", key, val, e)),
+            Err(_) => Ok(default),
+        }
+    }
+
+    /// Number from the env var, the default if it is not set.
+    pub fn usize_from_env(key: &str, default: usize) -> Result<usize, String> {
+        match std::env::var(key) {
+            Ok(val) => usize::from_str(&val).map_err(|e| format!("invalid {}={}: {}", key, val, e)),
+            Err(_) => Ok(default),
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use std::fmt::{Debug};
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
 use std::str::FromStr;
 use std::sync::Arc;
 
@@ -8,17 +9,26 @@ use hyper_tungstenite::HyperWebsocket;
 use hyper_tungstenite::tungstenite::{Error, Message};
 use log::{debug, error, info, trace};
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use serde_json::{json, Value};
+use rskafka::client::consumer::StartOffset;
 use tokio::sync::Mutex;
 use tokio::task;
 
-use pong::event::event::{EventWriter};
+use pong::codec::codec::{self, WireFormat};
+use pong::event::event::{EventWrapper, EventWriter};
 use pong::game_field::{GameState, Input};
 
 use crate::actor::{Actor};
-use crate::event::{HeartBeatEventPayload, MoveEventBatchPayload, MoveEventPayload, SessionEvent, SessionEventListDTO, SessionEventPayload, SessionEventType, StatusEventPayload, TickEvent};
-use crate::session::{Session, SessionState};
+use crate::event::{ChatContent, Emote, HeartBeatEventPayload, InputEventPayload, MoveEventBatchPayload, MoveEventPayload, SessionEvent, SessionEventListDTO, SessionEventType, StatusEventPayload, TickEvent};
+use crate::session::{Session, SessionMode, SessionState};
 use crate::session_manager::{SessionManager, SessionWriter};
+use crate::rematch::{accept_rematch, created_status};
+use crate::snapshot::{SnapshotBaseline, SnapshotStore};
+use crate::token::TokenSigner;
+use crate::utils::time_utils::now;
+
+// players report their activity to the session manager at most this often, in ms.
+const ACTIVITY_REPORT_INTERVAL: u128 = 1_000;
 
 #[async_trait]
 pub trait WebsocketHandler {
@@ -29,6 +39,7 @@ pub struct DefaultWebsocketHandler {
     websocket_session: WebSocketSession,
     websocket: HyperWebsocket,
     session_manager: Arc<Mutex<SessionManager>>,
+    token_signer: Arc<TokenSigner>,
 }
 
 impl DefaultWebsocketHandler {
@@ -36,9 +47,10 @@ impl DefaultWebsocketHandler {
         websocket_session: WebSocketSession,
         websocket: HyperWebsocket,
         session_manager: Arc<Mutex<SessionManager>>,
+        token_signer: Arc<TokenSigner>,
     ) -> DefaultWebsocketHandler {
         DefaultWebsocketHandler {
-            websocket_session, websocket, session_manager
+            websocket_session, websocket, session_manager, token_signer
         }
     }
 }
@@ -52,11 +64,21 @@ impl WebsocketHandler for DefaultWebsocketHandler {
         let websocket = self.websocket.await?;
         let (mut websocket_writer, mut websocket_reader) = websocket.split();
 
+        let session_id = self.websocket_session.session.session_id.clone();
+        let (generation, is_reconnect, snapshot_store) = {
+            let mut session_manager = self.session_manager.lock().await;
+            let (generation, was_connected) = session_manager.connect(&session_id, self.websocket_session.actor.id());
+            let is_player = self.websocket_session.connection_type != WebSocketConnectionType::OBSERVER;
+            (generation, was_connected && is_player, session_manager.snapshot_store())
+        };
+        // reconnecting players get the current state right away, they don't need to replay the session.
+        let start_offset = if is_reconnect { StartOffset::Latest } else { StartOffset::Earliest };
         let event_handler_pair = async {
             let session_manager = self.session_manager.lock().await;
-            session_manager.split(
-                &self.websocket_session.session.session_id,
-                self.websocket_session.connection_type.get_topics(),
+            session_manager.split_from(
+                &session_id,
+                self.websocket_session.connection_type.get_topics(&self.websocket_session.session.mode),
+                start_offset,
             ).await
         }.await;
         if let Err(_) = event_handler_pair {
@@ -71,24 +93,54 @@ impl WebsocketHandler for DefaultWebsocketHandler {
         }
 
         let (mut event_reader, mut event_writer) = event_handler_pair.unwrap();
+        let mut snapshot_baseline = SnapshotBaseline::new();
+        let mut initial_events = vec![];
+        if is_reconnect {
+            let reconnected = self.session_manager.lock().await.reconnect_session(&session_id, self.websocket_session.actor.clone()).await;
+            if let Err(e) = reconnected {
+                error(&self.websocket_session, &format!("failed to reconnect: {}", e));
+                return Err(Error::ConnectionClosed);
+            }
+            initial_events = reconnect_events(&reconnected.unwrap(), &self.websocket_session, &snapshot_store, &mut snapshot_baseline);
+        }
         let websocket_session_read_copy = self.websocket_session.clone();
+        let session_manager = Arc::clone(&self.session_manager);
+        let token_signer = Arc::clone(&self.token_signer);
         tokio::spawn(async move {
             info(
                 &websocket_session_read_copy,
                 "ready to read messages from ws connection",
             );
+            // the host keeps sending its final state, the result is only reported once.
+            let mut status_written = false;
+            let reports_activity = websocket_session_read_copy.connection_type != WebSocketConnectionType::OBSERVER;
+            let mut last_activity_report = 0;
             while let Some(message) = websocket_reader.next().await {
                 if let Err(e) = message {
                     error(&websocket_session_read_copy, &format!("ws message read failed for session: {:?}", e));
                     let reason = format!("ws closed: {:?}", e);
-                    write_session_close_event(&mut event_writer, &websocket_session_read_copy, reason.as_str()).await;
+                    handle_disconnect(&session_manager, &websocket_session_read_copy, generation, &reason, &mut event_writer).await;
                     break;
                 }
                 let message = message.unwrap();
+                if reports_activity && now() - last_activity_report >= ACTIVITY_REPORT_INTERVAL {
+                    session_manager.lock().await.touch(&websocket_session_read_copy.session.session_id);
+                    last_activity_report = now();
+                }
                 trace(&websocket_session_read_copy, &format!("read new message from websocket: {:?}", message));
+                let message = match message {
+                    Message::Binary(bytes) => match decode_binary_ws_message(&bytes) {
+                        Ok(json) => Message::Text(json),
+                        Err(e) => {
+                            error(&websocket_session_read_copy, &format!("Failed to decode binary ws message: {}", e));
+                            continue;
+                        }
+                    },
+                    other => other
+                };
                 match message {
                     Message::Text(msg) => {
-                        let ws_message = deserialize_ws_event(&msg, &websocket_session_read_copy.connection_type);
+                        let ws_message = deserialize_ws_event(&msg, &websocket_session_read_copy.connection_type, &websocket_session_read_copy.session.mode);
                         trace(&websocket_session_read_copy, "received ws event to persist to kafka");
                         if let Err(e) = ws_message {
                             error(&websocket_session_read_copy, &format!("Failed to deserialize ws message to event: {:?}", e));
@@ -109,11 +161,24 @@ impl WebsocketHandler for DefaultWebsocketHandler {
                                 match session_snapshot {
                                     SessionSnapshot::Host(_, payload) => {
                                         trace(&websocket_session_read_copy, "received message is HOST snapshot");
+                                        let status = match payload.state.winner {
+                                            Some(_) if !status_written => Some(StatusEventPayload::from(&websocket_session_read_copy.session, &payload.state)),
+                                            _ => None
+                                        };
+                                        snapshot_store.update(&payload);
                                         let write_res = write_events(vec![payload], "host_tick", &mut event_writer).await;
                                         if !write_res {
                                             error(&websocket_session_read_copy, "failed to write HOST tick");
                                         }
                                         any_error = !write_res;
+                                        if let Some(status) = status {
+                                            info(&websocket_session_read_copy, &format!("game is over, reporting result {:?}", status));
+                                            status_written = write_events(vec![status], "status", &mut event_writer).await;
+                                            if !status_written {
+                                                error(&websocket_session_read_copy, "failed to write final status");
+                                            }
+                                            any_error = any_error || !status_written;
+                                        }
                                     },
                                     SessionSnapshot::Peer(session_id, payload) => {
                                         trace(&websocket_session_read_copy, "received message is PEER snapshot");
@@ -123,8 +188,23 @@ impl WebsocketHandler for DefaultWebsocketHandler {
                                         }
                                         any_error = !write_res;
                                     },
+                                    SessionSnapshot::Input(session_id, payload) => {
+                                        trace(&websocket_session_read_copy, "received message is INPUT snapshot");
+                                        // the game loop moves the paddle of player_id, a connection can only send its own inputs.
+                                        let event = input_event(session_id, payload, websocket_session_read_copy.actor.id());
+                                        if let Err(e) = event {
+                                            error(&websocket_session_read_copy, &format!("{} - skip.", e));
+                                            continue;
+                                        }
+                                        let event = event.unwrap();
+                                        let write_res = write_events(vec![event], "input", &mut event_writer).await;
+                                        if !write_res {
+                                            error(&websocket_session_read_copy, "failed to write INPUT");
+                                        }
+                                        any_error = !write_res;
+                                    },
                                     SessionSnapshot::Observer(_, _) => {
-                                        // noop
+                                        // noop, observers interact through chat messages and emotes.
                                     }
                                 }
                                 if any_error {
@@ -135,10 +215,16 @@ impl WebsocketHandler for DefaultWebsocketHandler {
                             },
                             WebsocketEvent::HeartBeat(session_id, heartbeat) => {
                                 trace(&websocket_session_read_copy, "received message is heartbeat");
+                                // presence is tracked per actor, a connection can only report its own.
+                                if heartbeat.player_id != websocket_session_read_copy.actor.id() {
+                                    error(&websocket_session_read_copy, &format!("websocket sent heartbeat of other actor {} - skip.", heartbeat.player_id));
+                                    continue;
+                                }
                                 let event = HeartBeatEventPayload {
                                     session_id: session_id.clone(),
                                     actor_id: heartbeat.player_id,
-                                    ts: heartbeat.ts
+                                    ts: heartbeat.ts,
+                                    disconnected: false,
                                 };
                                 let res = write_events(vec![event], "heart_beat", &mut event_writer).await;
                                 if !res {
@@ -147,6 +233,30 @@ impl WebsocketHandler for DefaultWebsocketHandler {
                                     debug(&websocket_session_read_copy, "successfully persisted heartbeat");
                                 }
                             }
+                            WebsocketEvent::Rematch(session_id) => {
+                                if websocket_session_read_copy.connection_type == WebSocketConnectionType::OBSERVER {
+                                    error(&websocket_session_read_copy, "observers can't accept a rematch - skip.");
+                                    continue;
+                                }
+                                // both players learn about the rematch from the session events of this session.
+                                let actor_id = websocket_session_read_copy.actor.id();
+                                match accept_rematch(&session_manager, &token_signer, &session_id, actor_id).await {
+                                    Ok(status) => info(&websocket_session_read_copy, &format!("player {} accepted rematch: {:?}", actor_id, status)),
+                                    Err(e) => error(&websocket_session_read_copy, &format!("failed to accept rematch: {}", e)),
+                                }
+                            }
+                            WebsocketEvent::Chat(session_id, content) => {
+                                // the sender is always the actor of the connection, every actor of the session may chat.
+                                let actor_id = websocket_session_read_copy.actor.id();
+                                let event = session_manager.lock().await.moderate_chat(&session_id, actor_id, content);
+                                if let Err(e) = event {
+                                    info(&websocket_session_read_copy, &format!("rejected chat message of {}: {}", actor_id, e));
+                                    continue;
+                                }
+                                if !write_events(vec![event.unwrap()], "chat", &mut event_writer).await {
+                                    error(&websocket_session_read_copy, "failed to write chat message");
+                                }
+                            }
                         }
                     }
                     Message::Close(msg) => {
@@ -163,7 +273,7 @@ impl WebsocketHandler for DefaultWebsocketHandler {
                         };
 
                         let reason = format!("ws closed: {}", reason);
-                        write_session_close_event(&mut event_writer, &websocket_session_read_copy, reason.as_str()).await;
+                        handle_disconnect(&session_manager, &websocket_session_read_copy, generation, &reason, &mut event_writer).await;
                         break;
                     }
                     _ => {}
@@ -175,11 +285,20 @@ impl WebsocketHandler for DefaultWebsocketHandler {
             info!("ws receiver terminated")
         });
         let websocket_session_write_copy = self.websocket_session.clone();
+        let session_manager = Arc::clone(&self.session_manager);
+        let token_signer = Arc::clone(&self.token_signer);
         tokio::spawn(async move {
             debug(
                 &websocket_session_write_copy,
                 "ready to read messages from kafka"
             );
+            if !initial_events.is_empty() {
+                info(&websocket_session_write_copy, "sending current state to reconnected player");
+                if let Err(e) = websocket_writer.send(to_ws_message(&websocket_session_write_copy.wire_format, &initial_events)).await {
+                    error(&websocket_session_write_copy, &format!("Failed to send current state to websocket: {:?}", e));
+                    return;
+                }
+            }
             loop {
                 trace(&websocket_session_write_copy, "reading messages from kafka");
                 // TODO: Should perform more filtering, e.g. inputs of player are not relevant.
@@ -194,34 +313,36 @@ impl WebsocketHandler for DefaultWebsocketHandler {
                 if events.len() == 0 {
                     trace(&websocket_session_write_copy, "no new messages from kafka.");
                 } else {
-                    let mut session_events = events.iter().filter(|e| e.topic == "session")
+                    let session_closed = events.iter()
+                        .filter(|e| e.topic == "session")
+                        .any(|e| matches!(serde_json::from_str::<SessionEvent>(&e.event), Ok(SessionEvent::Closed(_))));
+                    let mut session_events = events.iter().filter(|e| e.topic == "session" || e.topic == "presence" || e.topic == "chat")
                         .map(|e| WebsocketEventDTO {
-                            topic: "session".to_owned(),
+                            topic: e.topic.clone(),
                             event: e.event.clone()
                         })
                         .collect();
 
-                    let mut tick_events = match websocket_session_write_copy.connection_type {
-                        WebSocketConnectionType::HOST => {
-                            events.iter().filter(|e| e.topic == "peer_tick")
-                                .map(|e| WebsocketEventDTO {topic: "tick".to_owned(), event: e.event.clone()})
-                                .collect()
-                        },
-                        _ => {
-                            events.iter().filter(|e| e.topic == "host_tick")
-                                .map(|e| WebsocketEventDTO {topic: "tick".to_owned(), event: e.event.clone()})
-                                .collect()
-                        }
-                    };
+                    let tick_topic = websocket_session_write_copy.connection_type.get_tick_topic(&websocket_session_write_copy.session.mode);
+                    let mut tick_events = events.iter().filter(|e| e.topic == tick_topic)
+                        .map(|e| match tick_topic {
+                            "host_tick" => relay_host_tick(&e.event, &mut snapshot_baseline),
+                            _ => e.event.clone()
+                        })
+                        .map(|event| WebsocketEventDTO {topic: "tick".to_owned(), event})
+                        .collect();
+
+                    let mut rematch_events = rematch_events(&events, &websocket_session_write_copy, &session_manager, &token_signer).await;
 
                     let mut event_dtos = vec![];
                     event_dtos.append(&mut session_events);
                     event_dtos.append(&mut tick_events);
+                    event_dtos.append(&mut rematch_events);
 
                     trace(&websocket_session_write_copy, &format!("{} new messages from kafka.", event_dtos.len()));
                     let json = serde_json::to_string(&event_dtos).unwrap();
                     trace(&websocket_session_write_copy, &format!("sending msg batch to client: {}", json));
-                    let message = Message::from(json);
+                    let message = to_ws_message(&websocket_session_write_copy.wire_format, &event_dtos);
                     trace(&websocket_session_write_copy, "sending kafka messages through websocket.");
                     let send_res = websocket_writer.send(message).await;
                     if let Err(e) = send_res {
@@ -233,6 +354,13 @@ impl WebsocketHandler for DefaultWebsocketHandler {
                         );
                         break;
                     }
+                    if session_closed {
+                        info(&websocket_session_write_copy, "session was closed, closing ws connection");
+                        if let Err(e) = websocket_writer.close().await {
+                            error(&websocket_session_write_copy, &format!("Failed to close websocket: {:?}", e));
+                        }
+                        break;
+                    }
                 }
 
                 trace(&websocket_session_write_copy, "kafka read done, waiting for next cycle.");
@@ -261,18 +389,150 @@ fn error(websocket_session: &WebSocketSession, msg: &str) {
     error!("[{}] {}", websocket_session.session.session_id, msg)
 }
 
-async fn write_session_close_event(event_writer: &mut SessionWriter, websocket_session: &WebSocketSession, close_reason: &str) {
-    let mut updated_session = websocket_session.session.clone();
-    updated_session.state = SessionState::CLOSED;
-    let session_closed_event = SessionEvent::Closed(SessionEventPayload {
-        actor: websocket_session.actor.clone(),
-        session: updated_session,
-        reason: format!("ws closed: {}", close_reason),
-    });
-    let msg = json!(session_closed_event).to_string();
-    let session_event_write_res = event_writer.write_to_session("session", vec![&msg]).await;
-    if let Err(e) = session_event_write_res {
-        eprintln!("Failed to write session closed event: {0}", e)
+// binary messages carry the payload as nested value, the json wrapper expects it as string.
+fn decode_binary_ws_message(bytes: &[u8]) -> Result<String, String> {
+    let mut message = codec::decode_value(bytes)?;
+    if let Some(payload) = message.get_mut("payload") {
+        if !payload.is_string() {
+            *payload = Value::String(payload.to_string());
+        }
+    }
+    Ok(message.to_string())
+}
+
+// the events are embedded as values instead of json strings, so that the client can decode the batch at once.
+fn encode_binary_ws_events(event_dtos: &[WebsocketEventDTO]) -> Vec<u8> {
+    let events = event_dtos.iter()
+        .map(|dto| {
+            let event = serde_json::from_str::<Value>(&dto.event).unwrap_or_else(|_| Value::String(dto.event.clone()));
+            json!({"topic": dto.topic, "event": event})
+        })
+        .collect::<Vec<Value>>();
+    codec::encode_value(&Value::Array(events))
+}
+
+fn to_ws_message(wire_format: &WireFormat, event_dtos: &[WebsocketEventDTO]) -> Message {
+    match wire_format {
+        WireFormat::Json => Message::from(serde_json::to_string(event_dtos).unwrap()),
+        WireFormat::Binary => Message::Binary(encode_binary_ws_events(event_dtos)),
+    }
+}
+
+// the reconnected session and the latest full host snapshot, the baseline continues from there.
+// Hosts of host sessions run the engine themselves, they only get the session.
+fn reconnect_events(
+    reconnected: &SessionEvent,
+    websocket_session: &WebSocketSession,
+    snapshot_store: &SnapshotStore,
+    snapshot_baseline: &mut SnapshotBaseline,
+) -> Vec<WebsocketEventDTO> {
+    let mut events = vec![WebsocketEventDTO {
+        topic: "session".to_owned(),
+        event: serde_json::to_string(reconnected).unwrap(),
+    }];
+    let tick_topic = websocket_session.connection_type.get_tick_topic(&websocket_session.session.mode);
+    if tick_topic != "host_tick" {
+        return events;
+    }
+    if let Some(snapshot) = snapshot_store.latest(&websocket_session.session.session_id) {
+        let snapshot = snapshot_baseline.apply(snapshot);
+        events.push(WebsocketEventDTO { topic: "tick".to_owned(), event: serde_json::to_string(&snapshot).unwrap() });
+    }
+    events
+}
+
+// every actor gets its own place in a rematch, including a token to connect to it.
+async fn rematch_events(
+    events: &[EventWrapper],
+    websocket_session: &WebSocketSession,
+    session_manager: &Arc<Mutex<SessionManager>>,
+    token_signer: &TokenSigner,
+) -> Vec<WebsocketEventDTO> {
+    let rematch_ids = events.iter()
+        .filter(|e| e.topic == "session")
+        .filter_map(|e| match serde_json::from_str::<SessionEvent>(&e.event) {
+            Ok(SessionEvent::Rematched(payload)) => payload.session.rematch,
+            _ => None
+        })
+        .collect::<Vec<String>>();
+    let mut dtos = vec![];
+    for rematch_id in rematch_ids {
+        let rematch = session_manager.lock().await.get_session(&rematch_id);
+        let status = rematch.and_then(|r| created_status(&r, websocket_session.actor.id(), token_signer));
+        if status.is_none() {
+            error(websocket_session, &format!("actor is not part of rematch {}", rematch_id));
+            continue;
+        }
+        dtos.push(WebsocketEventDTO { topic: "rematch".to_owned(), event: serde_json::to_string(&status.unwrap()).unwrap() });
+    }
+    dtos
+}
+
+fn input_event(session_id: String, payload: PeerSessionSnapshotDTO, actor_id: &str) -> Result<InputEventPayload, String> {
+    if payload.player_id != actor_id {
+        return Err(format!("websocket sent inputs of other player {}", payload.player_id));
+    }
+    Ok(InputEventPayload {
+        session_id,
+        inputs: payload.inputs,
+        player_id: payload.player_id,
+        ts: payload.ts,
+        input_seq: payload.input_seq
+    })
+}
+
+// host ticks are deltas, the baseline makes sure the client always gets a snapshot it can apply.
+fn relay_host_tick(event: &str, snapshot_baseline: &mut SnapshotBaseline) -> String {
+    let snapshot = serde_json::from_str::<HostSessionSnapshotDTO>(event);
+    if let Err(e) = snapshot {
+        error!("failed to deserialize host tick, forwarding it unchanged: {:?}", e);
+        return event.to_owned();
+    }
+    let snapshot = snapshot_baseline.apply(snapshot.unwrap());
+    serde_json::to_string(&snapshot).unwrap()
+}
+
+// Players of running sessions can reconnect until the presence grace period is over, their drop is only reported.
+// Pending sessions are closed right away, observers can leave without affecting the session.
+async fn handle_disconnect(
+    session_manager: &Arc<Mutex<SessionManager>>,
+    websocket_session: &WebSocketSession,
+    generation: u64,
+    reason: &str,
+    event_writer: &mut SessionWriter,
+) {
+    if websocket_session.connection_type == WebSocketConnectionType::OBSERVER {
+        info(websocket_session, &format!("observer left: {}", reason));
+        return;
+    }
+    let mut locked = session_manager.lock().await;
+    let session_id = &websocket_session.session.session_id;
+    let actor_id = websocket_session.actor.id();
+    if !locked.is_current_connection(session_id, actor_id, generation) {
+        info(websocket_session, &format!("replaced connection of {} dropped: {}", actor_id, reason));
+        return;
+    }
+    match locked.get_session(session_id).map(|s| s.state) {
+        Some(SessionState::RUNNING) => {
+            drop(locked);
+            info(websocket_session, &format!("player {} disconnected, waiting for reconnect: {}", actor_id, reason));
+            let event = HeartBeatEventPayload {
+                session_id: session_id.clone(),
+                actor_id: actor_id.to_owned(),
+                ts: now(),
+                disconnected: true,
+            };
+            if !write_events(vec![event], "heart_beat", event_writer).await {
+                error(websocket_session, "failed to report disconnect");
+            }
+        }
+        Some(SessionState::PENDING) => {
+            let res = locked.close_session(session_id, websocket_session.actor.clone(), reason).await;
+            if let Err(e) = res {
+                error(websocket_session, &format!("Failed to close session: {}", e));
+            }
+        }
+        _ => {}
     }
 }
 
@@ -280,7 +540,8 @@ async fn write_session_close_event(event_writer: &mut SessionWriter, websocket_s
 pub struct WebSocketSession {
     pub connection_type: WebSocketConnectionType,
     pub session: Session,
-    pub actor: Actor
+    pub actor: Actor,
+    pub wire_format: WireFormat,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -303,18 +564,35 @@ impl FromStr for WebSocketConnectionType {
     }
 }
 
+impl Display for WebSocketConnectionType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            WebSocketConnectionType::HOST => "host",
+            WebSocketConnectionType::PEER => "peer",
+            WebSocketConnectionType::OBSERVER => "observer",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 impl WebSocketConnectionType {
-    pub fn get_topics(&self) -> &[&str] {
-        match self {
-            WebSocketConnectionType::HOST => &["peer_tick", "session"],
-            WebSocketConnectionType::PEER | WebSocketConnectionType::OBSERVER => {
-                &["host_tick", "session"]
-            }
+    pub fn get_topics(&self, mode: &SessionMode) -> &[&str] {
+        match self.get_tick_topic(mode) {
+            "peer_tick" => &["peer_tick", "session", "presence", "chat"],
+            _ => &["host_tick", "session", "presence", "chat"],
+        }
+    }
+
+    // In server mode the server publishes the authoritative ticks as host ticks for every connection.
+    pub fn get_tick_topic(&self, mode: &SessionMode) -> &str {
+        match (self, mode) {
+            (WebSocketConnectionType::HOST, SessionMode::HOST) => "peer_tick",
+            _ => "host_tick",
         }
     }
 }
 
-fn deserialize_ws_event(message: &str, connection_type: &WebSocketConnectionType) -> Result<WebsocketEvent, String> {
+fn deserialize_ws_event(message: &str, connection_type: &WebSocketConnectionType, mode: &SessionMode) -> Result<WebsocketEvent, String> {
     let deserialized = serde_json::from_str::<WebsocketMessageWrapper>(message);
     if let Err(e) = deserialized {
         let err = format!("Failed to deserialize ws message {}: {:?}", message, e);
@@ -324,15 +602,28 @@ fn deserialize_ws_event(message: &str, connection_type: &WebSocketConnectionType
     let deserialized = deserialized.unwrap();
     match deserialized.msg_type {
         WebsocketEventType::SessionSnapshot => {
-            deserialize_event_snapshot(&deserialized.payload, connection_type).map(|s| WebsocketEvent::Snapshot(s.session_id().to_owned(), s))
+            deserialize_event_snapshot(&deserialized.payload, connection_type, mode).map(|s| WebsocketEvent::Snapshot(s.session_id().to_owned(), s))
         },
         WebsocketEventType::HeartBeat => {
             serde_json::from_str::<HeartBeatMessage>(&deserialized.payload).map_err(|e| e.to_string()).map(|h| WebsocketEvent::HeartBeat(h.session_id.clone(), h))
         }
+        WebsocketEventType::Rematch => {
+            serde_json::from_str::<RematchMessage>(&deserialized.payload).map_err(|e| e.to_string()).map(|r| WebsocketEvent::Rematch(r.session_id))
+        }
+        WebsocketEventType::Chat => {
+            serde_json::from_str::<ChatMessage>(&deserialized.payload).map_err(|e| e.to_string()).map(|c| WebsocketEvent::Chat(c.session_id, ChatContent::Text { text: c.text }))
+        }
+        WebsocketEventType::Emote => {
+            serde_json::from_str::<EmoteMessage>(&deserialized.payload).map_err(|e| e.to_string()).map(|e| WebsocketEvent::Chat(e.session_id, ChatContent::Emote { emote: e.emote }))
+        }
     }
 }
 
-fn deserialize_event_snapshot(serialized_snapshot: &str, connection_type: &WebSocketConnectionType) -> Result<SessionSnapshot, String> {
+fn deserialize_event_snapshot(serialized_snapshot: &str, connection_type: &WebSocketConnectionType, mode: &SessionMode) -> Result<SessionSnapshot, String> {
+    if *mode == SessionMode::SERVER && *connection_type != WebSocketConnectionType::OBSERVER {
+        // players of server sessions only send their inputs.
+        return serde_json::from_str::<PeerSessionSnapshotDTO>(serialized_snapshot).map_err(|e| e.to_string()).map(|s| SessionSnapshot::Input(s.session_id.to_owned(), s));
+    }
     match connection_type {
         WebSocketConnectionType::HOST => serde_json::from_str::<HostSessionSnapshotDTO>(serialized_snapshot).map_err(|e| e.to_string()).map(|s| SessionSnapshot::Host(s.session_id.to_owned(), s)),
         WebSocketConnectionType::PEER => serde_json::from_str::<PeerSessionSnapshotDTO>(serialized_snapshot).map_err(|e| e.to_string()).map(|s| SessionSnapshot::Peer(s.session_id.to_owned(), s)),
@@ -342,7 +633,9 @@ fn deserialize_event_snapshot(serialized_snapshot: &str, connection_type: &WebSo
 
 enum WebsocketEvent {
     Snapshot(String, SessionSnapshot),
-    HeartBeat(String, HeartBeatMessage)
+    HeartBeat(String, HeartBeatMessage),
+    Rematch(String),
+    Chat(String, ChatContent)
 }
 
 impl WebsocketEvent {
@@ -350,6 +643,8 @@ impl WebsocketEvent {
         match self {
             WebsocketEvent::HeartBeat(s, _) => &s,
             WebsocketEvent::Snapshot(s, _) => &s,
+            WebsocketEvent::Rematch(s) => &s,
+            WebsocketEvent::Chat(s, _) => &s,
         }
     }
 }
@@ -362,7 +657,24 @@ struct WebsocketMessageWrapper {
 
 #[derive(Deserialize)]
 enum WebsocketEventType {
-    HeartBeat, SessionSnapshot
+    HeartBeat, SessionSnapshot, Rematch, Chat, Emote
+}
+
+#[derive(Deserialize)]
+struct RematchMessage {
+    pub session_id: String
+}
+
+#[derive(Deserialize)]
+struct ChatMessage {
+    pub session_id: String,
+    pub text: String
+}
+
+#[derive(Deserialize)]
+struct EmoteMessage {
+    pub session_id: String,
+    pub emote: Emote
 }
 
 #[derive(Deserialize)]
@@ -375,6 +687,7 @@ struct HeartBeatMessage {
 enum SessionSnapshot {
     Host(String, HostSessionSnapshotDTO),
     Peer(String, PeerSessionSnapshotDTO),
+    Input(String, PeerSessionSnapshotDTO),
     Observer(String, ObserverSessionSnapshotDTO)
 }
 
@@ -383,19 +696,31 @@ impl SessionSnapshot {
         match self {
             SessionSnapshot::Host(id, _) => id,
             SessionSnapshot::Peer(id, _) => id,
+            SessionSnapshot::Input(id, _) => id,
             SessionSnapshot::Observer(id, _) => id
         }
     }
 }
 
-#[derive(Deserialize, Serialize, Debug)]
-struct HostSessionSnapshotDTO {
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct HostSessionSnapshotDTO {
     pub session_id: String,
     pub inputs: Vec<Input>,
     pub state: GameState,
     pub objects: Vec<GameObjectStateDTO>,
     pub player_id: String,
-    pub ts: u128
+    pub ts: u128,
+    // checksum of the host's field, peers can compare it with their own simulation.
+    #[serde(default)]
+    pub checksum: Option<u32>,
+    #[serde(default)]
+    pub seq: u64,
+    // seq of the snapshot the objects are a delta to, None if the snapshot contains every object.
+    #[serde(default)]
+    pub baseline: Option<u64>,
+    // last input sequence number of each player that went into this tick.
+    #[serde(default)]
+    pub acked_inputs: HashMap<String, u64>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -403,7 +728,9 @@ struct PeerSessionSnapshotDTO {
     pub session_id: String,
     pub inputs: Vec<Input>,
     pub player_id: String,
-    pub ts: u128
+    pub ts: u128,
+    #[serde(default)]
+    pub input_seq: u64
 }
 
 #[derive(Deserialize)]
@@ -413,8 +740,8 @@ struct ObserverSessionSnapshotDTO {
     pub ts: u128
 }
 
-#[derive(Deserialize, Serialize, Debug)]
-struct GameObjectStateDTO {
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GameObjectStateDTO {
     pub id: String,
     pub orientation_x: f64,
     pub orientation_y: f64,
@@ -476,3 +803,40 @@ struct WebsocketEventDTO {
     pub topic: String,
     pub event: String
 }
+
+#[cfg(test)]
+mod tests {
+    use pong::codec::codec;
+    use serde_json::{json, Value};
+
+    use crate::websocket_handler::{decode_binary_ws_message, encode_binary_ws_events, input_event, PeerSessionSnapshotDTO, WebsocketEventDTO, WebsocketMessageWrapper};
+
+    #[test]
+    fn should_decode_binary_message_to_json_wrapper() {
+        let message = json!({"msg_type": "HeartBeat", "payload": {"player_id": "p", "session_id": "abc", "ts": 1}});
+        let json = decode_binary_ws_message(&codec::encode_value(&message)).unwrap();
+        let wrapper = serde_json::from_str::<WebsocketMessageWrapper>(&json).unwrap();
+        let payload = serde_json::from_str::<Value>(&wrapper.payload).unwrap();
+        assert_eq!(payload, message["payload"]);
+    }
+
+    #[test]
+    fn should_embed_events_in_binary_batch() {
+        let dtos = vec![WebsocketEventDTO { topic: "tick".to_owned(), event: "{\"ts\":1}".to_owned() }];
+        let decoded = codec::decode_value(&encode_binary_ws_events(&dtos)).unwrap();
+        assert_eq!(decoded, json!([{"topic": "tick", "event": {"ts": 1}}]));
+    }
+
+    #[test]
+    fn should_reject_inputs_of_other_players() {
+        let payload = |player_id: &str| PeerSessionSnapshotDTO {
+            session_id: "abc".to_owned(),
+            inputs: vec![],
+            player_id: player_id.to_owned(),
+            ts: 1,
+            input_seq: 1
+        };
+        assert_eq!(input_event("abc".to_owned(), payload("player_1"), "player_1").map(|e| e.player_id), Ok("player_1".to_owned()));
+        assert!(input_event("abc".to_owned(), payload("player_2"), "player_1").is_err());
+    }
+}